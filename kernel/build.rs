@@ -40,6 +40,12 @@ _app_num:
     }
     writeln!(f, "	.quad app{}_end", apps.len() - 1).unwrap();
 
+    // 应用名，作为启动时的 argv[0]
+    writeln!(f, "\n	.global _app_names\n_app_names:").unwrap();
+    for app in apps.iter() {
+        writeln!(f, "	.string \"{}\"", app).unwrap();
+    }
+
     for i in 0..apps.len() {
         writeln!(
             f,
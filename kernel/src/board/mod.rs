@@ -0,0 +1,62 @@
+// 板级支持
+// 内核对所运行机器的假设都通过 Board 特征给出，有设备树时以设备树为准，见 machine.rs
+// 板子在编译时选择：默认为 QEMU virt，启用 board-sifive-u 特性时为 QEMU sifive_u
+
+use crate::{
+    machine::{Device, Region},
+    sbi,
+};
+
+mod qemu_exit;
+#[cfg(feature = "board-sifive-u")]
+mod sifive_u;
+#[cfg(not(feature = "board-sifive-u"))]
+mod virt;
+
+pub trait Board {
+    fn name(&self) -> &'static str;
+
+    fn console_putchar(&self, c: u8) {
+        sbi::console_putchar(c as usize);
+    }
+
+    // 控制台输入的下一个字节，还没有输入时为 None
+    fn console_getchar(&self) -> Option<u8> {
+        sbi::console_getchar()
+    }
+
+    // 关机，非零的 code 作为失败报告给宿主机
+    // SRST 只能报告成功或失败，因此只用于 0 与 1，其他退出码以及固件不支持 SRST 时使用关机设备
+    fn exit(&self, code: u32) -> ! {
+        if code <= 1 {
+            sbi::shutdown(code != 0);
+        }
+        self.power_off(code)
+    }
+
+    fn exit_success(&self) -> ! {
+        self.exit(0)
+    }
+
+    // 通过板子的关机设备关机并报告 code
+    fn power_off(&self, code: u32) -> !;
+
+    // 设备树没有描述时，内核所在的内存
+    fn memory(&self) -> Region;
+
+    // 设备树没有描述时的 PLIC
+    fn irq_controller(&self) -> Device;
+
+    // 设备树没有给出时 time 寄存器的频率
+    fn timer_freq(&self) -> usize;
+
+    // 用于关机的设备，会映射到内核地址空间
+    fn exit_device(&self) -> Option<Region> {
+        None
+    }
+}
+
+#[cfg(not(feature = "board-sifive-u"))]
+pub static BOARD: virt::QemuVirt = virt::QemuVirt;
+#[cfg(feature = "board-sifive-u")]
+pub static BOARD: sifive_u::QemuSifiveU = sifive_u::QemuSifiveU;
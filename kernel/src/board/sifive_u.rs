@@ -0,0 +1,63 @@
+use crate::{
+    machine::{machine, Device, Region},
+    mem_layout::PAGE_SIZE,
+};
+
+use super::{
+    qemu_exit::{QEMUExit, RISCV64},
+    Board,
+};
+
+const MEMORY: Region = Region {
+    base: 0x8000_0000,
+    size: 0x800_0000,
+};
+const PLIC: Device = Device {
+    reg: Region {
+        base: 0x0c00_0000,
+        size: 0x400_0000,
+    },
+    irq: None,
+};
+// 与 virt 相同，QEMU 在 0x100000 放置了 sifive,test0 设备
+const TEST: Region = Region {
+    base: 0x10_0000,
+    size: PAGE_SIZE,
+};
+// RTCCLK 为 1MHz
+const TIMER_FREQ: usize = 1_000_000;
+
+// QEMU 的 sifive_u 机器，hart 0 是没有 S 模式的 E51 监控核，内核运行在 hart 1 起的 U54 上
+// 通过 sifive_test 设备关机，退出码会报告给 QEMU
+pub struct QemuSifiveU;
+
+impl Board for QemuSifiveU {
+    fn name(&self) -> &'static str {
+        "qemu-sifive-u"
+    }
+
+    fn power_off(&self, code: u32) -> ! {
+        let test = RISCV64::new(machine().exit_device().map_or(TEST.base, |r| r.base) as u64);
+        if code == 0 {
+            test.exit_success()
+        } else {
+            test.exit(code)
+        }
+    }
+
+    fn memory(&self) -> Region {
+        MEMORY
+    }
+
+    fn irq_controller(&self) -> Device {
+        PLIC
+    }
+
+    fn timer_freq(&self) -> usize {
+        TIMER_FREQ
+    }
+
+    fn exit_device(&self) -> Option<Region> {
+        Some(TEST)
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    machine::{machine, Device, Region},
+    mem_layout::PAGE_SIZE,
+};
+
+use super::{
+    qemu_exit::{QEMUExit, RISCV64},
+    Board,
+};
+
+const MEMORY: Region = Region {
+    base: 0x8000_0000,
+    size: 0x800_0000,
+};
+const PLIC: Device = Device {
+    reg: Region {
+        base: 0x0c00_0000,
+        size: 0x60_0000,
+    },
+    irq: None,
+};
+const TEST: Region = Region {
+    base: 0x10_0000,
+    size: PAGE_SIZE,
+};
+const TIMER_FREQ: usize = 10_000_000;
+
+// QEMU 的 virt 机器，通过 sifive_test 设备关机
+pub struct QemuVirt;
+
+impl Board for QemuVirt {
+    fn name(&self) -> &'static str {
+        "qemu-virt"
+    }
+
+    fn power_off(&self, code: u32) -> ! {
+        let test = RISCV64::new(machine().exit_device().map_or(TEST.base, |r| r.base) as u64);
+        if code == 0 {
+            test.exit_success()
+        } else {
+            test.exit(code)
+        }
+    }
+
+    fn memory(&self) -> Region {
+        MEMORY
+    }
+
+    fn irq_controller(&self) -> Device {
+        PLIC
+    }
+
+    fn timer_freq(&self) -> usize {
+        TIMER_FREQ
+    }
+
+    fn exit_device(&self) -> Option<Region> {
+        Some(TEST)
+    }
+}
@@ -1,12 +1,12 @@
-use crate::sbi::console_putchar;
+use crate::board::{Board, BOARD};
 use core::fmt::{self, Write};
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        for b in s.bytes() {
+            BOARD.console_putchar(b);
         }
         Ok(())
     }
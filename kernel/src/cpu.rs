@@ -0,0 +1,93 @@
+//! Per-hart state
+//!
+//! Every hart keeps its hart id in `tp` while running in the kernel, so
+//! [`cpu_id`] is a single register read. Per-hart data lives in arrays
+//! indexed by hart id and is only touched by its own hart.
+
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+pub const MAX_HARTS: usize = 8;
+
+struct Cpu {
+    noff: usize,  // push_off() 的嵌套深度
+    intena: bool, // 第一次 push_off() 之前中断是否打开
+    intr: usize,  // 中断处理的嵌套深度
+}
+
+const CPU_INIT: Cpu = Cpu {
+    noff: 0,
+    intena: false,
+    intr: 0,
+};
+
+static mut CPUS: [Cpu; MAX_HARTS] = [CPU_INIT; MAX_HARTS];
+
+// 已经进入内核的 hart 掩码
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn cpu_id() -> usize {
+    let id;
+    unsafe {
+        asm!("mv {0}, tp", out(reg) id);
+    }
+    id
+}
+
+fn this_cpu() -> &'static mut Cpu {
+    unsafe { &mut CPUS[cpu_id()] }
+}
+
+// 关闭中断并记录嵌套深度，与 pop_off() 成对使用
+pub fn push_off() {
+    let old = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    let cpu = this_cpu();
+    if cpu.noff == 0 {
+        cpu.intena = old;
+    }
+    cpu.noff += 1;
+}
+
+pub fn pop_off() {
+    assert!(!sstatus::read().sie(), "pop_off: interruptible");
+    let cpu = this_cpu();
+    assert!(cpu.noff >= 1, "pop_off: unbalanced");
+    cpu.noff -= 1;
+    if cpu.noff == 0 && cpu.intena {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+}
+
+// 当前 hart 的中断关闭嵌套深度
+pub fn interrupt_depth() -> usize {
+    this_cpu().noff
+}
+
+// 中断处理程序的首尾调用，供 lockdep 区分中断上下文
+pub fn enter_interrupt() {
+    this_cpu().intr += 1;
+}
+
+pub fn exit_interrupt() {
+    let cpu = this_cpu();
+    assert!(cpu.intr >= 1, "exit_interrupt: unbalanced");
+    cpu.intr -= 1;
+}
+
+pub fn in_interrupt() -> bool {
+    this_cpu().intr > 0
+}
+
+pub fn mark_online() {
+    ONLINE_HARTS.fetch_or(1 << cpu_id(), Ordering::AcqRel);
+}
+
+pub fn online_harts() -> usize {
+    ONLINE_HARTS.load(Ordering::Acquire)
+}
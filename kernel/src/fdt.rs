@@ -0,0 +1,172 @@
+//! Minimal flattened device tree (FDT) reader
+//!
+//! SBI passes the physical address of the device tree blob in a1 when it
+//! jumps to the kernel. Only reading is supported: the structure block is
+//! walked token by token and properties are returned as raw big-endian bytes.
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+fn be32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+// 读取以 0 结尾的字符串
+fn cstr(data: &[u8], offset: usize) -> &str {
+    let len = data[offset..].iter().position(|&b| b == 0).unwrap_or(0);
+    core::str::from_utf8(&data[offset..offset + len]).unwrap_or("")
+}
+
+#[derive(Clone, Copy)]
+pub struct Fdt {
+    data: &'static [u8],
+    struct_offset: usize,
+    strings_offset: usize,
+}
+
+pub enum Token {
+    BeginNode(&'static str),
+    EndNode,
+    Prop(&'static str, &'static [u8]),
+}
+
+pub struct Tokens {
+    fdt: Fdt,
+    pos: usize,
+}
+
+impl Fdt {
+    /// Check the header of the blob at `addr`, `None` if it is not a device tree.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be readable, the blob must stay untouched while it is in use.
+    pub unsafe fn from_addr(addr: usize) -> Option<Self> {
+        if addr == 0 || addr % 8 != 0 {
+            return None;
+        }
+        let header = core::slice::from_raw_parts(addr as *const u8, 40);
+        if be32(header, 0) != FDT_MAGIC {
+            return None;
+        }
+        let total_size = be32(header, 4) as usize;
+        let data = core::slice::from_raw_parts(addr as *const u8, total_size);
+        Some(Self {
+            data,
+            struct_offset: be32(data, 8) as usize,
+            strings_offset: be32(data, 12) as usize,
+        })
+    }
+
+    pub fn addr(&self) -> usize {
+        self.data.as_ptr() as usize
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn tokens(&self) -> Tokens {
+        Tokens {
+            fdt: *self,
+            pos: self.struct_offset,
+        }
+    }
+
+    /// Find property `name` of the node at `path`, e.g. `("/cpus", "timebase-frequency")`.
+    /// A path component without a unit address also matches `name@address`.
+    pub fn find_property(&self, path: &str, name: &str) -> Option<&'static [u8]> {
+        let target = path.split('/').filter(|c| !c.is_empty()).count();
+        let component = |level: usize| path.split('/').filter(|c| !c.is_empty()).nth(level - 1);
+
+        let mut depth = 0usize; // 当前打开的节点数，根节点为第 1 个
+        let mut matched = 0usize; // 已与路径匹配的层数
+        for token in self.tokens() {
+            match token {
+                Token::BeginNode(node) => {
+                    depth += 1;
+                    let level = depth - 1;
+                    if level >= 1
+                        && matched == level - 1
+                        && component(level).map_or(false, |c| node_matches(c, node))
+                    {
+                        matched = level;
+                    }
+                }
+                Token::EndNode => {
+                    let level = depth - 1;
+                    if level >= 1 && matched >= level {
+                        matched = level - 1;
+                    }
+                    depth -= 1;
+                }
+                Token::Prop(prop, value) => {
+                    if depth - 1 == target && matched == target && prop == name {
+                        return Some(value);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn property_u32(&self, path: &str, name: &str) -> Option<u32> {
+        self.find_property(path, name)
+            .filter(|value| value.len() >= 4)
+            .map(|value| be32(value, 0))
+    }
+
+    pub fn property_str(&self, path: &str, name: &str) -> Option<&'static str> {
+        self.find_property(path, name).map(|value| cstr(value, 0))
+    }
+}
+
+fn node_matches(component: &str, node: &str) -> bool {
+    node == component || (!component.contains('@') && node.split('@').next() == Some(component))
+}
+
+impl Iterator for Tokens {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let data = self.fdt.data;
+        loop {
+            let token = be32(data, self.pos);
+            self.pos += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = cstr(data, self.pos);
+                    self.pos = align4(self.pos + name.len() + 1);
+                    return Some(Token::BeginNode(name));
+                }
+                FDT_END_NODE => return Some(Token::EndNode),
+                FDT_PROP => {
+                    let len = be32(data, self.pos) as usize;
+                    let name_offset = be32(data, self.pos + 4) as usize;
+                    self.pos += 8;
+                    let value = &data[self.pos..self.pos + len];
+                    self.pos = align4(self.pos + len);
+                    let name = cstr(data, self.fdt.strings_offset + name_offset);
+                    return Some(Token::Prop(name, value));
+                }
+                FDT_NOP => continue,
+                _ => return None, // FDT_END
+            }
+        }
+    }
+}
+
+/// Look up `key=value` in the kernel command line from `/chosen/bootargs`.
+pub fn bootarg(fdt: Option<&Fdt>, key: &str) -> Option<&'static str> {
+    fdt?.property_str("/chosen", "bootargs")?
+        .split_whitespace()
+        .find_map(|arg| arg.strip_prefix(key)?.strip_prefix('='))
+}
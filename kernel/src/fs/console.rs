@@ -0,0 +1,86 @@
+// 作为文件的控制台
+// 读取像规范模式的终端一样按行缓冲：输入的字节会回显，退格删除字符，一行输入完成后读取才返回
+// 行首的 ctrl-D 表示文件结束，SBI 控制台不能产生中断，没有输入时读者每 POLL_MS 毫秒轮询一次
+
+use crate::{
+    board::{Board, BOARD},
+    task::{current_killed, current_task_id, run_next_task_block},
+    timer::{add_timer, cancel_timer, get_time, ms_to_cycles, TimerEvent},
+};
+
+use super::File;
+
+const POLL_MS: usize = 10;
+
+const CTRL_D: u8 = 0x04;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+pub struct Console;
+
+// 等待下一个输入字符，期间让出 CPU，任务被 kill 时返回 None
+fn getchar() -> Option<u8> {
+    loop {
+        if let Some(c) = BOARD.console_getchar() {
+            return Some(c);
+        }
+        if current_killed() {
+            return None;
+        }
+        // 提前被唤醒时定时器仍在队列中，须取消
+        let event = TimerEvent::Wakeup(current_task_id());
+        add_timer(get_time() + ms_to_cycles(POLL_MS), event);
+        run_next_task_block();
+        cancel_timer(event);
+    }
+}
+
+impl File for Console {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: &mut [u8]) -> isize {
+        let mut n = 0;
+        while n < buf.len() {
+            let c = match getchar() {
+                Some(c) => c,
+                None => return -1,
+            };
+            match c {
+                CTRL_D if n == 0 => return 0,
+                CTRL_D => break,
+                BACKSPACE | DELETE => {
+                    if n > 0 {
+                        n -= 1;
+                        for &c in b"\x08 \x08" {
+                            BOARD.console_putchar(c);
+                        }
+                    }
+                }
+                c => {
+                    // 串口上回车键发送的是 '\r'
+                    let c = if c == b'\r' { b'\n' } else { c };
+                    BOARD.console_putchar(c);
+                    buf[n] = c;
+                    n += 1;
+                    if c == b'\n' {
+                        break;
+                    }
+                }
+            }
+        }
+        n as isize
+    }
+
+    fn write(&self, buf: &[u8]) -> isize {
+        for &c in buf {
+            BOARD.console_putchar(c);
+        }
+        buf.len() as isize
+    }
+}
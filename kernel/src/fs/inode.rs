@@ -0,0 +1,79 @@
+// ramfs 中打开的文件与目录
+
+use crate::sync::SleepLock;
+
+use super::{
+    ramfs::{Stat, FS},
+    Console, File,
+};
+
+pub struct InodeFile {
+    ino: usize,
+    readable: bool,
+    writable: bool,
+    append: bool,
+    device: bool,             // 设备文件只有控制台
+    offset: SleepLock<usize>, // 读写期间持有，其间会获取 FS 锁
+}
+
+impl InodeFile {
+    // ino 须已由 RamFs::open() 或 RamFs::create() 打开，关闭时释放
+    pub fn new(ino: usize, readable: bool, writable: bool, append: bool, device: bool) -> Self {
+        Self {
+            ino,
+            readable,
+            writable,
+            append,
+            device,
+            offset: SleepLock::new("file_offset", 0),
+        }
+    }
+}
+
+impl File for InodeFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, buf: &mut [u8]) -> isize {
+        if self.device {
+            return Console.read(buf);
+        }
+        let mut offset = self.offset.lock();
+        let n = FS.lock().read(self.ino, *offset, buf);
+        *offset += n;
+        n as isize
+    }
+
+    fn write(&self, buf: &[u8]) -> isize {
+        if self.device {
+            return Console.write(buf);
+        }
+        let mut offset = self.offset.lock();
+        let mut fs = FS.lock();
+        if self.append {
+            *offset = fs.size(self.ino);
+        }
+        let n = fs.write(self.ino, *offset, buf);
+        *offset += n;
+        // 文件已达上限时一个字节也写不进
+        if n == 0 && !buf.is_empty() {
+            return -1;
+        }
+        n as isize
+    }
+
+    fn stat(&self) -> Option<Stat> {
+        FS.lock().stat(self.ino)
+    }
+}
+
+impl Drop for InodeFile {
+    fn drop(&mut self) {
+        FS.lock().release(self.ino);
+    }
+}
@@ -0,0 +1,102 @@
+// 打开的文件
+// 文件描述符可以指向的对象都实现 File 特征，每个进程有一个由其线程共享的 FdTable
+// fork 时子进程得到一份副本，其中的描述符指向相同的打开文件，因此父子进程看到同一个管道
+// 指向文件的最后一个描述符关闭时文件被关闭，有名字的文件位于内存文件系统 ramfs 中
+
+use alloc::{sync::Arc, vec, vec::Vec};
+
+mod console;
+mod inode;
+mod pipe;
+pub mod ramfs;
+
+pub use console::Console;
+pub use pipe::make_pipe;
+
+use self::{
+    inode::InodeFile,
+    ramfs::{Stat, FS, T_FILE},
+};
+
+pub const O_RDONLY: u32 = 0x000;
+pub const O_WRONLY: u32 = 0x001;
+pub const O_RDWR: u32 = 0x002;
+pub const O_CREATE: u32 = 0x200;
+pub const O_TRUNC: u32 = 0x400;
+pub const O_APPEND: u32 = 0x800;
+
+// 每个进程的描述符数
+pub const NOFILE: usize = 16;
+
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    // 读入 buf，阻塞到至少有一个字节可读，文件结束时返回 0
+    fn read(&self, buf: &mut [u8]) -> isize;
+    // 写入 buf，阻塞到全部写完或文件不能再写入，例如没有读者的管道
+    fn write(&self, buf: &[u8]) -> isize;
+    // fstat 返回的状态，管道等没有状态的文件为 None
+    fn stat(&self) -> Option<Stat> {
+        None
+    }
+}
+
+// 以 O_* 标志 flags 打开相对于目录 cwd 的 path
+// 目录只能以只读方式打开，对设备的读写都交给控制台
+pub fn open(cwd: usize, path: &str, flags: u32) -> Option<Arc<dyn File>> {
+    let readable = flags & (O_WRONLY | O_RDWR) != O_WRONLY;
+    let writable = flags & (O_WRONLY | O_RDWR) != O_RDONLY;
+    let mut fs = FS.lock();
+    let ino = if flags & O_CREATE != 0 {
+        fs.create(cwd, path, T_FILE)?
+    } else {
+        let ino = fs.lookup(cwd, path)?;
+        fs.open(ino)?
+    };
+    if fs.is_dir(ino) && writable {
+        fs.release(ino);
+        return None;
+    }
+    if flags & O_TRUNC != 0 && writable {
+        fs.truncate(ino);
+    }
+    let device = fs.is_device(ino);
+    drop(fs);
+    Some(Arc::new(InodeFile::new(
+        ino,
+        readable,
+        writable,
+        flags & O_APPEND != 0,
+        device,
+    )))
+}
+
+#[derive(Clone)]
+pub struct FdTable {
+    files: Vec<Option<Arc<dyn File>>>,
+}
+
+impl FdTable {
+    pub fn new() -> Self {
+        Self {
+            files: vec![None; NOFILE],
+        }
+    }
+
+    pub fn get(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.files.get(fd)?.clone()
+    }
+
+    // 使用编号最小的空闲描述符
+    pub fn alloc(&mut self, file: Arc<dyn File>) -> Option<usize> {
+        let fd = self.files.iter().position(|f| f.is_none())?;
+        self.files[fd] = Some(file);
+        Some(fd)
+    }
+
+    // 返回被关闭的文件，调用者应在释放描述符表的锁后再丢弃它，
+    // 因为关闭管道的一端会唤醒另一端的任务
+    pub fn close(&mut self, fd: usize) -> Option<Arc<dyn File>> {
+        self.files.get_mut(fd)?.take()
+    }
+}
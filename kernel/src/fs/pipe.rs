@@ -0,0 +1,151 @@
+// 管道
+// 两端共享一个环形缓冲区，缓冲区空时读者睡眠，满时写者睡眠，每次变化都唤醒睡眠在管道上的所有任务重新检查
+// 写端关闭后读者读到文件结束，读端关闭后写入失败，被 kill 的任务停止等待并返回失败
+
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    sync::SpinLock,
+    task::{current_killed, current_task_id, sleep_on, wakeup_task},
+};
+
+use super::File;
+
+const PIPE_SIZE: usize = 512;
+
+struct PipeBuffer {
+    data: [u8; PIPE_SIZE],
+    head: usize, // 下一个读取的位置
+    len: usize,
+    read_open: bool,
+    write_open: bool,
+    waiters: VecDeque<usize>, // 在此管道上睡眠的任务
+}
+
+impl PipeBuffer {
+    fn wake_all(&mut self) {
+        while let Some(id) = self.waiters.pop_front() {
+            wakeup_task(id);
+        }
+    }
+}
+
+type Pipe = Arc<SpinLock<PipeBuffer>>;
+
+pub struct PipeReader(Pipe);
+pub struct PipeWriter(Pipe);
+
+// 创建管道，返回读端与写端
+pub fn make_pipe() -> (Arc<PipeReader>, Arc<PipeWriter>) {
+    let pipe = Arc::new(SpinLock::new(
+        "pipe",
+        PipeBuffer {
+            data: [0; PIPE_SIZE],
+            head: 0,
+            len: 0,
+            read_open: true,
+            write_open: true,
+            waiters: VecDeque::new(),
+        },
+    ));
+    (
+        Arc::new(PipeReader(pipe.clone())),
+        Arc::new(PipeWriter(pipe)),
+    )
+}
+
+impl File for PipeReader {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, buf: &mut [u8]) -> isize {
+        loop {
+            let mut pipe = self.0.lock();
+            if pipe.len == 0 {
+                if !pipe.write_open {
+                    return 0;
+                }
+                if current_killed() {
+                    return -1;
+                }
+                pipe.waiters.push_back(current_task_id());
+                sleep_on(pipe);
+                continue;
+            }
+            let n = buf.len().min(pipe.len);
+            for b in buf[..n].iter_mut() {
+                *b = pipe.data[pipe.head];
+                pipe.head = (pipe.head + 1) % PIPE_SIZE;
+            }
+            pipe.len -= n;
+            pipe.wake_all();
+            return n as isize;
+        }
+    }
+
+    fn write(&self, _buf: &[u8]) -> isize {
+        -1
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        let mut pipe = self.0.lock();
+        pipe.read_open = false;
+        pipe.wake_all();
+    }
+}
+
+impl File for PipeWriter {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> isize {
+        -1
+    }
+
+    fn write(&self, buf: &[u8]) -> isize {
+        let mut written = 0;
+        while written < buf.len() {
+            let mut pipe = self.0.lock();
+            if !pipe.read_open {
+                return -1;
+            }
+            if pipe.len == PIPE_SIZE {
+                if current_killed() {
+                    return -1;
+                }
+                pipe.waiters.push_back(current_task_id());
+                sleep_on(pipe);
+                continue;
+            }
+            let n = (buf.len() - written).min(PIPE_SIZE - pipe.len);
+            for &b in &buf[written..written + n] {
+                let tail = (pipe.head + pipe.len) % PIPE_SIZE;
+                pipe.data[tail] = b;
+                pipe.len += 1;
+            }
+            written += n;
+            pipe.wake_all();
+        }
+        written as isize
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        let mut pipe = self.0.lock();
+        pipe.write_open = false;
+        pipe.wake_all();
+    }
+}
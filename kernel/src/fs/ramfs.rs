@@ -0,0 +1,375 @@
+// 内存文件系统
+// inode 保存在以 inode 号为下标的表中，相当于去掉磁盘的磁盘文件系统 inode 缓存
+// 每个目录都有 . 与 .. 两项，读取目录得到 xv6 格式的 Dirent，用户程序以同样的方式遍历
+// 没有目录项链接到它、也没有打开的文件指向它时 inode 被释放
+// 不以 / 开头的路径相对于调用者的工作目录解析，所有操作都持有唯一的 FS 睡眠锁，等待它的任务睡眠而不是自旋
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use lazy_static::lazy_static;
+
+use crate::sync::SleepLock;
+
+pub const T_DIR: u16 = 1;
+pub const T_FILE: u16 = 2;
+pub const T_DEVICE: u16 = 3;
+
+// / 的 inode 号，0 表示空的目录项
+pub const ROOT_INO: usize = 1;
+// 目录项中名字的最大长度
+pub const DIRSIZ: usize = 14;
+// 从目录中读出的 Dirent 的字节数
+pub const DIRENT_SIZE: usize = 2 + DIRSIZ;
+
+const NINODE: usize = 128;
+const MAX_FILE_SIZE: usize = 64 * 1024;
+
+// fstat 报告的内容，布局与 xv6 的 struct stat 相同
+pub struct Stat {
+    pub dev: u32,
+    pub ino: u32,
+    pub kind: u16,
+    pub nlink: u16,
+    pub size: u64,
+}
+
+pub const STAT_SIZE: usize = 24;
+
+impl Stat {
+    // 与用户态 #[repr(C)] 的结构体布局一致，nlink 之后有 4 字节填充
+    pub fn to_bytes(&self) -> [u8; STAT_SIZE] {
+        let mut bytes = [0u8; STAT_SIZE];
+        bytes[0..4].copy_from_slice(&self.dev.to_ne_bytes());
+        bytes[4..8].copy_from_slice(&self.ino.to_ne_bytes());
+        bytes[8..10].copy_from_slice(&self.kind.to_ne_bytes());
+        bytes[10..12].copy_from_slice(&self.nlink.to_ne_bytes());
+        bytes[16..24].copy_from_slice(&self.size.to_ne_bytes());
+        bytes
+    }
+}
+
+// 从目录中读出的目录项：inode 号与以 0 填充的名字
+pub struct Dirent {
+    pub ino: u16,
+    pub name: [u8; DIRSIZ],
+}
+
+impl Dirent {
+    pub fn to_bytes(&self) -> [u8; DIRENT_SIZE] {
+        let mut bytes = [0u8; DIRENT_SIZE];
+        bytes[..2].copy_from_slice(&self.ino.to_ne_bytes());
+        bytes[2..].copy_from_slice(&self.name);
+        bytes
+    }
+}
+
+struct DirEntry {
+    name: String,
+    ino: usize,
+}
+
+enum Node {
+    File(Vec<u8>),
+    Dir(Vec<DirEntry>),
+    Device,
+}
+
+struct Inode {
+    nlink: usize,
+    open: usize, // 引用该 inode 的打开文件数
+    node: Node,
+}
+
+pub struct RamFs {
+    inodes: Vec<Option<Inode>>,
+}
+
+lazy_static! {
+    pub static ref FS: SleepLock<RamFs> = SleepLock::new("fs", RamFs::new());
+}
+
+impl RamFs {
+    // 只有根目录与 /dev/console
+    fn new() -> Self {
+        let mut fs = Self {
+            inodes: (0..NINODE).map(|_| None).collect(),
+        };
+        fs.inodes[ROOT_INO] = Some(Inode {
+            nlink: 1,
+            open: 0,
+            node: Node::Dir(vec![
+                DirEntry {
+                    name: ".".to_string(),
+                    ino: ROOT_INO,
+                },
+                DirEntry {
+                    name: "..".to_string(),
+                    ino: ROOT_INO,
+                },
+            ]),
+        });
+        let dev = fs.create(ROOT_INO, "/dev", T_DIR).unwrap();
+        let console = fs.create(ROOT_INO, "/dev/console", T_DEVICE).unwrap();
+        fs.release(dev);
+        fs.release(console);
+        fs
+    }
+
+    fn inode(&self, ino: usize) -> Option<&Inode> {
+        self.inodes.get(ino)?.as_ref()
+    }
+
+    fn inode_mut(&mut self, ino: usize) -> Option<&mut Inode> {
+        self.inodes.get_mut(ino)?.as_mut()
+    }
+
+    fn entries(&self, ino: usize) -> Option<&Vec<DirEntry>> {
+        match &self.inode(ino)?.node {
+            Node::Dir(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    fn entries_mut(&mut self, ino: usize) -> Option<&mut Vec<DirEntry>> {
+        match &mut self.inode_mut(ino)?.node {
+            Node::Dir(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    fn dir_lookup(&self, dir: usize, name: &str) -> Option<usize> {
+        self.entries(dir)?
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.ino)
+    }
+
+    fn start(cwd: usize, path: &str) -> usize {
+        if path.starts_with('/') {
+            ROOT_INO
+        } else {
+            cwd
+        }
+    }
+
+    // 相对于目录 cwd 的 path 所指的 inode
+    pub fn lookup(&self, cwd: usize, path: &str) -> Option<usize> {
+        let mut ino = Self::start(cwd, path);
+        self.entries(ino)?;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            ino = self.dir_lookup(ino, name)?;
+        }
+        Some(ino)
+    }
+
+    // 路径最后一项所在的目录及其名字，名字不能为空或是 "." 与 ".."
+    fn lookup_parent<'a>(&self, cwd: usize, path: &'a str) -> Option<(usize, &'a str)> {
+        let path = path.trim_end_matches('/');
+        let (dir, name) = match path.rfind('/') {
+            Some(i) => (self.lookup(cwd, &path[..=i])?, &path[i + 1..]),
+            None => (self.lookup(cwd, ".")?, path),
+        };
+        if name.is_empty() || name == "." || name == ".." {
+            return None;
+        }
+        Some((dir, name))
+    }
+
+    fn alloc_inode(&mut self, node: Node) -> Option<usize> {
+        let ino = (ROOT_INO + 1..NINODE).find(|&ino| self.inodes[ino].is_none())?;
+        self.inodes[ino] = Some(Inode {
+            nlink: 0,
+            open: 0,
+            node,
+        });
+        Some(ino)
+    }
+
+    // nlink 与 open 都为 0 时回收
+    fn try_free(&mut self, ino: usize) {
+        if let Some(inode) = self.inode(ino) {
+            if inode.nlink == 0 && inode.open == 0 {
+                self.inodes[ino] = None;
+            }
+        }
+    }
+
+    // 创建类型为 kind 的 path 并打开它
+    // 创建普通文件时若同名普通文件已存在则打开它，已存在其他对象时失败
+    pub fn create(&mut self, cwd: usize, path: &str, kind: u16) -> Option<usize> {
+        let (dir, name) = self.lookup_parent(cwd, path)?;
+        if let Some(ino) = self.dir_lookup(dir, name) {
+            let is_file = matches!(self.inode(ino)?.node, Node::File(_));
+            return if is_file && kind == T_FILE {
+                self.open(ino)
+            } else {
+                None
+            };
+        }
+        if name.len() > DIRSIZ {
+            return None;
+        }
+        let node = match kind {
+            T_DIR => Node::Dir(vec![
+                DirEntry {
+                    name: ".".to_string(),
+                    ino: 0,
+                },
+                DirEntry {
+                    name: "..".to_string(),
+                    ino: dir,
+                },
+            ]),
+            T_FILE => Node::File(Vec::new()),
+            _ => Node::Device,
+        };
+        let ino = self.alloc_inode(node)?;
+        if kind == T_DIR {
+            self.entries_mut(ino)?[0].ino = ino;
+            // 子目录的 ".." 指向父目录
+            self.inode_mut(dir)?.nlink += 1;
+        }
+        self.entries_mut(dir)?.push(DirEntry {
+            name: name.to_string(),
+            ino,
+        });
+        let inode = self.inode_mut(ino)?;
+        inode.nlink = 1;
+        inode.open = 1;
+        Some(ino)
+    }
+
+    // 打开 inode ino，关闭后须释放
+    pub fn open(&mut self, ino: usize) -> Option<usize> {
+        self.inode_mut(ino)?.open += 1;
+        Some(ino)
+    }
+
+    pub fn release(&mut self, ino: usize) {
+        if let Some(inode) = self.inode_mut(ino) {
+            inode.open -= 1;
+        }
+        self.try_free(ino);
+    }
+
+    // 使 new 成为文件 old 的另一个名字，目录不能被链接
+    pub fn link(&mut self, cwd: usize, old: &str, new: &str) -> Option<()> {
+        let ino = self.lookup(cwd, old)?;
+        if let Node::Dir(_) = self.inode(ino)?.node {
+            return None;
+        }
+        let (dir, name) = self.lookup_parent(cwd, new)?;
+        if name.len() > DIRSIZ || self.dir_lookup(dir, name).is_some() {
+            return None;
+        }
+        self.entries_mut(dir)?.push(DirEntry {
+            name: name.to_string(),
+            ino,
+        });
+        self.inode_mut(ino)?.nlink += 1;
+        Some(())
+    }
+
+    // 删除名字 path，目录只有为空时才能删除
+    pub fn unlink(&mut self, cwd: usize, path: &str) -> Option<()> {
+        let (dir, name) = self.lookup_parent(cwd, path)?;
+        let ino = self.dir_lookup(dir, name)?;
+        let is_dir = match self.entries(ino) {
+            Some(entries) if entries.len() > 2 => return None,
+            Some(_) => true,
+            None => false,
+        };
+        let entries = self.entries_mut(dir)?;
+        entries.retain(|entry| entry.name != name);
+        if is_dir {
+            self.inode_mut(dir)?.nlink -= 1;
+        }
+        self.inode_mut(ino)?.nlink -= 1;
+        self.try_free(ino);
+        Some(())
+    }
+
+    pub fn is_dir(&self, ino: usize) -> bool {
+        self.entries(ino).is_some()
+    }
+
+    pub fn is_device(&self, ino: usize) -> bool {
+        matches!(self.inode(ino).map(|inode| &inode.node), Some(Node::Device))
+    }
+
+    pub fn stat(&self, ino: usize) -> Option<Stat> {
+        let inode = self.inode(ino)?;
+        let (kind, size) = match &inode.node {
+            Node::File(data) => (T_FILE, data.len()),
+            Node::Dir(entries) => (T_DIR, entries.len() * DIRENT_SIZE),
+            Node::Device => (T_DEVICE, 0),
+        };
+        Some(Stat {
+            dev: 0,
+            ino: ino as u32,
+            kind,
+            nlink: inode.nlink as u16,
+            size: size as u64,
+        })
+    }
+
+    pub fn size(&self, ino: usize) -> usize {
+        self.stat(ino).map_or(0, |stat| stat.size as usize)
+    }
+
+    // 从 offset 处读入 buf，返回读取的字节数，目录读出的是 Dirent 数组
+    pub fn read(&self, ino: usize, offset: usize, buf: &mut [u8]) -> usize {
+        let dirents;
+        let data: &[u8] = match self.inode(ino).map(|inode| &inode.node) {
+            Some(Node::File(data)) => data,
+            Some(Node::Dir(entries)) => {
+                dirents = entries
+                    .iter()
+                    .flat_map(|entry| {
+                        let mut name = [0u8; DIRSIZ];
+                        name[..entry.name.len()].copy_from_slice(entry.name.as_bytes());
+                        Dirent {
+                            ino: entry.ino as u16,
+                            name,
+                        }
+                        .to_bytes()
+                    })
+                    .collect::<Vec<u8>>();
+                &dirents
+            }
+            _ => return 0,
+        };
+        if offset >= data.len() {
+            return 0;
+        }
+        let n = buf.len().min(data.len() - offset);
+        buf[..n].copy_from_slice(&data[offset..offset + n]);
+        n
+    }
+
+    // 在普通文件的 offset 处写入 buf，文件最多增长到 MAX_FILE_SIZE，返回写入的字节数
+    pub fn write(&mut self, ino: usize, offset: usize, buf: &[u8]) -> usize {
+        let data = match self.inode_mut(ino).map(|inode| &mut inode.node) {
+            Some(Node::File(data)) => data,
+            _ => return 0,
+        };
+        if offset >= MAX_FILE_SIZE {
+            return 0;
+        }
+        let n = buf.len().min(MAX_FILE_SIZE - offset);
+        if data.len() < offset + n {
+            data.resize(offset + n, 0);
+        }
+        data[offset..offset + n].copy_from_slice(&buf[..n]);
+        n
+    }
+
+    pub fn truncate(&mut self, ino: usize) {
+        if let Some(Node::File(data)) = self.inode_mut(ino).map(|inode| &mut inode.node) {
+            data.clear();
+        }
+    }
+}
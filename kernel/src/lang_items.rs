@@ -1,4 +1,7 @@
-use crate::{println, sbi::shutdown};
+use crate::{
+    board::{Board, BOARD},
+    println,
+};
 use core::panic::PanicInfo;
 
 #[panic_handler]
@@ -14,5 +17,5 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         println!("Panicked: {}", err);
     }
-    shutdown()
+    BOARD.exit(1)
 }
@@ -0,0 +1,330 @@
+// 从设备树得到的机器描述
+// 设备树在启动时、页面分配器接管物理内存之前解析一次，结果保存在定长的表中，不需要堆
+// 固件没有传入设备树时，各项查询退回到板子的默认值
+
+use crate::{
+    board::{Board, BOARD},
+    cpu::MAX_HARTS,
+    fdt::{Fdt, Token},
+    mem_layout::KERNEL_BASE,
+};
+
+const MAX_MEMORY_REGIONS: usize = 4;
+const MAX_VIRTIO: usize = 8;
+const MAX_DEPTH: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Region {
+    pub base: usize,
+    pub size: usize,
+}
+
+impl Region {
+    const fn empty() -> Self {
+        Self { base: 0, size: 0 }
+    }
+
+    pub fn end(&self) -> usize {
+        self.base + self.size
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        addr >= self.base && addr < self.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Device {
+    pub reg: Region,
+    pub irq: Option<u32>,
+}
+
+pub struct Machine {
+    memory: [Region; MAX_MEMORY_REGIONS],
+    memory_count: usize,
+    harts: [usize; MAX_HARTS], // 设备树中各 hart 的 hartid
+    hart_count: usize,
+    timebase: Option<usize>,
+    dtb: Option<Region>, // 设备树本身占用的内存
+    pub uart: Option<Device>,
+    pub plic: Option<Device>,
+    pub test: Option<Device>,
+    virtio: [Option<Device>; MAX_VIRTIO],
+}
+
+static mut MACHINE: Machine = Machine {
+    memory: [Region::empty(); MAX_MEMORY_REGIONS],
+    memory_count: 0,
+    harts: [0; MAX_HARTS],
+    hart_count: 0,
+    timebase: None,
+    dtb: None,
+    uart: None,
+    plic: None,
+    test: None,
+    virtio: [None; MAX_VIRTIO],
+};
+
+// 解析中的节点：属性总在子节点之前出现，节点结束时属性已收集完整
+#[derive(Clone, Copy)]
+struct Node {
+    address_cells: usize, // 供子节点的 reg 使用
+    size_cells: usize,
+    reg: Option<&'static [u8]>,
+    compatible: Option<&'static [u8]>,
+    device_type: Option<&'static [u8]>,
+    status: Option<&'static [u8]>,
+    irq: Option<u32>,
+}
+
+impl Node {
+    const fn new() -> Self {
+        Self {
+            address_cells: 2,
+            size_cells: 1,
+            reg: None,
+            compatible: None,
+            device_type: None,
+            status: None,
+            irq: None,
+        }
+    }
+
+    fn is_compatible(&self, name: &str) -> bool {
+        self.compatible.map_or(false, |list| {
+            list.split(|&b| b == 0).any(|c| c == name.as_bytes())
+        })
+    }
+
+    fn device_type_is(&self, name: &str) -> bool {
+        self.device_type.map_or(false, |t| {
+            t.split(|&b| b == 0).next() == Some(name.as_bytes())
+        })
+    }
+
+    fn enabled(&self) -> bool {
+        self.status
+            .map_or(true, |s| s.starts_with(b"okay") || s.starts_with(b"ok\0"))
+    }
+}
+
+// 读取由 cells 个 32 位大端单元组成的数，数据不足或超过 64 位时返回 None
+fn read_cells(data: &[u8], cells: usize) -> Option<usize> {
+    if cells > 2 || data.len() < cells * 4 {
+        return None;
+    }
+    Some(data[..cells * 4].chunks(4).fold(0, |acc, c| {
+        (acc << 32) | u32::from_be_bytes(c.try_into().unwrap()) as usize
+    }))
+}
+
+// 按父节点的 #address-cells/#size-cells 拆分 reg 属性，单元数不合法时没有区域
+fn regions(reg: &'static [u8], parent: &Node) -> impl Iterator<Item = Region> {
+    let (ac, sc) = (parent.address_cells, parent.size_cells);
+    let valid = ac <= 2 && sc <= 2 && ac + sc > 0;
+    let reg: &[u8] = if valid { reg } else { &[] };
+    reg.chunks_exact((ac + sc).max(1) * 4)
+        .filter_map(move |entry| {
+            Some(Region {
+                base: read_cells(entry, ac)?,
+                size: read_cells(&entry[ac * 4..], sc)?,
+            })
+        })
+}
+
+impl Machine {
+    fn parse(&mut self, fdt: &Fdt) {
+        let mut stack = [Node::new(); MAX_DEPTH];
+        let mut depth = 0usize;
+        for token in fdt.tokens() {
+            match token {
+                Token::BeginNode(_) => {
+                    assert!(depth < MAX_DEPTH, "device tree too deep");
+                    // 未声明时 #address-cells 与 #size-cells 分别为 2 和 1
+                    stack[depth] = Node::new();
+                    depth += 1;
+                }
+                Token::Prop(name, value) => {
+                    let node = &mut stack[depth - 1];
+                    // 长度不对的属性被忽略
+                    match name {
+                        "#address-cells" => {
+                            if let Some(cells) = read_cells(value, 1) {
+                                node.address_cells = cells;
+                            }
+                        }
+                        "#size-cells" => {
+                            if let Some(cells) = read_cells(value, 1) {
+                                node.size_cells = cells;
+                            }
+                        }
+                        "reg" => node.reg = Some(value),
+                        "compatible" => node.compatible = Some(value),
+                        "device_type" => node.device_type = Some(value),
+                        "status" => node.status = Some(value),
+                        "interrupts" => node.irq = read_cells(value, 1).map(|irq| irq as u32),
+                        "timebase-frequency" => {
+                            if let Some(freq) = read_cells(value, 1).filter(|&freq| freq > 0) {
+                                self.timebase = Some(freq);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Token::EndNode => {
+                    depth -= 1;
+                    if depth >= 1 {
+                        let (node, parent) = (stack[depth], stack[depth - 1]);
+                        self.add_node(&node, &parent);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_node(&mut self, node: &Node, parent: &Node) {
+        if !node.enabled() {
+            return;
+        }
+        // cpu 节点的 reg 是 hartid，超出 MAX_HARTS 的 hart 不使用
+        if node.device_type_is("cpu") {
+            let hartid = node.reg.and_then(|reg| regions(reg, parent).next());
+            if let Some(hartid) = hartid.map(|r| r.base).filter(|&id| id < MAX_HARTS) {
+                if self.hart_count < MAX_HARTS {
+                    self.harts[self.hart_count] = hartid;
+                    self.hart_count += 1;
+                }
+            }
+            return;
+        }
+        let reg = match node.reg {
+            Some(reg) => reg,
+            None => return,
+        };
+        if node.device_type_is("memory") {
+            for region in regions(reg, parent) {
+                if self.memory_count < MAX_MEMORY_REGIONS {
+                    self.memory[self.memory_count] = region;
+                    self.memory_count += 1;
+                }
+            }
+            return;
+        }
+        let device = match regions(reg, parent).next() {
+            Some(region) => Device {
+                reg: region,
+                irq: node.irq,
+            },
+            None => return,
+        };
+        if node.is_compatible("ns16550a") || node.is_compatible("sifive,uart0") {
+            self.uart = Some(device);
+        } else if node.is_compatible("riscv,plic0") || node.is_compatible("sifive,plic-1.0.0") {
+            self.plic = Some(device);
+        } else if node.is_compatible("sifive,test0") || node.is_compatible("sifive,test1") {
+            self.test = Some(device);
+        } else if node.is_compatible("virtio,mmio") {
+            if let Some(slot) = self.virtio.iter_mut().find(|d| d.is_none()) {
+                *slot = Some(device);
+            }
+        }
+    }
+
+    pub fn memory(&self) -> &[Region] {
+        &self.memory[..self.memory_count]
+    }
+
+    // 内核所在内存区域的结束地址
+    pub fn phys_top(&self) -> usize {
+        self.memory()
+            .iter()
+            .find(|r| r.contains(KERNEL_BASE))
+            .map_or(BOARD.memory().end(), |r| r.end())
+    }
+
+    // 内核之上页面分配器不能分配的内存
+    pub fn reserved(&self) -> Option<Region> {
+        self.dtb
+    }
+
+    // 设备树中没有 hart 时为 0..MAX_HARTS，不存在的 hart 由 SBI 报告
+    pub fn hartids(&self) -> &[usize] {
+        &self.harts[..self.hart_count]
+    }
+
+    pub fn timebase(&self) -> usize {
+        self.timebase.unwrap_or_else(|| BOARD.timer_freq())
+    }
+
+    pub fn irq_controller(&self) -> Device {
+        self.plic.unwrap_or_else(|| BOARD.irq_controller())
+    }
+
+    // 关机设备的寄存器，板子没有时为 None
+    pub fn exit_device(&self) -> Option<Region> {
+        self.test.map(|d| d.reg).or_else(|| BOARD.exit_device())
+    }
+
+    pub fn virtio(&self) -> impl Iterator<Item = &Device> {
+        self.virtio.iter().flatten()
+    }
+
+    // 所有设备的寄存器区域，供内核页表映射
+    pub fn mmio(&self) -> impl Iterator<Item = Region> + '_ {
+        self.uart
+            .iter()
+            .copied()
+            .chain(core::iter::once(self.irq_controller()))
+            .chain(self.virtio().copied())
+            .map(|d| d.reg)
+            .chain(self.exit_device())
+    }
+
+    fn print(&self) {
+        for r in self.memory() {
+            println!("[kernel] memory: [{:#x}, {:#x})", r.base, r.end());
+        }
+        println!(
+            "[kernel] harts {:?}, timebase {} Hz",
+            self.hartids(),
+            self.timebase()
+        );
+        let print_device = |name: &str, d: &Device| match d.irq {
+            Some(irq) => println!("[kernel] {} at {:#x}, irq {}", name, d.reg.base, irq),
+            None => println!("[kernel] {} at {:#x}", name, d.reg.base),
+        };
+        self.uart.iter().for_each(|d| print_device("uart", d));
+        self.plic.iter().for_each(|d| print_device("plic", d));
+        self.test
+            .iter()
+            .for_each(|d| print_device("test device", d));
+        self.virtio().for_each(|d| print_device("virtio", d));
+    }
+}
+
+// 解析设备树，须在建立页面分配器与启动其他 hart 之前调用
+pub fn init(fdt: Option<&Fdt>) {
+    println!("[kernel] board: {}", BOARD.name());
+    let machine = unsafe { &mut MACHINE };
+    match fdt {
+        Some(fdt) => {
+            machine.dtb = Some(Region {
+                base: fdt.addr(),
+                size: fdt.total_size(),
+            });
+            machine.parse(fdt);
+        }
+        None => println!("[kernel] no device tree, using defaults of the board."),
+    }
+    if machine.hart_count == 0 {
+        for (i, hartid) in machine.harts.iter_mut().enumerate() {
+            *hartid = i;
+        }
+        machine.hart_count = MAX_HARTS;
+    }
+    machine.print();
+}
+
+pub fn machine() -> &'static Machine {
+    unsafe { &MACHINE }
+}
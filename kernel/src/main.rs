@@ -2,42 +2,74 @@
 #![no_main]
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::test::runner)]
+#![reexport_test_harness_main = "test_main"]
 #![allow(unused)]
 
 extern crate alloc;
 
-#[path = "board/qemu.rs"]
 mod board;
 
 #[macro_use]
 mod console;
+mod cpu;
+mod fdt;
+mod fs;
 mod lang_items;
 mod logo;
+mod machine;
 mod mem;
 pub mod mem_layout;
 mod sbi;
+mod smp;
 mod sync;
 pub mod syscall;
 mod task;
+#[cfg(test)]
+mod test;
+mod timer;
 mod trap;
 
 use core::arch::global_asm;
 
-use sbi::shutdown;
-use task::run_first_task;
+use sync::{lock_kernel, unlock_kernel};
+use task::run_tasks;
 
 global_asm!(include_str!("entry.S"));
 global_asm!(include_str!("link_app.S"));
 
+// SBI 传入 a0 = hartid, a1 = 设备树地址
 #[no_mangle]
-pub fn main() {
+pub fn main(hartid: usize, dtb: usize) {
     clear_bss();
+    sbi::init();
     logo::print_logo();
+    // 设备树位于物理内存中，须在页面分配器接管内存之前读取
+    let fdt = unsafe { fdt::Fdt::from_addr(dtb) };
+    machine::init(fdt.as_ref());
+    timer::init(fdt.as_ref());
     trap::init();
     mem::init();
+    // cargo test 构建的内核只运行测试，结束后关机
+    #[cfg(test)]
+    test_main();
     task::load_tasks();
-    run_first_task();
-    shutdown()
+    cpu::mark_online();
+    smp::start_secondary_harts();
+    run_tasks()
+}
+
+// 其他 hart 由 smp::start_secondary_harts() 启动，a0 = hartid
+#[no_mangle]
+pub fn secondary_main(hartid: usize) -> ! {
+    lock_kernel();
+    mem::init_hart();
+    trap::init();
+    cpu::mark_online();
+    println!("[kernel] hart{} started.", hartid);
+    unlock_kernel();
+    run_tasks()
 }
 
 // init .bss segment.
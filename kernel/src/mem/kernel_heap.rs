@@ -22,7 +22,9 @@ pub fn init_heap() {
     }
 }
 
-pub fn kernel_heap_test() {
+// 堆分配的对象位于 .bss 中的 HEAP_SPACE 内
+#[test_case]
+fn kernel_heap_test() {
     use alloc::boxed::Box;
     use alloc::vec::Vec;
     extern "C" {
@@ -43,5 +45,4 @@ pub fn kernel_heap_test() {
     }
     assert!(bss_range.contains(&(v.as_ptr() as usize)));
     drop(v);
-    println!("kernel heap test passed!");
 }
@@ -5,8 +5,9 @@ use lazy_static::*;
 use riscv::register::satp;
 
 use crate::{
-    mem_layout::{KERNEL_BASE, KERNEL_STACK_SIZE, PAGE_SIZE, PHYS_TOP, TRAMPOLINE},
-    sync::UPSafeCell,
+    machine::machine,
+    mem_layout::{KERNEL_BASE, KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE},
+    sync::SpinLock,
     task::param::MAX_APP_NUM,
 };
 
@@ -14,6 +15,7 @@ use super::{
     address::Addr,
     page_allocator::{kalloc, PageTracker},
     page_table::{PTEFlags, PageTable},
+    tlb::flush_all,
 };
 
 extern "C" {
@@ -51,20 +53,32 @@ impl KernelSpace {
     }
 
     pub fn init(&mut self) {
-        self.page_table.map_range(
+        self.page_table.map_range_huge(
             Addr::new(KERNEL_BASE),
             Addr::new(KERNEL_BASE),
             etext as usize - KERNEL_BASE,
             PTEFlags::R | PTEFlags::X,
         );
 
-        self.page_table.map_range(
+        self.page_table.map_range_huge(
             Addr::new(etext as usize),
             Addr::new(etext as usize),
-            PHYS_TOP - etext as usize,
+            machine().phys_top() - etext as usize,
             PTEFlags::R | PTEFlags::W,
         );
 
+        // 设备寄存器，供驱动直接访问
+        for region in machine().mmio() {
+            let start = Addr::new(region.base).align_down();
+            let end = Addr::new(region.end()).align_up();
+            self.page_table.map_range_huge(
+                start,
+                start,
+                end.bits - start.bits,
+                PTEFlags::R | PTEFlags::W,
+            );
+        }
+
         self.page_table.map(
             Addr::new(TRAMPOLINE),
             Addr::new(trampoline as usize),
@@ -76,35 +90,48 @@ impl KernelSpace {
     pub fn print_kernel_pagetable(&self) {
         self.page_table.print_page_table();
     }
+    // 每个 hart 启动时调用一次，此后切换页表依靠 ASID 区分地址空间
     pub fn active(&self) {
         unsafe {
-            asm!("sfence.vma");
             satp::write(self.page_table.make_satp());
-            asm!("sfence.vma");
         }
+        flush_all();
     }
 
     pub fn make_satp(&self) -> usize {
         self.page_table.make_satp()
     }
-
-    pub fn space_test(&self) {
-        self.page_table.walk_test();
-    }
 }
 
 lazy_static! {
-    pub static ref KERNEL_SPACE: UPSafeCell<KernelSpace> =
-        unsafe { UPSafeCell::new(KernelSpace::new()) };
+    pub static ref KERNEL_SPACE: SpinLock<KernelSpace> =
+        SpinLock::new("kernel_space", KernelSpace::new());
+}
+
+#[test_case]
+fn kernel_space_walk_test() {
+    KERNEL_SPACE.lock().page_table.walk_test();
 }
 
 pub fn kernel_stack_i(id: usize) -> Addr {
     Addr::new(TRAMPOLINE - (id + 1) * (KERNEL_STACK_SIZE + PAGE_SIZE))
 }
 
+// 若地址落在某个内核栈下方的保护页中，返回该内核栈所属的任务号
+pub fn kernel_stack_guard(addr: usize) -> Option<usize> {
+    (0..MAX_APP_NUM).find(|&id| {
+        let bottom = kernel_stack_i(id).bits;
+        addr >= bottom - PAGE_SIZE && addr < bottom
+    })
+}
+
 pub fn kvminit() {
-    let mut kernel_space = KERNEL_SPACE.get_mut();
+    let mut kernel_space = KERNEL_SPACE.lock();
     kernel_space.init();
     kernel_space.active();
     println!("[kernel] kernel space init success.");
 }
+
+pub fn kvminithart() {
+    KERNEL_SPACE.lock().active();
+}
@@ -2,28 +2,27 @@ use crate::mem_layout::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
 
 use self::{address::Addr, kernel_space::kernel_stack_i};
 
-pub mod address;
+pub use sv39::address;
 mod kernel_heap;
 pub mod kernel_space;
 mod page_allocator;
 mod page_table;
+pub mod tlb;
 pub mod user_space;
 
 pub fn kernel_sp_i(id: usize) -> usize {
     kernel_stack_i(id).bits + KERNEL_STACK_SIZE
 }
 
-pub use page_table::copy_from_user;
-
 pub fn init() {
     kernel_heap::init_heap();
-    // kernel_heap::kernel_heap_test();
-
     page_allocator::kinit();
-    // page_allocator::page_allocator_test();
     kernel_space::kvminit();
-
-    // user_space::userspace_test();
-
+    tlb::asid_init();
     println!("[kernel] memory init success!");
 }
+
+// 其他 hart 启动后切换到已建立好的内核页表
+pub fn init_hart() {
+    kernel_space::kvminithart();
+}
@@ -3,8 +3,8 @@ use core::fmt::{self, Debug, Formatter};
 use core::ptr::null_mut;
 use lazy_static::*;
 
-use crate::mem_layout::{PAGE_SIZE, PHYS_TOP};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
+use crate::{machine::machine, mem_layout::PAGE_SIZE};
 
 use super::address::{Addr, Page};
 
@@ -12,6 +12,8 @@ use super::address::{Addr, Page};
 trait PageAlloc {
     fn empty() -> Self;
     fn init(&mut self, start: Addr, end: Addr);
+    // 在 init() 之后、分配之前调用，[start, end) 中的页面不会被分配
+    fn reserve(&mut self, start: Addr, end: Addr);
     fn alloc(&mut self) -> Option<Page>;
     fn dealloc(&mut self, page: Page);
 }
@@ -27,6 +29,9 @@ struct FreeList {
     rear: *mut FreeListNode,
 }
 
+// 链表节点位于空闲的物理页面中，不属于任何 hart，可以在 hart 之间转移
+unsafe impl Send for FreeList {}
+
 impl FreeList {
     pub fn empty() -> Self {
         Self {
@@ -69,11 +74,95 @@ impl FreeList {
     }
 }
 
+// 调试模式：用位图记录每个物理页的分配状态，并对空闲页面进行毒化
+#[cfg(feature = "debug-alloc")]
+mod debug {
+    use alloc::{vec, vec::Vec};
+
+    use crate::mem::address::{Addr, Page};
+    use crate::mem_layout::PAGE_SIZE;
+
+    // 释放页面时填充的字节
+    pub const POISON_BYTE: u8 = 0x5a;
+    // 页面开头存放空闲链表指针，不参与毒化检查
+    pub const LINK_SIZE: usize = core::mem::size_of::<usize>();
+
+    pub struct AllocBitmap {
+        base: Addr,
+        npages: usize,
+        bits: Vec<u64>,
+    }
+
+    impl AllocBitmap {
+        pub fn empty() -> Self {
+            Self {
+                base: Addr::empty(),
+                npages: 0,
+                bits: Vec::new(),
+            }
+        }
+
+        pub fn init(&mut self, start: Addr, end: Addr) {
+            self.base = start;
+            self.npages = (end.bits - start.bits) / PAGE_SIZE;
+            self.bits = vec![0u64; (self.npages + 63) / 64];
+        }
+
+        fn index(&self, page: Page) -> usize {
+            assert!(
+                page.addr >= self.base.bits && page.addr < self.base.bits + self.npages * PAGE_SIZE,
+                "{:?} is not managed by the page allocator",
+                page
+            );
+            (page.addr - self.base.bits) / PAGE_SIZE
+        }
+
+        fn allocated(&self, i: usize) -> bool {
+            self.bits[i / 64] & (1 << (i % 64)) != 0
+        }
+
+        pub fn mark_alloc(&mut self, page: Page) {
+            let i = self.index(page);
+            assert!(!self.allocated(i), "{:?} allocated twice", page);
+            self.bits[i / 64] |= 1 << (i % 64);
+        }
+
+        pub fn mark_free(&mut self, page: Page) {
+            let i = self.index(page);
+            assert!(self.allocated(i), "double free of {:?}", page);
+            self.bits[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    // 用毒化字节填充整个页面
+    pub fn poison(page: Page) {
+        unsafe {
+            core::ptr::write_bytes(page.addr as *mut u8, POISON_BYTE, PAGE_SIZE);
+        }
+    }
+
+    // 检查空闲期间页面是否被改写
+    pub fn check_poison(page: Page) {
+        let bytes = page.get_bytes();
+        if let Some(offset) = bytes[LINK_SIZE..].iter().position(|&b| b != POISON_BYTE) {
+            panic!(
+                "use after free: {:?} modified at offset {:#x}",
+                page,
+                offset + LINK_SIZE
+            );
+        }
+    }
+}
+
 // 链式物理页分配器
 pub struct PageAllocator {
     next: Addr,
     end: Addr,
+    hole_start: Addr, // 保留的页面范围，顺序分配时跳过
+    hole_end: Addr,
     free_list: FreeList,
+    #[cfg(feature = "debug-alloc")]
+    bitmap: debug::AllocBitmap,
 }
 
 impl PageAlloc for PageAllocator {
@@ -81,34 +170,57 @@ impl PageAlloc for PageAllocator {
         Self {
             next: Addr::empty(),
             end: Addr::empty(),
+            hole_start: Addr::empty(),
+            hole_end: Addr::empty(),
             free_list: FreeList::empty(),
+            #[cfg(feature = "debug-alloc")]
+            bitmap: debug::AllocBitmap::empty(),
         }
     }
     fn init(&mut self, start: Addr, end: Addr) {
         self.next = start;
         self.end = end;
+        #[cfg(feature = "debug-alloc")]
+        self.bitmap.init(start, end);
+    }
+    fn reserve(&mut self, start: Addr, end: Addr) {
+        self.hole_start = start;
+        self.hole_end = end;
     }
+    // 可分配的页面范围为 [start, end) 去掉保留的范围
     fn alloc(&mut self) -> Option<Page> {
-        if self.next > self.end {
-            if let Some(addr) = self.free_list.pop_front() {
-                Some(addr.into())
-            } else {
-                None
-            }
-        } else {
-            let res = self.next;
+        if self.next == self.hole_start {
+            self.next = self.hole_end;
+        }
+        if self.next < self.end {
+            let page: Page = self.next.into();
             self.next.bits += PAGE_SIZE;
-            Some(res.into())
+            #[cfg(feature = "debug-alloc")]
+            self.bitmap.mark_alloc(page);
+            Some(page)
+        } else {
+            let page: Page = self.free_list.pop_front()?.into();
+            #[cfg(feature = "debug-alloc")]
+            {
+                debug::check_poison(page);
+                self.bitmap.mark_alloc(page);
+            }
+            Some(page)
         }
     }
     fn dealloc(&mut self, page: Page) {
+        #[cfg(feature = "debug-alloc")]
+        {
+            self.bitmap.mark_free(page);
+            debug::poison(page);
+        }
         self.free_list.push_back(page.into())
     }
 }
 
 lazy_static! {
-    pub static ref PAGE_ALLOCATOR: UPSafeCell<PageAllocator> =
-        unsafe { UPSafeCell::new(PageAllocator::empty()) };
+    pub static ref PAGE_ALLOCATOR: SpinLock<PageAllocator> =
+        SpinLock::new("page_allocator", PageAllocator::empty());
 }
 
 pub struct PageTracker {
@@ -140,39 +252,72 @@ pub fn kinit() {
     extern "C" {
         fn ekernel();
     }
-    PAGE_ALLOCATOR.get_mut().init(
-        Addr::new(ekernel as usize).align_up(),
-        Addr::new(PHYS_TOP).align_down(),
-    );
+    let start = Addr::new(ekernel as usize).align_up();
+    let end = Addr::new(machine().phys_top()).align_down();
+    let mut allocator = PAGE_ALLOCATOR.lock();
+    allocator.init(start, end);
+    // 设备树可能位于内核与内存顶端之间，跳过它占用的页面，两侧的内存仍可分配
+    if let Some(reserved) = machine().reserved() {
+        let hole_start = Addr::new(reserved.base).align_down().max(start);
+        let hole_end = Addr::new(reserved.end()).align_up().min(end);
+        if hole_start < hole_end {
+            allocator.reserve(hole_start, hole_end);
+        }
+    }
 }
 
 pub fn kalloc() -> Option<PageTracker> {
-    PAGE_ALLOCATOR.get_mut().alloc().map(PageTracker::new)
+    PAGE_ALLOCATOR.lock().alloc().map(PageTracker::new)
 }
 
 fn kfree(page: Page) {
-    PAGE_ALLOCATOR.get_mut().dealloc(page);
+    PAGE_ALLOCATOR.lock().dealloc(page);
 }
 
-pub fn page_allocator_test() {
+// 分配出的页面对齐、互不重叠且可以读写，释放后可再次分配
+#[test_case]
+fn page_allocator_test() {
     let mut v: Vec<PageTracker> = Vec::new();
-    for i in 0..5 {
-        let page = kalloc().unwrap();
-        println!("{:?}", page);
-        v.push(page);
-    }
-    v.clear();
-    for i in 0..5 {
-        let page = kalloc().unwrap();
-        println!("{:?}", page);
-        v.push(page);
-    }
-    v.clear();
-    for i in 0..5 {
-        let page = kalloc().unwrap();
-        println!("{:?}", page);
-        v.push(page);
-    }
-    drop(v);
-    println!("page allocator test passed!");
+    for round in 0..3 {
+        for i in 0..5 {
+            let page = kalloc().unwrap();
+            let addr = page.page().addr;
+            assert_eq!(addr % PAGE_SIZE, 0);
+            assert!(v.iter().all(|p| p.page().addr != addr));
+            let bytes = unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, PAGE_SIZE) };
+            bytes.fill((round * 5 + i) as u8);
+            v.push(page);
+        }
+        for (i, page) in v.iter().enumerate() {
+            let bytes =
+                unsafe { core::slice::from_raw_parts(page.page().addr as *const u8, PAGE_SIZE) };
+            assert!(bytes.iter().all(|&b| b == (round * 5 + i) as u8));
+        }
+        v.clear();
+    }
+}
+
+// 在一个页面上建立独立的分配器：释放的页面被毒化，从空闲链表再次分配时通过毒化检查，位图随之更新
+#[cfg(feature = "debug-alloc")]
+#[test_case]
+fn debug_alloc_poison_test() {
+    let backing = kalloc().unwrap();
+    let start = Addr::new(backing.page().addr);
+    let mut allocator = PageAllocator::empty();
+    allocator.init(start, Addr::new(start.bits + PAGE_SIZE));
+
+    let page = allocator.alloc().unwrap();
+    assert_eq!(page.addr, start.bits);
+    assert!(allocator.alloc().is_none());
+    page.get_bytes_mut().fill(0xcc);
+    allocator.dealloc(page);
+    // 开头是空闲链表指针，其余字节都是毒化字节
+    let bytes = page.get_bytes();
+    assert!(bytes[debug::LINK_SIZE..]
+        .iter()
+        .all(|&b| b == debug::POISON_BYTE));
+
+    let again = allocator.alloc().unwrap();
+    assert_eq!(again.addr, page.addr);
+    assert!(allocator.alloc().is_none());
 }
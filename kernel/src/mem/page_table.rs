@@ -1,253 +1,466 @@
 use crate::mem::address::Page;
-use crate::mem_layout::{
-    MAX_PHYS_ADDR, MAX_VIRT_ADDR, MAX_VPN, PAGE_BITS, PAGE_SIZE, PTE_FLAGS_BITS,
-};
-use alloc::string::String;
-use alloc::vec::Vec;
-use bitflags::*;
-use core::fmt::{self, Debug, Formatter};
-use rand::{rngs::SmallRng, Rng, SeedableRng};
+use crate::mem_layout::{MAX_PHYS_ADDR, MAX_VIRT_ADDR, PAGE_BITS, PAGE_SIZE};
+use alloc::collections::BTreeMap;
+use sv39::{translate, walk, walk_alloc, PhysMem};
 
 use super::{
     address::Addr,
     page_allocator::{kalloc, PageTracker},
+    tlb::{flush_page, shootdown_asid, shootdown_page, AsidTracker, KERNEL_ASID, SATP_ASID_SHIFT},
 };
 
-bitflags! {
-    #[derive(PartialEq, Clone, Copy)]
-    pub struct PTEFlags: u8 {
-        const V = 1 << 0;
-        const R = 1 << 1;
-        const W = 1 << 2;
-        const X = 1 << 3;
-        const U = 1 << 4;
-        const G = 1 << 5;
-        const A = 1 << 6;
-        const D = 1 << 7;
-    }
-}
+pub use sv39::{level_size, PTEFlags, PageTableEntry};
 
-// 页表项结构
-#[repr(C)]
-pub struct PageTableEntry {
-    pub bits: usize,
-}
+// 内核通过恒等映射直接访问页表所在的物理页面
+struct DirectMap;
 
-impl Debug for PageTableEntry {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("pte: {:#x}", self.bits))
+impl PhysMem for DirectMap {
+    fn read_pte(&self, table: Page, index: usize) -> PageTableEntry {
+        table.get_ptes()[index]
     }
-}
 
-impl PageTableEntry {
-    pub fn empty() -> Self {
-        Self { bits: 0 }
-    }
-    pub fn new(pa: Addr, flags: PTEFlags) -> Self {
-        Self {
-            bits: pa.to_pte_bits() | flags.bits() as usize,
-        }
-    }
-
-    // 获取成页表项指向的物理页面地址
-    pub fn get_addr_bits(&self) -> usize {
-        (self.bits >> PTE_FLAGS_BITS) << PAGE_BITS
-    }
-    // 获取页表项 flags
-    pub fn flags(&self) -> PTEFlags {
-        PTEFlags::from_bits(self.bits as u8).unwrap()
-    }
-    pub fn valid(&self) -> bool {
-        (self.flags() & PTEFlags::V) != PTEFlags::empty()
-    }
-    pub fn readable(&self) -> bool {
-        (self.flags() & PTEFlags::R) != PTEFlags::empty()
-    }
-    pub fn writable(&self) -> bool {
-        (self.flags() & PTEFlags::W) != PTEFlags::empty()
-    }
-    pub fn executable(&self) -> bool {
-        (self.flags() & PTEFlags::X) != PTEFlags::empty()
-    }
-    pub fn user(&self) -> bool {
-        (self.flags() & PTEFlags::U) != PTEFlags::empty()
+    fn write_pte(&mut self, table: Page, index: usize, pte: PageTableEntry) {
+        table.get_ptes_mut()[index] = pte;
     }
 }
 
 pub struct PageTable {
     root: Addr,
-    tables: Vec<PageTracker>,
+    tables: BTreeMap<Addr, PageTracker>,
+    asid: Option<AsidTracker>, // 为空时使用内核的 ASID
 }
 
 impl PageTable {
     pub fn empty() -> Self {
         Self {
             root: Addr::empty(),
-            tables: Vec::new(),
+            tables: BTreeMap::new(),
+            asid: None,
         }
     }
-    // 调用 empty() 后必须使用此方法初始化
-    pub fn init(&mut self) {
-        let page_tracker = kalloc().unwrap();
+    // 调用 empty() 后必须使用此方法初始化，内存不足时返回 false
+    pub fn init(&mut self) -> bool {
+        let page_tracker = match kalloc() {
+            Some(page_tracker) => page_tracker,
+            None => return false,
+        };
         let page = page_tracker.page();
         page.clean_page();
         self.root = page.into();
-        self.tables.push(page_tracker);
+        self.tables.insert(self.root, page_tracker);
+        true
     }
 
     pub fn new() -> Self {
         let page_tracker = kalloc().unwrap();
         let page = page_tracker.page();
         page.clean_page();
-        let mut tables: Vec<PageTracker> = Vec::new();
+        let mut tables: BTreeMap<Addr, PageTracker> = BTreeMap::new();
         let root = page.into();
-        tables.push(page_tracker);
-        Self { root, tables }
+        tables.insert(root, page_tracker);
+        Self {
+            root,
+            tables,
+            asid: None,
+        }
+    }
+
+    pub fn set_asid(&mut self, asid: Option<AsidTracker>) {
+        self.asid = asid;
+    }
+
+    pub fn asid(&self) -> usize {
+        self.asid
+            .as_ref()
+            .map_or(KERNEL_ASID, |tracker| tracker.asid())
+    }
+
+    // 给定一个地址，获取其叶子页表项及其所在级别
+    // 遇到大页时提前返回，否则返回 0 级页表项
+    pub fn walk_leaf(&self, va: Addr) -> Option<(&mut PageTableEntry, usize)> {
+        let slot = walk(&DirectMap, self.root.into(), va, 0)?;
+        Some((&mut slot.table.get_ptes_mut()[slot.index], slot.level))
     }
 
-    // 给定一个地址，获取其 0 级页表项
+    // 给定一个地址，获取其叶子页表项
     pub fn walk(&self, va: Addr) -> Option<&mut PageTableEntry> {
+        self.walk_leaf(va).map(|(pte, _)| pte)
+    }
+
+    pub fn walk_alloc(&mut self, va: Addr) -> Option<&mut PageTableEntry> {
+        self.walk_alloc_level(va, 0)
+    }
+
+    // 获取第 target 级页表项，途中缺失的页表会被分配
+    // 若途中遇到大页叶子则直接返回该页表项
+    pub fn walk_alloc_level(&mut self, va: Addr, target: usize) -> Option<&mut PageTableEntry> {
+        let tables = &mut self.tables;
+        // 内存不足时返回 None
+        let slot = walk_alloc(&mut DirectMap, self.root.into(), va, target, || {
+            let page_tracker = kalloc()?;
+            let page = page_tracker.page();
+            page.clean_page();
+            tables.insert(page.into(), page_tracker);
+            Some(page)
+        })?;
+        Some(&mut slot.table.get_ptes_mut()[slot.index])
+    }
+
+    // 给定一个虚拟地址，返回其物理地址，只能用于用户
+    pub fn walk_addr(&self, va: Addr) -> Option<Addr> {
+        match translate(&DirectMap, self.root.into(), va) {
+            Some((pa, flags)) if flags.contains(PTEFlags::U) => Some(pa),
+            _ => None,
+        }
+    }
+
+    // 映射一个页面
+    pub fn map(&mut self, va: Addr, pa: Addr, flags: PTEFlags) {
+        self.map_level(va, pa, flags, 0);
+    }
+
+    // 映射一个页面，分配中间页表的内存不足时返回 false
+    pub fn try_map(&mut self, va: Addr, pa: Addr, flags: PTEFlags) -> bool {
+        assert!(va.aligned() && pa.aligned());
+        let pte = match self.walk_alloc(va) {
+            Some(pte) => pte,
+            None => return false,
+        };
+        assert!(!pte.valid(), "{:?} has been mapped", *pte);
+        *pte = PageTableEntry::new(pa, flags | PTEFlags::V);
+        flush_page(va, self.asid());
+        true
+    }
+
+    // 在第 level 级建立叶子页表项，va 与 pa 须按该级页面大小对齐
+    pub fn map_level(&mut self, va: Addr, pa: Addr, flags: PTEFlags, level: usize) {
         assert!(va.bits <= MAX_VIRT_ADDR);
+        assert!(pa.bits <= MAX_PHYS_ADDR);
+        assert!(flags.intersects(PTEFlags::R | PTEFlags::W | PTEFlags::X));
+        let size = level_size(level);
+        assert!(
+            va.bits % size == 0 && pa.bits % size == 0,
+            "{:?} -> {:?} is not aligned to level {}",
+            va,
+            pa,
+            level
+        );
+        let pte = self.walk_alloc_level(va, level).unwrap();
+        assert!(!pte.valid(), "{:?} has been mapped", *pte);
+        *pte = PageTableEntry::new(pa, flags | PTEFlags::V);
+        flush_page(va, self.asid());
+    }
 
-        let indexs = va.get_indexes();
-        let mut page: Page = self.root.into();
-        let mut res: Option<&mut PageTableEntry> = None;
-        for level in (0..3).rev() {
-            let pte = &mut page.get_ptes_mut()[indexs[level]];
-            if level == 0 {
-                res = Some(pte);
-                break;
-            }
-            if !pte.valid() {
-                break;
-            }
-            page = Page::new(pte.get_addr_bits());
+    // 映射一段连续的页面
+    pub fn map_range(&mut self, va: Addr, mut pa: Addr, len: usize, flags: PTEFlags) {
+        let mut a = va.align_down();
+        let end = va.add(len).align_up();
+        while a < end {
+            self.map(a, pa, flags);
+            a = a.add(PAGE_SIZE);
+            pa = pa.add(PAGE_SIZE);
         }
-        res
     }
 
-    pub fn walk_alloc(&mut self, va: Addr) -> Option<&mut PageTableEntry> {
+    // 与 map_range() 相同，但对齐条件允许时使用 2M/1G 大页
+    // 大页之后被部分取消映射或修改权限时需要拆分，内存不足时会失败，因此只用于内核的恒等映射
+    pub fn map_range_huge(&mut self, va: Addr, mut pa: Addr, len: usize, flags: PTEFlags) {
+        let mut a = va.align_down();
+        let end = va.add(len).align_up();
+        while a < end {
+            let level = (1..3)
+                .rev()
+                .find(|&level| {
+                    let size = level_size(level);
+                    a.bits % size == 0 && pa.bits % size == 0 && end.bits - a.bits >= size
+                })
+                .unwrap_or(0);
+            self.map_level(a, pa, flags, level);
+            a = a.add(level_size(level));
+            pa = pa.add(level_size(level));
+        }
+    }
+
+    // 将第 level 级的大页拆分为下一级的页表项，权限保持不变
+    // 内存不足时返回 false，大页保持不变
+    fn split(&mut self, pte: &mut PageTableEntry, level: usize) -> bool {
+        assert!(level > 0 && pte.is_leaf());
+        let page_tracker = match kalloc() {
+            Some(page_tracker) => page_tracker,
+            None => return false,
+        };
+        let page = page_tracker.page();
+        let flags = pte.flags();
+        let base = pte.get_addr_bits();
+        let size = level_size(level - 1);
+        for (i, child) in page.get_ptes_mut().iter_mut().enumerate() {
+            *child = PageTableEntry::new(Addr::new(base + i * size), flags);
+        }
+        *pte = PageTableEntry::new(page.into(), PTEFlags::V);
+        self.tables.insert(page.into(), page_tracker);
+        true
+    }
+
+    // 找到 va 所在的叶子页表项，若大页超出 [va, end) 则先将其拆分
+    // 返回沿途各级页表与叶子所在级别，va 未映射时返回 Some(None)，拆分时内存不足返回 None
+    fn walk_split(&mut self, va: Addr, end: Addr) -> Option<Option<([Page; 3], usize)>> {
         assert!(va.bits <= MAX_VIRT_ADDR);
 
         let indexs = va.get_indexes();
+        let mut path = [Page::empty(); 3];
         let mut page: Page = self.root.into();
-        let mut res: Option<&mut PageTableEntry> = None;
-        for level in (0..3).rev() {
+        let mut level = 2;
+        loop {
+            path[level] = page;
             let pte = &mut page.get_ptes_mut()[indexs[level]];
+            if !pte.valid() {
+                return Some(None);
+            }
             if level == 0 {
-                res = Some(pte);
                 break;
             }
-            if !pte.valid() {
-                if let Some(page_tracker) = kalloc() {
-                    let page = page_tracker.page();
-                    page.clean_page();
-                    *pte = PageTableEntry::new(page.into(), PTEFlags::V);
-                    self.tables.push(page_tracker);
-                } else {
-                    break; // 内存不足
+            if pte.is_leaf() {
+                let size = level_size(level);
+                if va.bits % size == 0 && end.bits - va.bits >= size {
+                    break;
+                }
+                if !self.split(pte, level) {
+                    return None;
                 }
             }
-            let pre = page;
             page = Page::new(pte.get_addr_bits());
+            level -= 1;
         }
-        res
+        Some(Some((path, level)))
     }
 
-    // 给定一个虚拟地址，返回其物理地址，只能用于用户
-    pub fn walk_addr(&self, va: Addr) -> Option<Addr> {
-        assert!(va.bits <= MAX_VIRT_ADDR);
+    // 取消 va 所在叶子的映射并回收变空的中间页表
+    // 返回原先映射的物理地址与叶子大小，va 未映射时返回 Some(None)，拆分大页时内存不足返回 None
+    fn unmap_leaf(&mut self, va: Addr, end: Addr) -> Option<Option<(Addr, usize)>> {
+        let indexs = va.get_indexes();
+        let (path, leaf_level) = match self.walk_split(va, end)? {
+            Some(leaf) => leaf,
+            None => return Some(None),
+        };
 
-        if let Some(pte) = self.walk(va) {
-            if !pte.valid() || !pte.user() {
-                None
+        let pte = &mut path[leaf_level].get_ptes_mut()[indexs[leaf_level]];
+        let pa = Addr::new(pte.get_addr_bits());
+        *pte = PageTableEntry::empty();
+        shootdown_page(va, self.asid());
+
+        let mut level = leaf_level;
+        while level < 2 && path[level].get_ptes().iter().all(|pte| !pte.valid()) {
+            path[level + 1].get_ptes_mut()[indexs[level + 1]] = PageTableEntry::empty();
+            let table: Addr = path[level].into();
+            self.tables.remove(&table);
+            level += 1;
+        }
+        // 按地址刷新只作用于叶子页表项，释放了中间页表时需刷新整个地址空间
+        if level != leaf_level {
+            shootdown_asid(self.asid());
+        }
+        Some(Some((pa, level_size(leaf_level))))
+    }
+
+    // 取消一个页面的映射，返回原先映射的物理地址
+    // 页面未映射，或位于大页中而拆分时内存不足，返回 None
+    pub fn unmap(&mut self, va: Addr) -> Option<Addr> {
+        let va = va.align_down();
+        self.unmap_leaf(va, va.add(PAGE_SIZE))
+            .flatten()
+            .map(|(pa, size)| pa.add(va.bits & (size - 1)))
+    }
+
+    // 取消一段地址范围的映射，未映射的页面会被跳过
+    // 拆分大页时内存不足返回 false，此前的页面已取消映射
+    pub fn unmap_range(&mut self, va: Addr, len: usize) -> bool {
+        let mut a = va.align_down();
+        let end = va.add(len).align_up();
+        while a < end {
+            match self.unmap_leaf(a, end) {
+                Some(Some((_, size))) => a = a.add(size),
+                Some(None) => a = a.add(PAGE_SIZE),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    // 修改一段地址范围的访问权限，保留 G/A/D 位
+    // 范围内存在未映射的页面，或拆分大页时内存不足，返回 false
+    pub fn protect(&mut self, va: Addr, len: usize, flags: PTEFlags) -> bool {
+        assert!(flags.intersects(PTEFlags::R | PTEFlags::W | PTEFlags::X));
+        let mut a = va.align_down();
+        let end = va.add(len).align_up();
+        let mut all_mapped = true;
+        while a < end {
+            let leaf = match self.walk_split(a, end) {
+                Some(leaf) => leaf,
+                None => return false,
+            };
+            if let Some((path, level)) = leaf {
+                let pte = &mut path[level].get_ptes_mut()[a.get_indexes()[level]];
+                let kept = pte.flags() & (PTEFlags::G | PTEFlags::A | PTEFlags::D);
+                *pte =
+                    PageTableEntry::new(Addr::new(pte.get_addr_bits()), kept | flags | PTEFlags::V);
+                shootdown_page(a, self.asid());
+                a = a.add(level_size(level));
             } else {
-                Some(Addr::new(pte.get_addr_bits() | (va.bits & (PAGE_SIZE - 1))))
+                all_mapped = false;
+                a = a.add(PAGE_SIZE);
             }
-        } else {
-            None
         }
+        all_mapped
     }
 
-    // 映射一个页面
-    pub fn map(&mut self, va: Addr, pa: Addr, flags: PTEFlags) {
+    // 给定一个虚拟地址，返回其物理地址与权限，不检查 U 位
+    pub fn translate(&self, va: Addr) -> Option<(Addr, PTEFlags)> {
         assert!(va.bits <= MAX_VIRT_ADDR);
-        assert!(pa.bits <= MAX_PHYS_ADDR);
-        let pte = self.walk_alloc(va).unwrap();
-        assert!(!pte.valid(), "{:?} has been mapped", *pte);
-        *pte = PageTableEntry::new(pa, flags | PTEFlags::V);
+
+        let (pte, level) = self.walk_leaf(va)?;
+        if !pte.valid() {
+            return None;
+        }
+        Some((
+            Addr::new(pte.get_addr_bits() | (va.bits & (level_size(level) - 1))),
+            pte.flags(),
+        ))
     }
 
-    // 映射一段连续的页面
-    pub fn map_range(&mut self, va: Addr, mut pa: Addr, len: usize, flags: PTEFlags) {
-        let mut a = va.align_down();
-        let last = va.add(len - 1).align_down();
-        while (true) {
-            self.map(a, pa, flags);
-            if a == last {
-                break;
+    // 遍历所有叶子映射，回调参数为虚拟地址、叶子页表项与其级别
+    pub fn visit_leaves<F: FnMut(Addr, &mut PageTableEntry, usize)>(&self, mut f: F) {
+        Self::visit_level(self.root.into(), 2, 0, &mut f);
+    }
+
+    fn visit_level<F: FnMut(Addr, &mut PageTableEntry, usize)>(
+        page: Page,
+        level: usize,
+        base: usize,
+        f: &mut F,
+    ) {
+        for (i, pte) in page.get_ptes_mut().iter_mut().enumerate() {
+            if !pte.valid() {
+                continue;
+            }
+            let va = base + i * level_size(level);
+            if level == 0 || pte.is_leaf() {
+                f(Addr::new(va), pte, level);
+            } else {
+                Self::visit_level(Page::new(pte.get_addr_bits()), level - 1, va, f);
             }
-            a = a.add(PAGE_SIZE);
-            pa = pa.add(PAGE_SIZE);
         }
     }
 
-    // satp 寄存器存放的2级页表地址
+    // satp 寄存器存放的2级页表地址与 ASID
     pub fn make_satp(&self) -> usize {
-        (8usize << 60) | (self.root.bits >> PAGE_BITS)
+        (8usize << 60) | (self.asid() << SATP_ASID_SHIFT) | (self.root.bits >> PAGE_BITS)
     }
 
     // 打印页表
     pub fn print_page_table(&self) {
-        let mut page: Page = self.root.into();
+        let page: Page = self.root.into();
         println!("pagetable: {:?}", page);
-        for (i, pte) in page.get_ptes().into_iter().enumerate() {
+        Self::print_level(page, 2);
+        println!("print page table success");
+    }
+
+    fn print_level(page: Page, level: usize) {
+        let indent = (3 - level) * 2;
+        for pte in page.get_ptes().iter() {
             if !pte.valid() {
                 continue;
             }
-            println!("  ..{:?}, pa: {:?}", *pte, Addr::new(pte.get_addr_bits()));
-            page = Page::new(pte.get_addr_bits());
-            for (i, pte) in page.get_ptes().into_iter().enumerate() {
-                if !pte.valid() {
-                    continue;
-                }
-                println!("    ..{:?}, pa: {:?}", *pte, Addr::new(pte.get_addr_bits()));
-                page = Page::new(pte.get_addr_bits());
-                for (i, pte) in page.get_ptes().into_iter().enumerate() {
-                    if !pte.valid() {
-                        continue;
-                    }
-                    println!(
-                        "      ..{:?}, pa: {:?}",
-                        *pte,
-                        Addr::new(pte.get_addr_bits())
-                    );
-                }
+            let pa = Addr::new(pte.get_addr_bits());
+            if level > 0 && pte.is_leaf() {
+                println!(
+                    "{:indent$}..{:?}, pa: {:?}, size: {:#x}",
+                    "",
+                    *pte,
+                    pa,
+                    level_size(level),
+                    indent = indent
+                );
+                continue;
+            }
+            println!("{:indent$}..{:?}, pa: {:?}", "", *pte, pa, indent = indent);
+            if level > 0 {
+                Self::print_level(Page::new(pa.bits), level - 1);
             }
         }
-        println!("print page table success");
     }
 
-    // 随机生成内核代码段的虚拟地址，与 walk_addr() 的结果是否一致
+    // 随机生成内核中恒等映射的虚拟地址，检查 walk_addr() 的结果
+    #[cfg(test)]
     pub fn walk_test(&self) {
-        let mut va = Addr::empty();
-        let mut pa = Addr::empty();
+        use rand::{rngs::SmallRng, Rng, SeedableRng};
+
         let mut rng = SmallRng::seed_from_u64(0);
-        for _ in 0..10 {
-            va = Addr::new(rng.gen_range(0x8020_0000..0x8080_0000));
-            pa = self.walk_addr(va).unwrap();
-            println!("walk va: {:?} -> pa: {:?}", va, pa);
+        for _ in 0..100 {
+            let va = Addr::new(rng.gen_range(0x8020_0000..0x8080_0000));
+            // walk_addr() 只接受用户页面，这里直接查叶子页表项
+            let (pte, level) = self.walk_leaf(va).unwrap();
+            assert!(pte.valid());
+            assert_eq!(
+                pte.get_addr_bits() | (va.bits & (level_size(level) - 1)),
+                va.bits
+            );
         }
     }
 }
 
-pub fn copy_from_user(page_table: Addr, src: Addr, len: usize) -> &'static [u8] {
-    let page_table = PageTable {
-        root: page_table,
-        tables: Vec::new(),
-    };
+// 在大页的中间取消映射与修改权限，拆分后其余地址的翻译保持不变
+#[test_case]
+fn superpage_split_test() {
+    let rw = PTEFlags::R | PTEFlags::W;
+    let mut page_table = PageTable::new();
+    let va = Addr::new(0x4000_0000);
+    let pa = Addr::new(0x8000_0000);
+    page_table.map_range_huge(va, pa, level_size(2), rw);
+    assert_eq!(page_table.walk_leaf(va).unwrap().1, 2);
+
+    // 1G 大页被拆分为 2M 大页，洞所在的 2M 大页再拆分为 4K 页面
+    let hole = va.add(3 * level_size(1) + 5 * PAGE_SIZE);
+    let hole_offset = hole.bits - va.bits;
+    assert_eq!(page_table.unmap(hole), Some(pa.add(hole_offset)));
+    assert_eq!(page_table.translate(hole), None);
+    assert_eq!(page_table.walk_leaf(va).unwrap().1, 1);
+    assert_eq!(page_table.walk_leaf(hole.add(PAGE_SIZE)).unwrap().1, 0);
+    for offset in [
+        0,
+        hole_offset - 1,
+        hole_offset + PAGE_SIZE,
+        level_size(2) - 1,
+    ] {
+        assert_eq!(
+            page_table.translate(va.add(offset)),
+            Some((pa.add(offset), PTEFlags::V | rw))
+        );
+    }
+
+    // 只有范围内的页面变为只读
+    let start = va.add(7 * level_size(1) + 2 * PAGE_SIZE);
+    let start_offset = start.bits - va.bits;
+    assert!(page_table.protect(start, 3 * PAGE_SIZE, PTEFlags::R));
+    for (offset, flags) in [
+        (start_offset - 1, rw),
+        (start_offset, PTEFlags::R),
+        (start_offset + 3 * PAGE_SIZE - 1, PTEFlags::R),
+        (start_offset + 3 * PAGE_SIZE, rw),
+    ] {
+        assert_eq!(
+            page_table.translate(va.add(offset)),
+            Some((pa.add(offset), PTEFlags::V | flags))
+        );
+    }
 
-    let pa = page_table.walk_addr(src).unwrap();
+    // 覆盖整个大页的范围不需要拆分
+    let whole = va.add(9 * level_size(1));
+    assert!(page_table.protect(whole, level_size(1), PTEFlags::R));
+    assert_eq!(page_table.walk_leaf(whole).unwrap().1, 1);
+    assert!(!page_table.protect(hole, PAGE_SIZE, PTEFlags::R));
 
-    unsafe { core::slice::from_raw_parts(pa.bits as *const u8, len) }
+    assert!(page_table.unmap_range(va, level_size(2)));
+    for offset in [0, start_offset, level_size(2) - 1] {
+        assert_eq!(page_table.translate(va.add(offset)), None);
+    }
 }
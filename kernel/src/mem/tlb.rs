@@ -0,0 +1,143 @@
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::fmt::{self, Debug, Formatter};
+use lazy_static::*;
+
+use crate::{smp::tlb_shootdown, sync::SpinLock};
+
+use super::address::Addr;
+
+// satp 中 ASID 字段的位置与宽度
+pub const SATP_ASID_SHIFT: usize = 44;
+pub const SATP_ASID_MASK: usize = 0xffff;
+
+// ASID 0 保留给内核页表，硬件不支持 ASID 或 ASID 耗尽时用户页表也使用它
+pub const KERNEL_ASID: usize = 0;
+
+// 刷新某个地址空间中单个虚拟地址的叶子页表项
+pub fn flush_page(va: Addr, asid: usize) {
+    unsafe {
+        asm!("sfence.vma {0}, {1}", in(reg) va.bits, in(reg) asid);
+    }
+}
+
+// 刷新某个地址空间的全部 TLB 表项，包括中间页表的缓存
+pub fn flush_asid(asid: usize) {
+    unsafe {
+        asm!("sfence.vma zero, {0}", in(reg) asid);
+    }
+}
+
+pub fn flush_all() {
+    unsafe {
+        asm!("sfence.vma zero, zero");
+    }
+}
+
+// 页表项被修改或删除后，其他 hart 的 TLB 中也可能缓存着旧表项
+// 本 hart 按地址刷新，其他 hart 刷新整个地址空间
+pub fn shootdown_page(va: Addr, asid: usize) {
+    flush_page(va, asid);
+    tlb_shootdown(asid);
+}
+
+pub fn shootdown_asid(asid: usize) {
+    flush_asid(asid);
+    tlb_shootdown(asid);
+}
+
+// ASID 分配器，回收的 ASID 优先复用
+pub struct AsidAllocator {
+    next: usize,
+    max: usize,
+    recycled: Vec<usize>,
+}
+
+impl AsidAllocator {
+    pub fn empty() -> Self {
+        Self {
+            next: KERNEL_ASID + 1,
+            max: 0,
+            recycled: Vec::new(),
+        }
+    }
+
+    pub fn init(&mut self, asid_bits: usize) {
+        self.max = (1 << asid_bits) - 1;
+    }
+
+    pub fn alloc(&mut self) -> Option<usize> {
+        if let Some(asid) = self.recycled.pop() {
+            Some(asid)
+        } else if self.next <= self.max {
+            self.next += 1;
+            Some(self.next - 1)
+        } else {
+            None
+        }
+    }
+
+    // 调用者须已清除旧地址空间残留在各 hart 的 TLB 中的表项
+    pub fn dealloc(&mut self, asid: usize) {
+        assert!(asid != KERNEL_ASID && asid < self.next);
+        assert!(!self.recycled.contains(&asid), "asid {} freed twice", asid);
+        self.recycled.push(asid);
+    }
+}
+
+lazy_static! {
+    pub static ref ASID_ALLOCATOR: SpinLock<AsidAllocator> =
+        SpinLock::new("asid_allocator", AsidAllocator::empty());
+}
+
+pub struct AsidTracker {
+    asid: usize,
+}
+
+impl Debug for AsidTracker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("AsidTracker: asid = {}", self.asid))
+    }
+}
+
+impl Drop for AsidTracker {
+    fn drop(&mut self) {
+        // 复用前必须清除 TLB，等待其他 hart 响应时不能持有分配器的锁
+        shootdown_asid(self.asid);
+        ASID_ALLOCATOR.lock().dealloc(self.asid);
+    }
+}
+
+impl AsidTracker {
+    pub fn asid(&self) -> usize {
+        self.asid
+    }
+}
+
+pub fn asid_alloc() -> Option<AsidTracker> {
+    ASID_ALLOCATOR
+        .lock()
+        .alloc()
+        .map(|asid| AsidTracker { asid })
+}
+
+// 向 satp 的 ASID 字段写入全 1 后读回，得到硬件实现的 ASID 位数
+// 必须在分页开启后调用
+fn probe_asid_bits() -> usize {
+    let old: usize;
+    let probed: usize;
+    unsafe {
+        asm!("csrr {0}, satp", out(reg) old);
+        asm!("csrw satp, {0}", in(reg) old | (SATP_ASID_MASK << SATP_ASID_SHIFT));
+        asm!("csrr {0}, satp", out(reg) probed);
+        asm!("csrw satp, {0}", in(reg) old);
+    }
+    flush_all();
+    ((probed >> SATP_ASID_SHIFT) & SATP_ASID_MASK).count_ones() as usize
+}
+
+pub fn asid_init() {
+    let asid_bits = probe_asid_bits();
+    ASID_ALLOCATOR.lock().init(asid_bits);
+    println!("[kernel] {} asid bits supported.", asid_bits);
+}
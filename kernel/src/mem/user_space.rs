@@ -1,22 +1,58 @@
-use core::usize::MIN;
-
 use crate::{
-    mem::{address::Addr, page_table::PTEFlags},
-    mem_layout::{PAGE_SIZE, TRAMPOLINE, TRAP_FRAME, USER_STACK_SIZE},
-    sync::UPSafeCell,
+    mem::{
+        address::{Addr, Page},
+        page_table::PTEFlags,
+    },
+    mem_layout::{
+        ARG_MAX, MAX_VIRT_ADDR, PAGE_SIZE, TRAMPOLINE, TRAP_FRAME, TRAP_FRAME_SLOTS,
+        USER_STACK_MAX_SIZE, USER_STACK_SIZE, USER_STACK_TOP,
+    },
 };
-use alloc::{collections::BTreeMap, string::String};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::mem::size_of;
 use lazy_static::*;
 
 use super::{
     page_allocator::{kalloc, PageTracker},
     page_table::PageTable,
+    tlb::asid_alloc,
 };
 
+// 用户页面异常相对于用户栈的位置
+pub enum StackFault {
+    Grown,    // 位于栈的增长范围内，已映射新页面
+    Overflow, // 落在栈底保护页，栈已溢出
+    NoMemory, // 位于栈的增长范围内，但没有内存映射新页面
+    Outside,  // 与用户栈无关
+}
+
+// 辅助向量的类型，取值与 Linux 相同
+const AT_NULL: usize = 0;
+const AT_PAGESZ: usize = 6;
+const AT_ENTRY: usize = 9;
+
+// 初始用户栈上的程序参数，argv 与 envp 为用户虚拟地址
+pub struct UserArgs {
+    pub sp: usize,
+    pub argc: usize,
+    pub argv: usize,
+    pub envp: usize,
+}
+
 pub struct UserSpace {
     page_table: PageTable,
     data_pages: BTreeMap<Addr, PageTracker>,
     size: usize,
+    stack_bottom: Addr,                            // 用户栈当前已映射的最低地址
+    heap_start: Addr,                              // 堆紧接在程序的最后一个段之后
+    brk: Addr,                                     // 程序断点，堆的结束地址
+    trap_frames: [Option<Addr>; TRAP_FRAME_SLOTS], // 已分配的 trapframe 的物理地址
+}
+
+// 第 slot 个 trapframe 的虚拟地址
+pub fn trap_frame_va(slot: usize) -> usize {
+    assert!(slot < TRAP_FRAME_SLOTS);
+    TRAP_FRAME - slot * PAGE_SIZE
 }
 
 impl UserSpace {
@@ -25,19 +61,22 @@ impl UserSpace {
             page_table: PageTable::empty(),
             data_pages: BTreeMap::new(),
             size: 0,
+            stack_bottom: Addr::new(USER_STACK_TOP),
+            heap_start: Addr::empty(),
+            brk: Addr::empty(),
+            trap_frames: [None; TRAP_FRAME_SLOTS],
         }
     }
 
-    fn alloc(&mut self, mut start: Addr, end: Addr, perm: PTEFlags) {
+    // .bss 段要求页面清零，内存不足时返回 false
+    fn alloc(&mut self, mut start: Addr, end: Addr, perm: PTEFlags) -> bool {
         while (start < end) {
-            let page_tracker = kalloc().unwrap();
-            let pa: Addr = page_tracker.page().into();
-            self.data_pages.insert(pa, page_tracker);
-            self.size += PAGE_SIZE;
-            self.page_table.map(start, pa, perm | PTEFlags::U);
-
+            if !self.map_zeroed(start, perm) {
+                return false;
+            }
             start = start.add(PAGE_SIZE);
         }
+        true
     }
 
     fn load_segment(&mut self, va: Addr, data: &[u8]) {
@@ -52,50 +91,302 @@ impl UserSpace {
         }
     }
 
-    // 映射 trampoline 和 trampframe
-    fn init_pagetable(&mut self) -> Addr {
+    // 为一个线程分配并映射 trapframe，返回其编号与物理地址
+    pub fn alloc_trap_frame(&mut self) -> Option<(usize, Addr)> {
+        let slot = self.trap_frames.iter().position(|tf| tf.is_none())?;
+        let page_tracker = kalloc()?;
+        let pa: Addr = page_tracker.page().into();
+        if !self.page_table.try_map(
+            Addr::new(trap_frame_va(slot)),
+            pa,
+            PTEFlags::R | PTEFlags::W,
+        ) {
+            return None;
+        }
+        self.data_pages.insert(pa, page_tracker);
+        self.size += PAGE_SIZE;
+        self.trap_frames[slot] = Some(pa);
+        Some((slot, pa))
+    }
+
+    // 线程结束后回收其 trapframe
+    pub fn dealloc_trap_frame(&mut self, slot: usize) {
+        let pa = self.trap_frames[slot]
+            .take()
+            .expect("trapframe not allocated");
+        self.page_table.unmap(Addr::new(trap_frame_va(slot)));
+        self.data_pages.remove(&pa);
+        self.size -= PAGE_SIZE;
+    }
+
+    // 映射 trampoline 和 trampframe，内存不足时返回 None
+    fn init_pagetable(&mut self) -> Option<Addr> {
         extern "C" {
             fn trampoline();
         }
-        self.page_table.init();
+        if !self.page_table.init() {
+            return None;
+        }
+        self.page_table.set_asid(asid_alloc());
 
         // 映射 trampoline
-        self.page_table.map(
+        if !self.page_table.try_map(
             Addr::new(TRAMPOLINE),
             Addr::new(trampoline as usize),
             PTEFlags::R | PTEFlags::X,
-        );
+        ) {
+            return None;
+        }
 
-        // 为 trapframe 分配内存
-        let page_tracker = kalloc().unwrap();
+        // 为主线程分配并映射 0 号 trapframe，返回其物理地址
+        let (slot, pa) = self.alloc_trap_frame()?;
+        assert_eq!(slot, 0);
+        Some(pa)
+    }
+
+    // 分配一个清零的页面映射到 va，内存不足时返回 false
+    fn map_zeroed(&mut self, va: Addr, perm: PTEFlags) -> bool {
+        let page_tracker = match kalloc() {
+            Some(page_tracker) => page_tracker,
+            None => return false,
+        };
+        page_tracker.page().clean_page();
         let pa: Addr = page_tracker.page().into();
+        if !self.page_table.try_map(va, pa, perm | PTEFlags::U) {
+            return false;
+        }
         self.data_pages.insert(pa, page_tracker);
         self.size += PAGE_SIZE;
+        true
+    }
+
+    // 取消 [start, end) 中页面的映射并释放它们
+    fn unmap_pages(&mut self, mut start: Addr, end: Addr) {
+        while start < end {
+            if let Some(pa) = self.page_table.unmap(start) {
+                self.data_pages.remove(&pa);
+                self.size -= PAGE_SIZE;
+            }
+            start = start.add(PAGE_SIZE);
+        }
+    }
 
-        // 映射 trapframe
-        self.page_table
-            .map(Addr::new(TRAP_FRAME), pa, PTEFlags::R | PTEFlags::W);
+    // 将程序断点移动 increment 字节，返回原来的断点
+    // 超出堆的范围或内存不足时返回 None，此时地址空间保持不变
+    pub fn sbrk(&mut self, increment: isize) -> Option<usize> {
+        let old = self.brk;
+        let new = Addr::new(old.bits.checked_add_signed(increment)?);
+        // 堆不能伸入用户栈下方的保护页
+        let limit = USER_STACK_TOP - USER_STACK_MAX_SIZE - PAGE_SIZE;
+        if new < self.heap_start || new.bits > limit {
+            return None;
+        }
+        let (old_end, new_end) = (old.align_up(), new.align_up());
+        let mut va = old_end;
+        while va < new_end {
+            if !self.map_zeroed(va, PTEFlags::R | PTEFlags::W) {
+                self.unmap_pages(old_end, va);
+                return None;
+            }
+            va = va.add(PAGE_SIZE);
+        }
+        self.unmap_pages(new_end, old_end);
+        self.brk = new;
+        Some(old.bits)
+    }
 
-        // 返回 trapframe 的物理地址
-        pa
+    // 将用户栈向下扩展到 va 所在的页面，内存不足时返回 false，已映射的页面保留
+    fn grow_stack(&mut self, va: Addr) -> bool {
+        let bottom = va.align_down();
+        while (self.stack_bottom > bottom) {
+            let page = Addr::new(self.stack_bottom.bits - PAGE_SIZE);
+            if !self.map_zeroed(page, PTEFlags::R | PTEFlags::W) {
+                return false;
+            }
+            self.stack_bottom = page;
+        }
+        true
     }
 
-    fn init_stack(&mut self, va: Addr) {
-        let mut a = 0usize;
-        while (a < USER_STACK_SIZE) {
-            let page_tracker = kalloc().unwrap();
+    fn init_stack(&mut self) -> bool {
+        self.stack_bottom = Addr::new(USER_STACK_TOP);
+        self.grow_stack(Addr::new(USER_STACK_TOP - USER_STACK_SIZE))
+    }
+
+    // 处理用户页面异常：栈增长范围内按需分配，保护页则报告溢出
+    pub fn handle_stack_fault(&mut self, va: Addr) -> StackFault {
+        let limit = USER_STACK_TOP - USER_STACK_MAX_SIZE;
+        if va.bits >= limit && va.bits < self.stack_bottom.bits {
+            if self.grow_stack(va) {
+                StackFault::Grown
+            } else {
+                StackFault::NoMemory
+            }
+        } else if va.bits >= limit - PAGE_SIZE && va.bits < limit {
+            StackFault::Overflow
+        } else {
+            StackFault::Outside
+        }
+    }
+
+    // 将 data 复制到用户虚拟地址 va 处，目标页面未映射时返回 false
+    pub fn copy_out(&self, mut va: Addr, data: &[u8]) -> bool {
+        let mut copied = 0usize;
+        while (copied < data.len()) {
+            let pa = match self.translate(va) {
+                Some(pa) => pa,
+                None => return false,
+            };
+            let len = core::cmp::min(PAGE_SIZE - va.page_offset(), data.len() - copied);
+            let dst = unsafe { core::slice::from_raw_parts_mut(pa.bits as *mut u8, len) };
+            dst.copy_from_slice(&data[copied..(copied + len)]);
+            copied += len;
+            va = va.add(len);
+        }
+        true
+    }
+
+    // 从用户虚拟地址 va 处读取 buf.len() 个字节，源页面未映射时返回 false
+    pub fn copy_in(&self, mut va: Addr, buf: &mut [u8]) -> bool {
+        let mut copied = 0usize;
+        while (copied < buf.len()) {
+            let pa = match self.translate(va) {
+                Some(pa) => pa,
+                None => return false,
+            };
+            let len = core::cmp::min(PAGE_SIZE - va.page_offset(), buf.len() - copied);
+            let src = unsafe { core::slice::from_raw_parts(pa.bits as *const u8, len) };
+            buf[copied..(copied + len)].copy_from_slice(src);
+            copied += len;
+            va = va.add(len);
+        }
+        true
+    }
+
+    // 读取 va 处以 '\0' 结尾的字符串，超过 max 字节、未映射或不是 UTF-8 时返回 None
+    pub fn copy_in_str(&self, mut va: Addr, max: usize) -> Option<String> {
+        let mut bytes = Vec::new();
+        loop {
+            let mut c = [0u8];
+            if bytes.len() >= max || !self.copy_in(va, &mut c) {
+                return None;
+            }
+            if c[0] == 0 {
+                return String::from_utf8(bytes).ok();
+            }
+            bytes.push(c[0]);
+            va = va.add(1);
+        }
+    }
+
+    // 复制整个用户地址空间供 fork 使用，子进程只有 0 号 trapframe
+    // 返回新的地址空间与其 trapframe 的物理地址，内存不足时返回 None
+    pub fn fork(&self) -> Option<(UserSpace, Addr)> {
+        let mut child = UserSpace::empty();
+        let trap_frame = child.init_pagetable()?;
+        let mut enough = true;
+        self.page_table.visit_leaves(|va, pte, level| {
+            // trampoline 与 trapframe 不带 U 位，已由 init_pagetable() 映射
+            if !pte.user() || !enough {
+                return;
+            }
+            assert_eq!(level, 0, "user pages are never huge pages");
+            let page_tracker = match kalloc() {
+                Some(page_tracker) => page_tracker,
+                None => {
+                    enough = false;
+                    return;
+                }
+            };
+            let src = Page::new(pte.get_addr_bits());
+            page_tracker
+                .page()
+                .get_bytes_mut()
+                .copy_from_slice(src.get_bytes());
             let pa: Addr = page_tracker.page().into();
-            self.data_pages.insert(pa, page_tracker);
-            self.size += PAGE_SIZE;
+            if !child.page_table.try_map(va, pa, pte.flags()) {
+                enough = false;
+                return;
+            }
+            child.data_pages.insert(pa, page_tracker);
+            child.size += PAGE_SIZE;
+        });
+        child.stack_bottom = self.stack_bottom;
+        child.heap_start = self.heap_start;
+        child.brk = self.brk;
+        enough.then_some((child, trap_frame))
+    }
+
+    // 在用户栈顶按 System V 的约定放置程序参数，自高地址向低地址依次为：
+    // argv 与 envp 的字符串、auxv、envp 指针数组、argv 指针数组、argc
+    // 返回时 sp 指向 argc 且 16 字节对齐，总大小超过 ARG_MAX 时返回 None
+    fn push_args(&mut self, argv: &[&str], envp: &[&str], entry: usize) -> Option<UserArgs> {
+        let auxv = [(AT_PAGESZ, PAGE_SIZE), (AT_ENTRY, entry), (AT_NULL, 0)];
+        let strings: usize = argv.iter().chain(envp).map(|s| s.len() + 1).sum();
+        let words = 1 + (argv.len() + 1) + (envp.len() + 1) + auxv.len() * 2;
+        // 两次 16 字节对齐最多再占用 30 字节
+        if strings + words * size_of::<usize>() + 30 > ARG_MAX {
+            return None;
+        }
 
-            self.page_table
-                .map(va.add(a), pa, PTEFlags::R | PTEFlags::W | PTEFlags::U);
-            a += PAGE_SIZE;
+        let strings_bottom = (USER_STACK_TOP - strings) & !0xf;
+        let sp = (strings_bottom - words * size_of::<usize>()) & !0xf;
+        // 内存不足时 exec 失败，不能 panic
+        if !self.grow_stack(Addr::new(sp)) {
+            return None;
+        }
+
+        // 字符串，同时记下各自的地址
+        let mut top = USER_STACK_TOP;
+        let mut push_str = |space: &Self, s: &str| {
+            top -= s.len() + 1;
+            (space.copy_out(Addr::new(top), s.as_bytes())
+                && space.copy_out(Addr::new(top + s.len()), &[0]))
+            .then_some(top)
+        };
+        let argv_ptrs: Vec<usize> = argv
+            .iter()
+            .map(|s| push_str(self, s))
+            .collect::<Option<_>>()?;
+        let envp_ptrs: Vec<usize> = envp
+            .iter()
+            .map(|s| push_str(self, s))
+            .collect::<Option<_>>()?;
+
+        let mut block = Vec::with_capacity(words);
+        block.push(argv.len());
+        block.extend_from_slice(&argv_ptrs);
+        block.push(0);
+        block.extend_from_slice(&envp_ptrs);
+        block.push(0);
+        for (key, value) in auxv {
+            block.push(key);
+            block.push(value);
+        }
+        for (i, word) in block.iter().enumerate() {
+            let va = Addr::new(sp + i * size_of::<usize>());
+            if !self.copy_out(va, &word.to_ne_bytes()) {
+                return None;
+            }
         }
+
+        Some(UserArgs {
+            sp,
+            argc: argv.len(),
+            argv: sp + size_of::<usize>(),
+            envp: sp + (argv.len() + 2) * size_of::<usize>(),
+        })
     }
 
-    pub fn init_from_elf(&mut self, elf_data: &[u8]) -> (Addr, Addr) {
-        let trap_frame = self.init_pagetable();
+    // 加载 elf 并在用户栈上放置 argv 与 envp，返回初始栈上的参数与 0 号 trapframe 的物理地址
+    // 参数过长或内存不足时返回 None
+    pub fn init_from_elf(
+        &mut self,
+        elf_data: &[u8],
+        argv: &[&str],
+        envp: &[&str],
+    ) -> Option<(UserArgs, Addr)> {
+        let trap_frame = self.init_pagetable()?;
 
         let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
         let elf_header = elf.header;
@@ -122,7 +413,9 @@ impl UserSpace {
                 if flags.is_execute() {
                     perm |= PTEFlags::X;
                 }
-                self.alloc(start, end, perm);
+                if !self.alloc(start, end, perm) {
+                    return None;
+                }
                 self.load_segment(
                     start,
                     &elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize],
@@ -131,11 +424,16 @@ impl UserSpace {
             }
         }
 
-        prog_end = prog_end.align_up();
-        self.init_stack(prog_end.add(PAGE_SIZE));
-        let stack_top = prog_end.add(USER_STACK_SIZE + PAGE_SIZE);
+        self.heap_start = prog_end.align_up();
+        self.brk = self.heap_start;
+
+        if !self.init_stack() {
+            return None;
+        }
+        let entry = elf_header.pt2.entry_point() as usize;
+        let args = self.push_args(argv, envp, entry)?;
 
-        (stack_top, trap_frame)
+        Some((args, trap_frame))
     }
     pub fn print_user_pagetable(&self) {
         self.page_table.print_page_table();
@@ -144,22 +442,26 @@ impl UserSpace {
     pub fn make_satp(&self) -> usize {
         self.page_table.make_satp()
     }
-}
 
-pub fn userspace_test() {
-    extern "C" {
-        fn _app_num();
+    // 用户虚拟地址对应的物理地址，仅限用户可访问的页面
+    pub fn translate(&self, va: Addr) -> Option<Addr> {
+        // 地址来自用户，可能超出 Sv39 的范围
+        if va.bits > MAX_VIRT_ADDR {
+            return None;
+        }
+        self.page_table.walk_addr(va)
     }
+}
 
-    let app_num = unsafe { *(_app_num as *const usize) };
-    let app_start =
-        unsafe { core::slice::from_raw_parts((_app_num as usize + 8) as *const usize, app_num) };
-    let app0 = unsafe {
-        core::slice::from_raw_parts(app_start[0] as *const u8, app_start[1] - app_start[0])
-    };
-
+// 从 init 的 ELF 建立用户地址空间
+#[test_case]
+fn userspace_test() {
+    let elf = crate::task::find_app("init").unwrap();
     let mut user = UserSpace::empty();
-    user.init_from_elf(app0);
-    user.print_user_pagetable();
-    println!("user space test success!");
+    let (args, _) = user.init_from_elf(elf, &["init"], &[]).unwrap();
+    assert_eq!(args.argc, 1);
+    assert!(args.sp < USER_STACK_TOP);
+    assert!(user.translate(Addr::new(args.sp)).is_some());
+    assert!(user.translate(Addr::new(args.argv)).is_some());
+    assert_eq!(user.brk, user.heap_start);
 }
@@ -2,31 +2,26 @@
 pub const KERNEL_STACK_SIZE: usize = 0x2000;
 pub const USER_STACK_SIZE: usize = 0x2000;
 pub const KERNEL_HEAP_SIZE: usize = 0x20_0000; // kernel heap allocator size
-pub const PAGE_SIZE: usize = 4096; // bytes per page
-pub const PAGE_BITS: usize = 12; // bits of offset within a page
-pub const PTE_FLAGS_BITS: usize = 10;
-pub const PTE_NUM_PER_PAGE: usize = 1 << PTE_NUM_PER_PAGE_BITS;
-pub const PTE_NUM_PER_PAGE_BITS: usize = 9;
 
-// physical address & virtual address
-pub const PA_WIDTH_SV39: usize = 56;
-pub const VA_WIDTH_SV39: usize = 39;
-pub const MAX_PHYS_ADDR: usize = (1 << PA_WIDTH_SV39) - 1;
-pub const MAX_VIRT_ADDR: usize = (1 << VA_WIDTH_SV39) - 1;
-pub const MAX_PHYS_SIZE: usize = 1 << (PA_WIDTH_SV39 - 1);
-pub const MAX_VIRT_SIZE: usize = 1 << (VA_WIDTH_SV39 - 1);
-
-// physical page number & virtual page number
-pub const PPN_WIDTH_SV39: usize = PA_WIDTH_SV39 - PAGE_BITS;
-pub const VPN_WIDTH_SV39: usize = VA_WIDTH_SV39 - PAGE_BITS;
-pub const MAX_PPN: usize = (1 << PPN_WIDTH_SV39) - 1;
-pub const MAX_VPN: usize = (1 << VPN_WIDTH_SV39) - 1;
+// 页面、页表项与 Sv39 地址宽度的参数
+pub use sv39::{
+    MAX_PHYS_ADDR, MAX_PHYS_SIZE, MAX_PPN, MAX_VIRT_ADDR, MAX_VIRT_SIZE, MAX_VPN, PAGE_BITS,
+    PAGE_SIZE, PA_WIDTH_SV39, PPN_WIDTH_SV39, PTE_FLAGS_BITS, PTE_NUM_PER_PAGE,
+    PTE_NUM_PER_PAGE_BITS, VA_WIDTH_SV39, VPN_WIDTH_SV39,
+};
 
 /* memory layout */
 
 pub const KERNEL_BASE: usize = 0x8020_0000;
-pub const PHYS_TOP: usize = 0x8800_0000;
 pub const TRAMPOLINE: usize = MAX_VIRT_SIZE - PAGE_SIZE;
 pub const TRAP_FRAME: usize = TRAMPOLINE - PAGE_SIZE;
+// 同一地址空间中每个线程各有一个 trapframe，第 i 个位于 TRAP_FRAME - i * PAGE_SIZE
+pub const TRAP_FRAME_SLOTS: usize = 16;
+// 用户栈位于 trapframe 下方，中间隔一个未映射的页面
+pub const USER_STACK_TOP: usize = TRAP_FRAME - TRAP_FRAME_SLOTS * PAGE_SIZE;
+// 用户栈按需增长的上限，其下方一页为保护页
+pub const USER_STACK_MAX_SIZE: usize = 0x10_0000;
+// 程序启动时放在用户栈上的参数、环境变量与辅助向量的总大小上限
+pub const ARG_MAX: usize = 0x2000;
 
 pub const MAX_BUF_SIZE: usize = 1024;
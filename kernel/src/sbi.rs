@@ -1,7 +1,14 @@
+// SBI 客户端
+// 基本扩展报告支持的扩展使用 SBI v1.0 的调用约定（该约定自 v0.2 引入）：
+// a7 = 扩展号, a6 = 功能号, a0/a1 返回 (错误码, 返回值)
+// 固件不支持时退回 v0.1 的旧接口，见 init()
+
 #![allow(unused)]
 
 use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+// legacy v0.1 extensions, each one a single function
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
@@ -12,30 +19,309 @@ const SBI_REMOTE_SFENCE_VMA: usize = 6;
 const SBI_REMOTE_SFENCE_VMA_ASID: usize = 7;
 const SBI_SHUTDOWN: usize = 8;
 
+// v1.0 extensions
+const EID_BASE: usize = 0x10;
+const EID_TIME: usize = 0x54494d45;
+const EID_IPI: usize = 0x735049;
+const EID_RFENCE: usize = 0x52464e43;
+const EID_HSM: usize = 0x48534d;
+const EID_SRST: usize = 0x53525354;
+
+const BASE_GET_SPEC_VERSION: usize = 0;
+const BASE_GET_IMPL_ID: usize = 1;
+const BASE_GET_IMPL_VERSION: usize = 2;
+const BASE_PROBE_EXTENSION: usize = 3;
+
+const TIME_SET_TIMER: usize = 0;
+const IPI_SEND_IPI: usize = 0;
+const RFENCE_REMOTE_FENCE_I: usize = 0;
+const RFENCE_REMOTE_SFENCE_VMA: usize = 1;
+const RFENCE_REMOTE_SFENCE_VMA_ASID: usize = 2;
+const HSM_HART_START: usize = 0;
+const HSM_HART_STOP: usize = 1;
+const HSM_HART_GET_STATUS: usize = 2;
+const SRST_SYSTEM_RESET: usize = 0;
+
+pub const HSM_STATUS_STARTED: usize = 0;
+pub const HSM_STATUS_STOPPED: usize = 1;
+
+// 标准错误码
+pub const SBI_SUCCESS: isize = 0;
+pub const SBI_ERR_FAILED: isize = -1;
+pub const SBI_ERR_NOT_SUPPORTED: isize = -2;
+pub const SBI_ERR_INVALID_PARAM: isize = -3;
+pub const SBI_ERR_DENIED: isize = -4;
+pub const SBI_ERR_INVALID_ADDRESS: isize = -5;
+pub const SBI_ERR_ALREADY_AVAILABLE: isize = -6;
+
+// SRST 的复位类型与原因
+const RESET_TYPE_SHUTDOWN: usize = 0;
+const RESET_REASON_NONE: usize = 0;
+const RESET_REASON_SYSTEM_FAILURE: usize = 1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SbiRet {
+    pub error: isize,
+    pub value: usize,
+}
+
+impl SbiRet {
+    pub fn ok(self) -> Result<usize, isize> {
+        match self.error {
+            SBI_SUCCESS => Ok(self.value),
+            error => Err(error),
+        }
+    }
+}
+
+// 固件支持的扩展，由 init() 探测
+const EXT_TIME: usize = 1 << 0;
+const EXT_IPI: usize = 1 << 1;
+const EXT_RFENCE: usize = 1 << 2;
+const EXT_HSM: usize = 1 << 3;
+const EXT_SRST: usize = 1 << 4;
+
+static EXTENSIONS: AtomicUsize = AtomicUsize::new(0);
+static SPEC_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+fn has(ext: usize) -> bool {
+    EXTENSIONS.load(Ordering::Relaxed) & ext != 0
+}
+
+// v0.1 调用约定：a7 = 功能号, 返回值在 a0
 #[inline(always)]
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
+    sbi_call4(which, [arg0, arg1, arg2, 0])
+}
+
+// 旧接口中只有 SBI_REMOTE_SFENCE_VMA_ASID 需要第 4 个参数
+#[inline(always)]
+fn sbi_call4(which: usize, args: [usize; 4]) -> usize {
     let mut ret;
     unsafe {
         asm!(
             "ecall",
-            inlateout("x10") arg0 => ret,
-            in("x11") arg1,
-            in("x12") arg2,
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
             in("x17") which,
         );
     }
     ret
 }
 
+// v1.0 调用约定：a7 = 扩展号, a6 = 功能号, 返回 (a0 = 错误码, a1 = 返回值)
+#[inline(always)]
+fn sbi_call_ext(eid: usize, fid: usize, args: [usize; 5]) -> SbiRet {
+    let (error, value);
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") args[0] => error,
+            inlateout("x11") args[1] => value,
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x16") fid,
+            in("x17") eid,
+        );
+    }
+    SbiRet { error, value }
+}
+
+fn base_call(fid: usize, arg0: usize) -> SbiRet {
+    sbi_call_ext(EID_BASE, fid, [arg0, 0, 0, 0, 0])
+}
+
+pub fn probe_extension(eid: usize) -> bool {
+    base_call(BASE_PROBE_EXTENSION, eid)
+        .ok()
+        .map_or(false, |available| available != 0)
+}
+
+/// Detect the SBI version and extensions, falls back to the legacy
+/// interface on v0.1 firmware, which lacks the base extension.
+pub fn init() {
+    let version = match base_call(BASE_GET_SPEC_VERSION, 0).ok() {
+        Ok(version) => version,
+        Err(_) => {
+            println!("[kernel] SBI v0.1, using legacy calls.");
+            return;
+        }
+    };
+    SPEC_VERSION.store(version, Ordering::Relaxed);
+
+    let mut extensions = 0;
+    for (eid, ext) in [
+        (EID_TIME, EXT_TIME),
+        (EID_IPI, EXT_IPI),
+        (EID_RFENCE, EXT_RFENCE),
+        (EID_HSM, EXT_HSM),
+        (EID_SRST, EXT_SRST),
+    ] {
+        if probe_extension(eid) {
+            extensions |= ext;
+        }
+    }
+    EXTENSIONS.store(extensions, Ordering::Relaxed);
+
+    let impl_id = base_call(BASE_GET_IMPL_ID, 0).value;
+    let impl_version = base_call(BASE_GET_IMPL_VERSION, 0).value;
+    println!(
+        "[kernel] SBI v{}.{}, implementation {} version {:#x}, extensions:{}{}{}{}{}",
+        (version >> 24) & 0x7f,
+        version & 0xff_ffff,
+        impl_id,
+        impl_version,
+        if has(EXT_TIME) { " TIME" } else { "" },
+        if has(EXT_IPI) { " IPI" } else { "" },
+        if has(EXT_RFENCE) { " RFENCE" } else { "" },
+        if has(EXT_HSM) { " HSM" } else { "" },
+        if has(EXT_SRST) { " SRST" } else { "" },
+    );
+}
+
 pub fn set_timer(timer: usize) {
-    sbi_call(SBI_SET_TIMER, timer, 0, 0);
+    if has(EXT_TIME) {
+        sbi_call_ext(EID_TIME, TIME_SET_TIMER, [timer, 0, 0, 0, 0]);
+    } else {
+        sbi_call(SBI_SET_TIMER, timer, 0, 0);
+    }
 }
 
 pub fn console_putchar(c: usize) {
     sbi_call(SBI_CONSOLE_PUTCHAR, c, 0, 0);
 }
 
-pub fn shutdown() -> ! {
-    sbi_call(SBI_SHUTDOWN, 0, 0, 0);
-    panic!("It should shutdown!");
+// 没有输入时返回 None
+pub fn console_getchar() -> Option<u8> {
+    match sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0) as isize {
+        c if c < 0 => None,
+        c => Some(c as u8),
+    }
+}
+
+// 向 hart_mask 中的 hart 发送核间中断
+pub fn send_ipi(hart_mask: usize) -> Result<(), isize> {
+    if has(EXT_IPI) {
+        sbi_call_ext(EID_IPI, IPI_SEND_IPI, [hart_mask, 0, 0, 0, 0])
+            .ok()
+            .map(|_| ())
+    } else {
+        // 旧接口传入掩码的地址，内核恒等映射，虚拟地址即物理地址
+        let error = sbi_call(SBI_SEND_IPI, &hart_mask as *const usize as usize, 0, 0);
+        ok_legacy(error)
+    }
+}
+
+fn ok_legacy(error: usize) -> Result<(), isize> {
+    match error as isize {
+        SBI_SUCCESS => Ok(()),
+        error => Err(error),
+    }
+}
+
+// 刷新 hart_mask 中各 hart 的指令缓存
+pub fn remote_fence_i(hart_mask: usize) -> Result<(), isize> {
+    if has(EXT_RFENCE) {
+        sbi_call_ext(EID_RFENCE, RFENCE_REMOTE_FENCE_I, [hart_mask, 0, 0, 0, 0])
+            .ok()
+            .map(|_| ())
+    } else {
+        let error = sbi_call(
+            SBI_REMOTE_FENCE_I,
+            &hart_mask as *const usize as usize,
+            0,
+            0,
+        );
+        ok_legacy(error)
+    }
+}
+
+// 在 hart_mask 中的 hart 上刷新 [start, start + size) 的 TLB，size 为 usize::MAX 时全部刷新
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) -> Result<(), isize> {
+    if has(EXT_RFENCE) {
+        sbi_call_ext(
+            EID_RFENCE,
+            RFENCE_REMOTE_SFENCE_VMA,
+            [hart_mask, 0, start, size, 0],
+        )
+        .ok()
+        .map(|_| ())
+    } else {
+        let mask = &hart_mask as *const usize as usize;
+        ok_legacy(sbi_call(SBI_REMOTE_SFENCE_VMA, mask, start, size))
+    }
+}
+
+// 同 remote_sfence_vma()，只刷新地址空间 asid 中的表项
+pub fn remote_sfence_vma_asid(
+    hart_mask: usize,
+    start: usize,
+    size: usize,
+    asid: usize,
+) -> Result<(), isize> {
+    if has(EXT_RFENCE) {
+        sbi_call_ext(
+            EID_RFENCE,
+            RFENCE_REMOTE_SFENCE_VMA_ASID,
+            [hart_mask, 0, start, size, asid],
+        )
+        .ok()
+        .map(|_| ())
+    } else {
+        let mask = &hart_mask as *const usize as usize;
+        ok_legacy(sbi_call4(
+            SBI_REMOTE_SFENCE_VMA_ASID,
+            [mask, start, size, asid],
+        ))
+    }
+}
+
+/// Whether remote TLB fences can be requested from the firmware.
+pub fn has_rfence() -> bool {
+    has(EXT_RFENCE)
+}
+
+// 启动一个处于停止状态的 hart，其从 start_addr 开始执行，a0 = hartid, a1 = opaque
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> Result<(), isize> {
+    if !has(EXT_HSM) {
+        return Err(SBI_ERR_NOT_SUPPORTED);
+    }
+    sbi_call_ext(EID_HSM, HSM_HART_START, [hartid, start_addr, opaque, 0, 0])
+        .ok()
+        .map(|_| ())
+}
+
+// 停止当前 hart，成功时不返回
+pub fn hart_stop() -> isize {
+    sbi_call_ext(EID_HSM, HSM_HART_STOP, [0; 5]).error
+}
+
+// 查询 hart 状态，hartid 不存在或不支持 HSM 时返回 None
+pub fn hart_status(hartid: usize) -> Option<usize> {
+    if !has(EXT_HSM) {
+        return None;
+    }
+    sbi_call_ext(EID_HSM, HSM_HART_GET_STATUS, [hartid, 0, 0, 0, 0])
+        .ok()
+        .ok()
+}
+
+fn system_reset(reset_type: usize, reason: usize) {
+    if has(EXT_SRST) {
+        sbi_call_ext(EID_SRST, SRST_SYSTEM_RESET, [reset_type, reason, 0, 0, 0]);
+    }
+}
+
+// 通过 SRST 扩展关机，failure 作为原因报告给固件
+// 固件不支持 SRST 或关机失败时返回，由调用者改用板子的关机设备
+pub fn shutdown(failure: bool) {
+    let reason = if failure {
+        RESET_REASON_SYSTEM_FAILURE
+    } else {
+        RESET_REASON_NONE
+    };
+    system_reset(RESET_TYPE_SHUTDOWN, reason);
 }
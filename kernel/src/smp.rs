@@ -0,0 +1,154 @@
+//! Secondary hart startup and inter-processor interrupts
+//!
+//! The boot hart starts every other hart through the SBI HSM extension once
+//! the kernel is initialised. Harts talk to each other with supervisor
+//! software interrupts: the sender records what it wants in the target's
+//! pending word, then raises the interrupt through SBI.
+
+use core::arch::asm;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{
+    cpu::{cpu_id, online_harts, MAX_HARTS},
+    machine::machine,
+    mem::tlb::{flush_all, flush_asid},
+    sbi::{
+        hart_start, hart_status, has_rfence, remote_sfence_vma_asid, send_ipi, HSM_STATUS_STOPPED,
+    },
+};
+
+const IPI_RESCHEDULE: usize = 1 << 0;
+const IPI_TLB_FLUSH: usize = 1 << 1;
+
+// TLB_PENDING 中的取值：0 无请求，ASID + 1 刷新该地址空间，FLUSH_ALL 全部刷新
+const FLUSH_ALL: usize = usize::MAX;
+
+const ATOMIC_ZERO: AtomicUsize = AtomicUsize::new(0);
+static IPI_PENDING: [AtomicUsize; MAX_HARTS] = [ATOMIC_ZERO; MAX_HARTS];
+static TLB_PENDING: [AtomicUsize; MAX_HARTS] = [ATOMIC_ZERO; MAX_HARTS];
+
+// 正在空闲循环中等待中断的 hart 掩码
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" {
+    fn _secondary_start();
+}
+
+fn harts(mask: usize) -> impl Iterator<Item = usize> {
+    (0..MAX_HARTS).filter(move |hart| mask & (1 << hart) != 0)
+}
+
+pub fn start_secondary_harts() {
+    let me = cpu_id();
+    for &hartid in machine().hartids().iter().filter(|&&hart| hart != me) {
+        if hart_status(hartid) != Some(HSM_STATUS_STOPPED) {
+            continue;
+        }
+        if let Err(error) = hart_start(hartid, _secondary_start as usize, 0) {
+            println!("[kernel] failed to start hart{}, error {}", hartid, error);
+        }
+    }
+}
+
+// 向 mask 中的 hart 发送核间中断，返回未能送达的 hart 掩码
+// 固件拒绝整个掩码时（例如其中有不存在的 hart）逐个重试，只放弃确实无法送达的 hart
+fn send(mask: usize, kind: usize) -> usize {
+    for hart in harts(mask) {
+        IPI_PENDING[hart].fetch_or(kind, Ordering::AcqRel);
+    }
+    if send_ipi(mask).is_ok() {
+        return 0;
+    }
+    let mut failed = 0;
+    for hart in harts(mask) {
+        if let Err(error) = send_ipi(1 << hart) {
+            println!(
+                "[kernel] failed to send ipi to hart{}, error {}",
+                hart, error
+            );
+            failed |= 1 << hart;
+        }
+    }
+    failed
+}
+
+pub fn set_idle(idle: bool) {
+    if idle {
+        IDLE_HARTS.fetch_or(1 << cpu_id(), Ordering::AcqRel);
+    } else {
+        IDLE_HARTS.fetch_and(!(1 << cpu_id()), Ordering::AcqRel);
+    }
+}
+
+/// Wake one idle hart so that it picks up a newly runnable task.
+pub fn kick_idle_hart() {
+    let idle = IDLE_HARTS.load(Ordering::Acquire) & !(1 << cpu_id());
+    if let Some(hart) = harts(idle).next() {
+        send(1 << hart, IPI_RESCHEDULE);
+    }
+}
+
+/// Make every other online hart drop its TLB entries of `asid`,
+/// returns after all of them have done so.
+pub fn tlb_shootdown(asid: usize) {
+    let others = online_harts() & !(1 << cpu_id());
+    if others == 0 {
+        return;
+    }
+    // 固件同步完成远程刷新，不依赖其他 hart 响应中断
+    if has_rfence() && remote_sfence_vma_asid(others, 0, usize::MAX, asid).is_ok() {
+        return;
+    }
+    for hart in harts(others) {
+        let _ = TLB_PENDING[hart].fetch_update(Ordering::AcqRel, Ordering::Acquire, |old| {
+            Some(if old == 0 || old == asid + 1 {
+                asid + 1
+            } else {
+                FLUSH_ALL
+            })
+        });
+    }
+    // 收不到中断的 hart 无法等待，其请求留在 TLB_PENDING 中，
+    // 在它下一次进入内核获取大内核锁时完成
+    let failed = send(others, IPI_TLB_FLUSH);
+    for hart in harts(others & !failed) {
+        while TLB_PENDING[hart].load(Ordering::Acquire) != 0 {
+            // 其他 hart 可能同时在等待本 hart 刷新
+            service_tlb_flush();
+            spin_loop();
+        }
+    }
+}
+
+/// Carry out TLB flushes requested by other harts. Takes no lock, so it can
+/// run while spinning for one.
+pub fn service_tlb_flush() {
+    let pending = &TLB_PENDING[cpu_id()];
+    loop {
+        let request = pending.load(Ordering::Acquire);
+        match request {
+            0 => return,
+            FLUSH_ALL => flush_all(),
+            asid => flush_asid(asid - 1),
+        }
+        // 刷新期间若有新的请求到达则再刷新一次
+        if pending
+            .compare_exchange(request, 0, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            return;
+        }
+    }
+}
+
+/// Acknowledge a supervisor software interrupt and serve everything pending,
+/// returns whether a reschedule was requested.
+pub fn handle_ipi() -> bool {
+    unsafe {
+        asm!("csrc sip, {0}", in(reg) 1 << 1); // 清除 sip.SSIP
+    }
+    let pending = IPI_PENDING[cpu_id()].swap(0, Ordering::AcqRel);
+    service_tlb_flush();
+    pending & IPI_RESCHEDULE != 0
+}
@@ -0,0 +1,38 @@
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::{cpu::cpu_id, smp::service_tlb_flush};
+
+// 大内核锁：同一时刻只允许一个 hart 在内核中运行，
+// 全局数据各自由 SpinLock 或 SleepLock 保护，这把锁只使内核整体串行执行
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static HOLDER: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+pub fn lock_kernel() {
+    assert!(
+        HOLDER.load(Ordering::Relaxed) != cpu_id() || !LOCKED.load(Ordering::Relaxed),
+        "hart{} acquires the kernel lock twice",
+        cpu_id()
+    );
+    while LOCKED
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        // 持有者可能正在等待本 hart 完成 TLB 刷新
+        service_tlb_flush();
+        spin_loop();
+    }
+    HOLDER.store(cpu_id(), Ordering::Relaxed);
+    // 未能通过核间中断送达的刷新请求在进入内核时补上
+    service_tlb_flush();
+}
+
+pub fn unlock_kernel() {
+    assert!(
+        LOCKED.load(Ordering::Relaxed) && HOLDER.load(Ordering::Relaxed) == cpu_id(),
+        "hart{} releases a kernel lock it does not hold",
+        cpu_id()
+    );
+    HOLDER.store(usize::MAX, Ordering::Relaxed);
+    LOCKED.store(false, Ordering::Release);
+}
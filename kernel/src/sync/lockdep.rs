@@ -0,0 +1,244 @@
+//! Lock dependency checker, enabled by the `lockdep` feature
+//!
+//! Every spin lock belongs to a class named after it. Whenever a lock is
+//! acquired while others are held, an edge `held -> acquired` is added to a
+//! global order graph together with the two call sites. A new edge that
+//! closes a cycle means two code paths take the same locks in opposite
+//! order, which can deadlock even if it has not happened yet.
+//!
+//! Each class also remembers where it was taken in interrupt context and
+//! where it was taken with interrupts enabled. Seeing both means an
+//! interrupt may arrive while the lock is held and spin on it forever.
+
+use alloc::vec::Vec;
+use core::hint::spin_loop;
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cpu::{cpu_id, in_interrupt, MAX_HARTS};
+
+type Site = &'static Location<'static>;
+
+// 每个 hart 最多同时持有的锁数量
+const MAX_HELD: usize = 16;
+
+struct Class {
+    name: &'static str,
+    irq_site: Option<Site>,    // 在中断上下文中获取的位置
+    irq_on_site: Option<Site>, // 在开中断状态下获取的位置
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    from_site: Site,
+    to_site: Site,
+}
+
+struct Graph {
+    classes: Vec<Class>,
+    edges: Vec<Edge>,
+}
+
+#[derive(Clone, Copy)]
+struct Held {
+    class: usize,
+    site: Site,
+}
+
+struct HeldStack {
+    locks: [Option<Held>; MAX_HELD],
+    depth: usize,
+}
+
+const HELD_INIT: HeldStack = HeldStack {
+    locks: [None; MAX_HELD],
+    depth: 0,
+};
+
+// 只由所属 hart 在关中断时访问
+static mut HELD: [HeldStack; MAX_HARTS] = [HELD_INIT; MAX_HARTS];
+
+// 依赖图本身不能使用 SpinLock 保护，否则会递归进入 lockdep
+static GRAPH_LOCKED: AtomicBool = AtomicBool::new(false);
+static mut GRAPH: Graph = Graph {
+    classes: Vec::new(),
+    edges: Vec::new(),
+};
+
+fn with_graph<R>(f: impl FnOnce(&mut Graph) -> R) -> R {
+    while GRAPH_LOCKED
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        spin_loop();
+    }
+    let r = f(unsafe { &mut GRAPH });
+    GRAPH_LOCKED.store(false, Ordering::Release);
+    r
+}
+
+impl Graph {
+    fn class(&mut self, name: &'static str) -> usize {
+        match self.classes.iter().position(|c| c.name == name) {
+            Some(id) => id,
+            None => {
+                self.classes.push(Class {
+                    name,
+                    irq_site: None,
+                    irq_on_site: None,
+                });
+                self.classes.len() - 1
+            }
+        }
+    }
+
+    // 返回是否为新的边
+    fn add_edge(&mut self, from: Held, to: Held) -> bool {
+        if self
+            .edges
+            .iter()
+            .any(|e| e.from == from.class && e.to == to.class)
+        {
+            return false;
+        }
+        self.edges.push(Edge {
+            from: from.class,
+            to: to.class,
+            from_site: from.site,
+            to_site: to.site,
+        });
+        true
+    }
+
+    // 广度优先搜索 from 到 to 的路径，返回途经的边
+    fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut prev: Vec<Option<usize>> = (0..self.classes.len()).map(|_| None).collect();
+        let mut visited: Vec<bool> = (0..self.classes.len()).map(|c| c == from).collect();
+        let mut queue = Vec::from([from]);
+        let mut head = 0;
+        while head < queue.len() {
+            let class = queue[head];
+            head += 1;
+            if class == to {
+                let mut path = Vec::new();
+                let mut c = to;
+                while let Some(e) = prev[c] {
+                    path.push(e);
+                    c = self.edges[e].from;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (i, e) in self.edges.iter().enumerate() {
+                if e.from == class && !visited[e.to] {
+                    visited[e.to] = true;
+                    prev[e.to] = Some(i);
+                    queue.push(e.to);
+                }
+            }
+        }
+        None
+    }
+
+    fn print_edge(&self, e: &Edge) {
+        println!(
+            "[lockdep]   {} acquired at {}\n[lockdep]     while holding {} acquired at {}",
+            self.classes[e.to].name, e.to_site, self.classes[e.from].name, e.from_site
+        );
+    }
+
+    fn report_cycle(&self, new_edge: usize, path: &[usize]) {
+        let e = &self.edges[new_edge];
+        println!(
+            "[lockdep] possible deadlock on hart{}: {} -> {} reverses an existing order",
+            cpu_id(),
+            self.classes[e.from].name,
+            self.classes[e.to].name
+        );
+        println!("[lockdep] new dependency:");
+        self.print_edge(e);
+        println!("[lockdep] existing dependency chain:");
+        for &i in path {
+            self.print_edge(&self.edges[i]);
+        }
+    }
+
+    // 同一类锁既在中断上下文中获取，又在开中断时获取
+    fn check_irq(&self, class: usize) -> bool {
+        let c = &self.classes[class];
+        match (c.irq_site, c.irq_on_site) {
+            (Some(irq), Some(on)) => {
+                println!(
+                    "[lockdep] irq-unsafe lock {} on hart{}:\n[lockdep]   taken in interrupt context at {}\n[lockdep]   taken with interrupts enabled at {}",
+                    c.name,
+                    cpu_id(),
+                    irq,
+                    on
+                );
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+fn held_stack() -> &'static mut HeldStack {
+    unsafe { &mut HELD[cpu_id()] }
+}
+
+/// Record that this hart is about to acquire the lock `name` at `site`.
+/// `irqs_on` tells whether interrupts were enabled before the lock turned
+/// them off. Must be called with interrupts disabled.
+pub fn acquire(name: &'static str, site: Site, irqs_on: bool) {
+    let held = held_stack();
+    let ok = with_graph(|graph| {
+        let class = graph.class(name);
+        let c = &mut graph.classes[class];
+        if in_interrupt() && c.irq_site.is_none() {
+            c.irq_site = Some(site);
+        }
+        if irqs_on && c.irq_on_site.is_none() {
+            c.irq_on_site = Some(site);
+        }
+        if !graph.check_irq(class) {
+            return Err(name);
+        }
+
+        let new = Held { class, site };
+        for h in held.locks[..held.depth].iter().flatten() {
+            // 重复获取同一把锁由 SpinLock 自身报告
+            if h.class == class || !graph.add_edge(*h, new) {
+                continue;
+            }
+            if let Some(path) = graph.path(class, h.class) {
+                graph.report_cycle(graph.edges.len() - 1, &path);
+                return Err(name);
+            }
+        }
+        Ok(class)
+    });
+    match ok {
+        Ok(class) => {
+            assert!(held.depth < MAX_HELD, "lockdep: too many locks held");
+            held.locks[held.depth] = Some(Held { class, site });
+            held.depth += 1;
+        }
+        Err(name) => panic!("lockdep: bad locking of {}", name),
+    }
+}
+
+/// Record that this hart released the lock `name`, not necessarily the
+/// most recently acquired one.
+pub fn release(name: &'static str) {
+    let held = held_stack();
+    let pos = with_graph(|graph| {
+        held.locks[..held.depth]
+            .iter()
+            .rposition(|h| h.map_or(false, |h| graph.classes[h.class].name == name))
+    });
+    let pos = pos.unwrap_or_else(|| panic!("lockdep: release of {} which is not held", name));
+    held.locks.copy_within(pos + 1..held.depth, pos);
+    held.depth -= 1;
+    held.locks[held.depth] = None;
+}
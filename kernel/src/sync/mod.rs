@@ -1,3 +1,9 @@
-mod up;
+mod kernel_lock;
+#[cfg(feature = "lockdep")]
+mod lockdep;
+mod sleep;
+mod spin;
 
-pub use up::UPSafeCell;
+pub use kernel_lock::{lock_kernel, unlock_kernel};
+pub use sleep::{SleepLock, SleepLockGuard};
+pub use spin::{SpinLock, SpinLockGuard};
@@ -0,0 +1,139 @@
+use alloc::collections::VecDeque;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+use crate::task::{running_task_id, sleep_on, wakeup_task};
+
+use super::SpinLock;
+
+// 启动阶段还没有任务，只有启动 hart 在运行，以它作为持有者
+const BOOT_HOLDER: usize = usize::MAX;
+
+fn holder_id() -> usize {
+    running_task_id().unwrap_or(BOOT_HOLDER)
+}
+
+struct SleepLockInner {
+    holder: Option<usize>, // 持有锁的任务
+    waiters: VecDeque<usize>,
+}
+
+// 睡眠锁：获取失败的任务睡眠等待而不是自旋，适合 inode、缓冲区等持有时间较长的资源
+// 持有期间不关闭中断，只能在任务上下文中使用
+pub struct SleepLock<T> {
+    inner: SpinLock<SleepLockInner>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SleepLock<T> {}
+unsafe impl<T: Send> Send for SleepLock<T> {}
+
+pub struct SleepLockGuard<'a, T> {
+    lock: &'a SleepLock<T>,
+}
+
+impl<T> SleepLock<T> {
+    pub fn new(name: &'static str, value: T) -> Self {
+        Self {
+            inner: SpinLock::new(
+                name,
+                SleepLockInner {
+                    holder: None,
+                    waiters: VecDeque::new(),
+                },
+            ),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn lock(&self) -> SleepLockGuard<'_, T> {
+        let id = holder_id();
+        let mut inner = self.inner.lock();
+        while inner.holder.is_some() {
+            assert!(
+                inner.holder != Some(id),
+                "task{} acquires sleeplock {} twice",
+                id,
+                self.inner.name()
+            );
+            assert!(
+                id != BOOT_HOLDER,
+                "sleeplock {} contended outside a task",
+                self.inner.name()
+            );
+            inner.waiters.push_back(id);
+            sleep_on(inner);
+            inner = self.inner.lock();
+        }
+        inner.holder = Some(id);
+        SleepLockGuard { lock: self }
+    }
+
+    // 当前任务是否持有该锁
+    pub fn holding(&self) -> bool {
+        self.inner.lock().holder == Some(holder_id())
+    }
+}
+
+impl<T> Deref for SleepLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SleepLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SleepLockGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut inner = self.lock.inner.lock();
+        inner.holder = None;
+        // 唤醒最早等待的任务，它醒来后重新竞争锁
+        if let Some(waiter) = inner.waiters.pop_front() {
+            drop(inner);
+            wakeup_task(waiter);
+        }
+    }
+}
+
+#[test_case]
+fn sleeplock_lock_unlock_test() {
+    let lock = SleepLock::new("test", 0);
+    {
+        let mut guard = lock.lock();
+        *guard += 1;
+    }
+    // 释放后可以再次获取
+    assert_eq!(*lock.lock(), 1);
+}
+
+#[test_case]
+fn sleeplock_holder_test() {
+    let lock = SleepLock::new("test", ());
+    assert!(!lock.holding());
+    let guard = lock.lock();
+    assert!(lock.holding());
+    drop(guard);
+    assert!(!lock.holding());
+    // 被其他任务持有时当前任务不是持有者
+    lock.inner.lock().holder = Some(1);
+    assert!(!lock.holding());
+}
+
+#[test_case]
+fn sleeplock_wakeup_test() {
+    // 释放锁时按等待的先后唤醒一个任务，它醒来后重新竞争锁
+    let lock = SleepLock::new("test", ());
+    let guard = lock.lock();
+    lock.inner.lock().waiters.extend([2, 3]);
+    drop(guard);
+    let inner = lock.inner.lock();
+    assert_eq!(inner.holder, None);
+    assert_eq!(inner.waiters.len(), 1);
+    assert_eq!(inner.waiters.front(), Some(&3));
+}
@@ -0,0 +1,120 @@
+use core::cell::UnsafeCell;
+use core::fmt::{self, Debug, Formatter};
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+use crate::{
+    cpu::{cpu_id, pop_off, push_off},
+    smp::service_tlb_flush,
+};
+
+const NO_HOLDER: usize = usize::MAX;
+
+// 自旋锁：持有期间关闭本 hart 的中断，避免中断处理程序再次获取同一把锁而死锁
+// 不可重入，同一 hart 重复获取会 panic
+pub struct SpinLock<T> {
+    name: &'static str,
+    locked: AtomicBool,
+    holder: AtomicUsize,                                      // 持有锁的 hart
+    location: UnsafeCell<Option<&'static Location<'static>>>, // 获取锁的位置，仅由持有者写入
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> SpinLock<T> {
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            name,
+            locked: AtomicBool::new(false),
+            holder: AtomicUsize::new(NO_HOLDER),
+            location: UnsafeCell::new(None),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        let irqs_on = sstatus::read().sie();
+        push_off();
+        if self.holding() {
+            panic!(
+                "hart{} acquires spinlock {} twice, first at {}",
+                cpu_id(),
+                self.name,
+                unsafe { (*self.location.get()).unwrap() }
+            );
+        }
+        // 在自旋之前检查，真的发生死锁时也能给出报告
+        #[cfg(feature = "lockdep")]
+        super::lockdep::acquire(self.name, Location::caller(), irqs_on);
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // 持有者可能正在等待本 hart 完成 TLB 刷新
+            service_tlb_flush();
+            spin_loop();
+        }
+        self.holder.store(cpu_id(), Ordering::Relaxed);
+        unsafe {
+            *self.location.get() = Some(Location::caller());
+        }
+        SpinLockGuard { lock: self }
+    }
+
+    // 当前 hart 是否持有该锁，须在关中断时调用
+    pub fn holding(&self) -> bool {
+        self.locked.load(Ordering::Relaxed) && self.holder.load(Ordering::Relaxed) == cpu_id()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Debug for SpinLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.holder.load(Ordering::Relaxed) {
+            NO_HOLDER => f.write_fmt(format_args!("SpinLock {}: free", self.name)),
+            hart => f.write_fmt(format_args!("SpinLock {}: held by hart{}", self.name, hart)),
+        }
+    }
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        assert!(self.lock.holding(), "release spinlock {}", self.lock.name);
+        unsafe {
+            *self.lock.location.get() = None;
+        }
+        self.lock.holder.store(NO_HOLDER, Ordering::Relaxed);
+        self.lock.locked.store(false, Ordering::Release);
+        #[cfg(feature = "lockdep")]
+        super::lockdep::release(self.lock.name);
+        pop_off();
+    }
+}
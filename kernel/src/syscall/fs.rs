@@ -1,31 +1,218 @@
 //! File and filesystem-related syscalls
 
+use alloc::{string::String, sync::Arc, vec};
+
 use crate::{
-    mem::{address::Addr, copy_from_user},
-    mem_layout::PAGE_BITS,
-    task::current_user_satp,
+    fs::{
+        make_pipe, open,
+        ramfs::{FS, T_DIR},
+        File,
+    },
+    mem_layout::PAGE_SIZE,
+    task::{
+        current_copy_in, current_copy_in_str, current_copy_out, current_cwd, current_files,
+        cwd_in_use, set_current_cwd,
+    },
 };
 
-const FD_SDTIN: usize = 0;
-const FD_STDOUT: usize = 1;
-const FD_STDERR: usize = 2;
+// 每次在内核与用户之间搬运的最大字节数
+const CHUNK_SIZE: usize = PAGE_SIZE;
+// 路径长度上限
+const MAX_PATH: usize = 128;
+
+fn copy_in_path(path: usize) -> Option<String> {
+    current_copy_in_str(path, MAX_PATH)
+}
+
+fn get_file(fd: usize) -> Option<Arc<dyn File>> {
+    current_files()?.lock().get(fd)
+}
 
 /// write buf of length `len`  to a file with `fd`
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    let buf_usize = buf as usize;
-    match fd {
-        FD_STDOUT => {
-            let buffer = copy_from_user(
-                Addr::new(current_user_satp() << PAGE_BITS),
-                Addr::new(buf_usize),
-                len,
-            );
-            let string = unsafe { core::str::from_utf8(buffer).unwrap() };
-            print!("{}", string);
-            len as isize
+    let file = match get_file(fd) {
+        Some(file) if file.writable() => file,
+        _ => return -1,
+    };
+    let mut chunk = vec![0u8; len.min(CHUNK_SIZE)];
+    let mut written = 0;
+    while written < len {
+        let n = (len - written).min(CHUNK_SIZE);
+        if !current_copy_in(buf as usize + written, &mut chunk[..n]) {
+            return -1;
+        }
+        let ret = file.write(&chunk[..n]);
+        if ret < 0 {
+            return if written > 0 { written as isize } else { ret };
+        }
+        written += ret as usize;
+    }
+    written as isize
+}
+
+/// read at most `len` bytes from `fd` into `buf`, returns 0 at end of file
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let file = match get_file(fd) {
+        Some(file) if file.readable() => file,
+        _ => return -1,
+    };
+    let mut chunk = vec![0u8; len.min(CHUNK_SIZE)];
+    let n = file.read(&mut chunk);
+    if n > 0 && !current_copy_out(buf as usize, &chunk[..n as usize]) {
+        return -1;
+    }
+    n
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1,
+    };
+    let file = files.lock().close(fd);
+    // 在释放描述符表的锁后关闭文件
+    file.map_or(-1, |_| 0)
+}
+
+/// a new descriptor for the file `fd` refers to, the lowest one free
+pub fn sys_dup(fd: usize) -> isize {
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1,
+    };
+    let mut table = files.lock();
+    match table.get(fd) {
+        Some(file) => table.alloc(file).map_or(-1, |fd| fd as isize),
+        None => -1,
+    }
+}
+
+/// create a pipe, stores the read and write descriptors in `fds[0]` and `fds[1]`
+pub fn sys_pipe(fds: *mut u32) -> isize {
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1,
+    };
+    let (reader, writer) = make_pipe();
+    let mut table = files.lock();
+    let read_fd = match table.alloc(reader) {
+        Some(fd) => fd,
+        None => return -1,
+    };
+    let write_fd = match table.alloc(writer) {
+        Some(fd) => fd,
+        None => {
+            let reader = table.close(read_fd);
+            drop(table);
+            drop(reader);
+            return -1;
         }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
+    };
+    drop(table);
+
+    let mut pair = [0u8; 8];
+    pair[..4].copy_from_slice(&(read_fd as u32).to_ne_bytes());
+    pair[4..].copy_from_slice(&(write_fd as u32).to_ne_bytes());
+    if !current_copy_out(fds as usize, &pair) {
+        let mut table = files.lock();
+        let closed = (table.close(read_fd), table.close(write_fd));
+        drop(table);
+        drop(closed);
+        return -1;
+    }
+    0
+}
+
+/// open `path` with the `O_*` `flags`, returns the new descriptor
+pub fn sys_open(path: usize, flags: u32) -> isize {
+    let path = match copy_in_path(path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1,
+    };
+    let file = match open(current_cwd(), &path, flags) {
+        Some(file) => file,
+        None => return -1,
+    };
+    // 没有空闲描述符时在释放锁之后关闭文件
+    let fd = files.lock().alloc(file.clone());
+    fd.map_or(-1, |fd| fd as isize)
+}
+
+/// store the status of the file `fd` at `stat`
+pub fn sys_fstat(fd: usize, stat: usize) -> isize {
+    let stat_bytes = match get_file(fd).and_then(|file| file.stat()) {
+        Some(stat) => stat.to_bytes(),
+        None => return -1,
+    };
+    if current_copy_out(stat, &stat_bytes) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_mkdir(path: usize) -> isize {
+    let path = match copy_in_path(path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let cwd = current_cwd();
+    let mut fs = FS.lock();
+    match fs.create(cwd, &path, T_DIR) {
+        Some(ino) => {
+            fs.release(ino);
+            0
         }
+        None => -1,
+    }
+}
+
+/// change the working directory of the current process
+pub fn sys_chdir(path: usize) -> isize {
+    let path = match copy_in_path(path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let cwd = current_cwd();
+    let fs = FS.lock();
+    match fs.lookup(cwd, &path) {
+        Some(ino) if fs.is_dir(ino) => {
+            drop(fs);
+            set_current_cwd(ino);
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// create the name `new` for the file `old`
+pub fn sys_link(old: usize, new: usize) -> isize {
+    let (old, new) = match (copy_in_path(old), copy_in_path(new)) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return -1,
+    };
+    let cwd = current_cwd();
+    FS.lock().link(cwd, &old, &new).map_or(-1, |_| 0)
+}
+
+/// remove the name `path`, directories must be empty and not the working
+/// directory of any process
+pub fn sys_unlink(path: usize) -> isize {
+    let path = match copy_in_path(path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let cwd = current_cwd();
+    let ino = match FS.lock().lookup(cwd, &path) {
+        Some(ino) => ino,
+        None => return -1,
+    };
+    if cwd_in_use(ino) {
+        return -1;
     }
+    FS.lock().unlink(cwd, &path).map_or(-1, |_| 0)
 }
@@ -31,18 +31,53 @@ pub const SYS_UNLINK: usize = 18;
 pub const SYS_LINK: usize = 19;
 pub const SYS_MKDIR: usize = 20;
 pub const SYS_CLOSE: usize = 21;
+pub const SYS_SETPRIORITY: usize = 22;
+pub const SYS_SHUTDOWN: usize = 23;
+pub const SYS_FUTEX_WAIT: usize = 24;
+pub const SYS_FUTEX_WAKE: usize = 25;
+pub const SYS_CLONE: usize = 26;
+pub const SYS_JOIN: usize = 27;
 
 mod fs;
 mod process;
+mod sync;
 
 use fs::*;
 use process::*;
+use sync::*;
 
 /// handle syscall exception with `syscall_id` and other arguments
 pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
     match syscall_id {
-        SYS_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYS_FORK => sys_fork(),
         SYS_EXIT => sys_exit(args[0] as i32),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYS_WAIT => sys_wait(args[0] as isize, args[1], args[2]),
+        SYS_PIPE => sys_pipe(args[0] as *mut u32),
+        SYS_READ => sys_read(args[0], args[1] as *mut u8, args[2]),
+        SYS_KILL => sys_kill(args[0]),
+        SYS_EXEC => sys_exec(args[0], args[1], args[2]),
+        SYS_FSTAT => sys_fstat(args[0], args[1]),
+        SYS_CHDIR => sys_chdir(args[0]),
+        SYS_DUP => sys_dup(args[0]),
+        SYS_GETPID => sys_getpid(),
+        SYS_SBRK => sys_sbrk(args[0] as isize),
+        SYS_OPEN => sys_open(args[0], args[1] as u32),
+        SYS_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYS_UNLINK => sys_unlink(args[0]),
+        SYS_LINK => sys_link(args[0], args[1]),
+        SYS_MKDIR => sys_mkdir(args[0]),
+        SYS_CLOSE => sys_close(args[0]),
+        SYS_SLEEP => sys_sleep(args[0]),
+        SYS_UPTIME => sys_uptime(),
+        SYS_SETPRIORITY => sys_set_priority(args[0] as isize),
+        SYS_SHUTDOWN => sys_shutdown(args[0] as u32),
+        SYS_FUTEX_WAIT => sys_futex_wait(args[0], args[1] as u32, args[2]),
+        SYS_FUTEX_WAKE => sys_futex_wake(args[0], args[1]),
+        SYS_CLONE => sys_clone(args[0], args[1]),
+        SYS_JOIN => sys_join(args[0]),
+        _ => {
+            println!("[kernel] Unsupported syscall_id: {}", syscall_id);
+            -1
+        }
     }
 }
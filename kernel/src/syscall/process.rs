@@ -1,10 +1,167 @@
 //! App management syscalls
 
-use crate::task::run_next_task_kill;
+use alloc::{string::String, vec::Vec};
+
+use crate::{
+    board::{Board, BOARD},
+    task::{
+        clone_current_task, current_copy_in, current_copy_in_str, current_copy_out, current_killed,
+        current_pid, current_sbrk, current_task_id, exec_current_task, find_app, fork_current_task,
+        join_task, kill_task, param::MAX_PRIORITY, run_next_task_block, run_next_task_exit,
+        set_current_priority, wait_child,
+    },
+    timer::{add_timer, cancel_timer, get_time, get_time_ms, ms_to_cycles, TimerEvent},
+};
 
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> isize {
-    println!("[kernel] Task exited with code {}", exit_code);
-    run_next_task_kill();
+    run_next_task_exit(exit_code);
     0
 }
+
+/// create a copy of the current process, returns 0 in the child and the
+/// child's pid in the parent
+pub fn sys_fork() -> isize {
+    fork_current_task().map_or(-1, |pid| pid as isize)
+}
+
+// exec 参数的数量与每个字符串的长度上限
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 256;
+
+// 读取用户态以空指针结尾的字符串指针数组，addr 为 0 时视为空数组
+fn copy_in_str_array(addr: usize) -> Option<Vec<String>> {
+    let mut strings = Vec::new();
+    if addr == 0 {
+        return Some(strings);
+    }
+    loop {
+        let mut ptr = [0u8; 8];
+        if strings.len() > MAX_ARGS || !current_copy_in(addr + strings.len() * 8, &mut ptr) {
+            return None;
+        }
+        match usize::from_ne_bytes(ptr) {
+            0 => return Some(strings),
+            ptr => strings.push(current_copy_in_str(ptr, MAX_ARG_LEN)?),
+        }
+    }
+}
+
+/// replace the current program with the application `path`, passing it the
+/// null-terminated arrays `argv` and `envp`. Applications are looked up by
+/// name among those built into the kernel, a leading `/` is ignored.
+/// Does not return on success.
+pub fn sys_exec(path: usize, argv: usize, envp: usize) -> isize {
+    let path = match current_copy_in_str(path, MAX_ARG_LEN) {
+        Some(path) => path,
+        None => return -1,
+    };
+    let elf_data = match find_app(path.trim_start_matches('/')) {
+        Some(elf_data) => elf_data,
+        None => return -1,
+    };
+    let (argv, envp) = match (copy_in_str_array(argv), copy_in_str_array(envp)) {
+        (Some(argv), Some(envp)) => (argv, envp),
+        _ => return -1,
+    };
+    let argv: Vec<&str> = argv.iter().map(|s| s.as_str()).collect();
+    let envp: Vec<&str> = envp.iter().map(|s| s.as_str()).collect();
+    // 成功时 a0 即新程序的 argc
+    exec_current_task(elf_data, &argv, &envp).map_or(-1, |argc| argc as isize)
+}
+
+/// `waitpid` option: return 0 instead of blocking when no child has exited.
+pub const WNOHANG: usize = 1;
+
+/// grow the heap of the current process by `increment` bytes, or shrink it
+/// when negative. Returns the old end of the heap, or -1 when out of memory
+/// or out of range. New memory is zeroed.
+pub fn sys_sbrk(increment: isize) -> isize {
+    current_sbrk(increment).map_or(-1, |brk| brk as isize)
+}
+
+/// kill the process `pid`, it exits with -1 the next time it would return to
+/// user mode. Sleeping threads of it are woken first.
+pub fn sys_kill(pid: usize) -> isize {
+    if kill_task(pid) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// wait for child `pid` to exit, any child if `pid` is -1, and reclaim it.
+/// Stores its exit code at `status` unless it is null, returns its pid, or
+/// -1 when there is no such child.
+pub fn sys_wait(pid: isize, status: usize, options: usize) -> isize {
+    let pid = if pid == -1 { None } else { Some(pid as usize) };
+    match wait_child(pid, options & WNOHANG != 0) {
+        Some((0, _)) => 0,
+        Some((pid, exit_code)) => {
+            if status != 0 && !current_copy_out(status, &exit_code.to_ne_bytes()) {
+                return -1;
+            }
+            pid as isize
+        }
+        None => -1,
+    }
+}
+
+pub fn sys_getpid() -> isize {
+    current_pid() as isize
+}
+
+/// The new thread shares the address space of its creator.
+/// No other kind of clone is supported yet.
+pub const CLONE_VM: usize = 0x100;
+
+/// create a thread running on the user stack `stack`, which returns 0 from
+/// the same call, returns the new thread id to the caller
+pub fn sys_clone(stack: usize, flags: usize) -> isize {
+    if flags & CLONE_VM == 0 || stack == 0 || stack % 16 != 0 {
+        return -1;
+    }
+    clone_current_task(stack).map_or(-1, |id| id as isize)
+}
+
+/// wait for thread `id` of the same process to exit, returns its exit code
+pub fn sys_join(id: usize) -> isize {
+    join_task(id).map_or(-1, |exit_code| exit_code as isize)
+}
+
+/// block the current task for `ms` milliseconds, returns -1 early if it is killed
+pub fn sys_sleep(ms: usize) -> isize {
+    let deadline = get_time().saturating_add(ms_to_cycles(ms));
+    let event = TimerEvent::Wakeup(current_task_id());
+    // 管道、kill 等也会唤醒任务，未到期时继续睡眠
+    // 每次醒来都取消定时器，否则它会唤醒之后使用这个任务槽的任务
+    while get_time() < deadline {
+        if current_killed() {
+            return -1;
+        }
+        add_timer(deadline, event);
+        run_next_task_block();
+        cancel_timer(event);
+    }
+    0
+}
+
+/// milliseconds since boot
+pub fn sys_uptime() -> isize {
+    get_time_ms() as isize
+}
+
+/// power off the machine, a nonzero `code` is reported to the host as failure
+pub fn sys_shutdown(code: u32) -> isize {
+    println!("[kernel] Shutdown with code {}", code);
+    BOARD.exit(code)
+}
+
+/// set the scheduling weight of the current task, must be in 1..=MAX_PRIORITY
+pub fn sys_set_priority(priority: isize) -> isize {
+    if priority <= 0 || priority as usize > MAX_PRIORITY {
+        return -1;
+    }
+    set_current_priority(priority as usize);
+    priority
+}
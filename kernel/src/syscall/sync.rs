@@ -0,0 +1,102 @@
+//! Futex syscalls
+//!
+//! Waiters are queued by the physical address of the futex word, so two
+//! address spaces sharing the page meet in the same queue whatever virtual
+//! address each of them uses.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::*;
+
+use crate::{
+    sync::SpinLock,
+    task::{current_task_id, current_user_translate, sleep_on, wakeup_task},
+    timer::{add_timer, cancel_timer, get_time, ms_to_cycles, TimerEvent},
+};
+
+/// The futex word did not hold the expected value.
+pub const FUTEX_EAGAIN: isize = -2;
+/// The timeout expired before anyone woke the waiter.
+pub const FUTEX_ETIMEDOUT: isize = -3;
+
+lazy_static! {
+    // 物理地址 -> 等待的任务，按到达顺序唤醒
+    static ref FUTEX_QUEUES: SpinLock<BTreeMap<usize, VecDeque<usize>>> =
+        SpinLock::new("futex", BTreeMap::new());
+}
+
+fn futex_key(addr: usize) -> Option<usize> {
+    if addr % core::mem::size_of::<u32>() != 0 {
+        return None;
+    }
+    current_user_translate(addr)
+}
+
+/// block while the u32 at `addr` equals `expected`, for at most `timeout`
+/// milliseconds unless `timeout` is 0
+pub fn sys_futex_wait(addr: usize, expected: u32, timeout: usize) -> isize {
+    let key = match futex_key(addr) {
+        Some(key) => key,
+        None => return -1,
+    };
+    let id = current_task_id();
+    let mut queues = FUTEX_QUEUES.lock();
+    // 持有队列锁时比较，futex_wake 无法插入到比较与入队之间
+    let word = unsafe { &*(key as *const AtomicU32) };
+    if word.load(Ordering::SeqCst) != expected {
+        return FUTEX_EAGAIN;
+    }
+    queues.entry(key).or_default().push_back(id);
+    if timeout > 0 {
+        add_timer(
+            get_time().saturating_add(ms_to_cycles(timeout)),
+            TimerEvent::Wakeup(id),
+        );
+    }
+    sleep_on(queues);
+
+    if timeout > 0 {
+        cancel_timer(TimerEvent::Wakeup(id));
+    }
+    // 仍在队列中说明是被定时器唤醒的
+    let mut queues = FUTEX_QUEUES.lock();
+    let queue = match queues.get_mut(&key) {
+        Some(queue) => queue,
+        None => return 0,
+    };
+    match queue.iter().position(|&waiter| waiter == id) {
+        Some(pos) => {
+            queue.remove(pos);
+            if queue.is_empty() {
+                queues.remove(&key);
+            }
+            FUTEX_ETIMEDOUT
+        }
+        None => 0,
+    }
+}
+
+/// wake at most `n` tasks waiting on the u32 at `addr`, returns how many were woken
+pub fn sys_futex_wake(addr: usize, n: usize) -> isize {
+    let key = match futex_key(addr) {
+        Some(key) => key,
+        None => return -1,
+    };
+    let mut queues = FUTEX_QUEUES.lock();
+    let woken: VecDeque<usize> = match queues.get_mut(&key) {
+        Some(queue) => {
+            let woken = queue.drain(..n.min(queue.len())).collect();
+            if queue.is_empty() {
+                queues.remove(&key);
+            }
+            woken
+        }
+        None => VecDeque::new(),
+    };
+    drop(queues);
+
+    for &id in woken.iter() {
+        wakeup_task(id);
+    }
+    woken.len() as isize
+}
@@ -9,7 +9,7 @@ pub struct TaskContext {
 }
 
 impl TaskContext {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             s: [0; 12],
             ra: 0,
@@ -20,4 +20,14 @@ impl TaskContext {
         self.ra = user_trap_return as usize;
         self.sp = ksp;
     }
+
+    // 内核线程第一次被调度时经 kernel_thread_start 进入 entry，entry 保存在 s0 中
+    pub fn init_kernel(&mut self, ksp: usize, entry: usize) {
+        extern "C" {
+            fn kernel_thread_start();
+        }
+        self.ra = kernel_thread_start as usize;
+        self.sp = ksp;
+        self.s[0] = entry;
+    }
 }
@@ -1,5 +1,6 @@
 extern "C" {
     fn _app_num();
+    fn _app_names();
 }
 
 pub fn get_app_num() -> usize {
@@ -14,3 +15,22 @@ pub fn get_app_data(id: usize) -> &'static [u8] {
         core::slice::from_raw_parts((*app_start) as *const u8, *(app_start.add(1)) - *app_start)
     }
 }
+
+// 应用名依次存放在 _app_names 处，各以 '\0' 结尾，用作 argv[0]
+pub fn get_app_name(id: usize) -> &'static str {
+    assert!(id < get_app_num());
+    let mut name = _app_names as usize as *const u8;
+    unsafe {
+        for _ in 0..id {
+            while name.read() != 0 {
+                name = name.add(1);
+            }
+            name = name.add(1);
+        }
+        let mut len = 0;
+        while name.add(len).read() != 0 {
+            len += 1;
+        }
+        core::str::from_utf8(core::slice::from_raw_parts(name, len)).unwrap()
+    }
+}
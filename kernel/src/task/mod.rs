@@ -1,18 +1,27 @@
 use core::arch::global_asm;
 
-use alloc::vec::Vec;
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 use lazy_static::lazy_static;
 
 use crate::{
-    board::{QEMUExit, QEMU_EXIT_HANDLE},
-    sync::UPSafeCell,
-    trap::TrapContext,
+    board::{Board, BOARD},
+    fs::FdTable,
+    mem::{
+        address::Addr,
+        user_space::{StackFault, UserSpace},
+    },
+    smp::{kick_idle_hart, set_idle},
+    sync::{lock_kernel, unlock_kernel, SpinLock, SpinLockGuard},
+    timer::{cancel_timer, start_quantum, stop_quantum, TimerEvent},
+    trap::{wait_for_interrupt, TrapContext},
 };
 
 use self::{
     context::TaskContext,
-    loader::{get_app_data, get_app_num},
-    param::MAX_APP_NUM,
+    loader::{get_app_data, get_app_name, get_app_num},
+    param::{INIT_APP, INIT_PID, MAX_APP_NUM},
+    processor::current_processor,
+    scheduler::{create_scheduler, Scheduler, DEFAULT_SCHEDULER},
     task::{TaskControlBlock, TaskStatus},
 };
 
@@ -21,6 +30,8 @@ global_asm!(include_str!("switch.S"));
 mod context;
 mod loader;
 pub mod param;
+mod processor;
+mod scheduler;
 #[allow(clippy::module_inception)]
 pub mod task;
 
@@ -30,133 +41,631 @@ extern "C" {
 
 pub struct TaskManagerInner {
     tasks: Vec<TaskControlBlock>,
-    current: usize,
+    scheduler: Box<dyn Scheduler + Send>,
 }
 
 pub struct TaskManager {
-    task_num: usize,
-    inner: UPSafeCell<TaskManagerInner>,
+    inner: SpinLock<TaskManagerInner>,
 }
 
 lazy_static! {
     pub static ref TASK_MANAGER: TaskManager = TaskManager {
-        task_num: get_app_num(),
-        inner: UPSafeCell::new({
+        inner: SpinLock::new("task_manager", {
             let tasks: Vec<TaskControlBlock> =
                 (0..MAX_APP_NUM).map(|_| TaskControlBlock::new()).collect();
-            TaskManagerInner { tasks, current: 0 }
+            TaskManagerInner {
+                tasks,
+                scheduler: create_scheduler(DEFAULT_SCHEDULER).unwrap(),
+            }
         })
     };
 }
 
 impl TaskManager {
+    // 只启动 init 进程，其他应用由它通过 fork 与 exec 运行
     fn load_tasks(&self) {
-        let mut inner = self.inner.get_mut();
-        let tasks = &mut inner.tasks;
-
-        for id in 0..self.task_num {
-            tasks[id].init_from_elf(get_app_data(id), id);
+        let mut inner = self.inner.lock();
+        let elf_data = find_app(INIT_APP).expect("init not found");
+        // 没有 init 系统无法运行
+        if !inner.tasks[INIT_PID].init_from_elf(elf_data, &[INIT_APP], &[], INIT_PID) {
+            panic!("failed to load {}", INIT_APP);
         }
+        inner.scheduler.on_create(INIT_PID);
+        inner.scheduler.enqueue(INIT_PID);
+        println!("[kernel] using {} scheduler.", inner.scheduler.name());
     }
 
-    fn run_first_task(&self) -> ! {
-        let mut inner = self.inner.get_mut();
-        let current = inner.current;
-        inner.tasks[current].status = TaskStatus::Running;
-        let mut zero = TaskContext::new();
-        let first_cx = &mut inner.tasks[current].context as *const TaskContext;
-        drop(inner);
+    // 每个 hart 的空闲循环：选取可运行的任务执行，任务让出 CPU 时切换回这里
+    // 没有可运行的任务时用 wfi 等待中断，所有任务结束后关机
+    // 进入时不持有大内核锁，切换到任务时锁随之转交给任务
+    fn run_tasks(&self) -> ! {
+        lock_kernel();
+        loop {
+            let mut inner = self.inner.lock();
+            if let Some(next) = inner.scheduler.pick_next() {
+                inner.tasks[next].status = TaskStatus::Running;
+                let processor = current_processor();
+                processor.current = Some(next);
+                let idle_cx = &mut processor.idle_context as *mut TaskContext;
+                let next_cx = &inner.tasks[next].context as *const TaskContext;
+                drop(inner);
 
-        println!("[kernle] task{} running.", current);
-        unsafe {
-            switch(&mut zero as *mut TaskContext, first_cx);
+                start_quantum();
+                unsafe {
+                    switch(idle_cx, next_cx);
+                }
+                current_processor().current = None;
+
+                // 内核线程没有父进程也不能被 join，结束后由空闲循环回收
+                // 回收须在切换回空闲循环之后，此前它仍在使用自己的内核栈
+                let mut inner = self.inner.lock();
+                let task = &mut inner.tasks[next];
+                if task.is_kernel_thread() && task.status == TaskStatus::Zombie {
+                    task.clear();
+                }
+            } else if inner.tasks.iter().any(|task| {
+                // 后台内核线程不阻止关机
+                !task.is_kernel_thread()
+                    && (task.status == TaskStatus::Sleeping || task.status == TaskStatus::Running)
+            }) {
+                drop(inner);
+                stop_quantum();
+                set_idle(true);
+                unlock_kernel();
+                wait_for_interrupt();
+                lock_kernel();
+                set_idle(false);
+            } else {
+                drop(inner);
+                println!("[kernel] All tasks completed!");
+                BOARD.exit_success();
+            }
         }
-        panic!("Unreachaable in run_first_task!");
     }
 
     fn mark_current_runnable(&self) {
-        let mut inner = self.inner.get_mut();
-        let current = inner.current;
+        let mut inner = self.inner.lock();
+        let current = current_id();
         inner.tasks[current].status = TaskStatus::Runable;
+        inner.scheduler.enqueue(current);
+    }
+
+    // 当前任务进入睡眠，需由 wakeup() 重新加入就绪队列
+    fn mark_current_sleeping(&self) {
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current].status = TaskStatus::Sleeping;
+        inner.scheduler.on_block(current);
+    }
+
+    fn wakeup(&self, id: usize) {
+        let mut inner = self.inner.lock();
+        if inner.wakeup(id) {
+            drop(inner);
+            kick_idle_hart();
+        }
     }
 
-    fn mark_current_zombie(&self) {
-        let mut inner = self.inner.get_mut();
-        let current = inner.current;
+    // 当前任务结束，唤醒等待它的 join()，并释放它对地址空间的引用，使 exec() 不再把它计入
+    // 进程的主线程结束时还要唤醒在 wait() 中的父进程，其子进程交给 init 回收
+    // 主线程已结束的进程中的其他线程结束时同样唤醒父进程，wait() 要等所有线程结束才回收进程
+    fn mark_current_zombie(&self, exit_code: i32) {
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        if current == INIT_PID {
+            panic!("init exiting with {}", exit_code);
+        }
         inner.tasks[current].status = TaskStatus::Zombie;
+        inner.tasks[current].exit_code = exit_code;
+        inner.scheduler.on_exit(current);
+        // 内核使用自己的页表，此后不会再访问该线程的用户地址空间
+        let space = inner.tasks[current].release_space();
+        if let Some(joiner) = inner.tasks[current].joiner.take() {
+            inner.wakeup(joiner);
+        }
+        let pid = inner.tasks[current].pid;
+        if pid != current && inner.tasks[pid].status == TaskStatus::Zombie {
+            if let Some(parent) = inner.tasks[pid].parent {
+                inner.wakeup_waiting(parent);
+            }
+        }
+        if pid == current {
+            if let Some(parent) = inner.tasks[current].parent {
+                inner.wakeup_waiting(parent);
+            }
+            let mut zombie_orphan = false;
+            for task in inner.tasks.iter_mut() {
+                if task.parent == Some(current) {
+                    task.parent = Some(INIT_PID);
+                    zombie_orphan |= task.status == TaskStatus::Zombie;
+                }
+            }
+            if zombie_orphan {
+                inner.wakeup_waiting(INIT_PID);
+            }
+        }
+        // 关闭管道会唤醒其他任务，释放最后一个引用时会回收地址空间，需在释放任务锁之后进行
+        let files = inner.tasks[current].files.take();
+        drop(inner);
+        drop(files);
+        drop(space);
+        // 留在队列中的唤醒事件会唤醒之后使用这个任务槽的任务
+        cancel_timer(TimerEvent::Wakeup(current));
     }
 
-    fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.get_mut();
-        let current = inner.current;
-        ((current + 1)..(current + 1 + self.task_num))
-            .map(|x| x % self.task_num)
-            .find(|&id| inner.tasks[id].status == TaskStatus::Runable)
+    // 空闲的任务槽，0 号不使用，使 fork 与 clone 在子任务中返回的 0 不会与任务号混淆
+    fn alloc_id(inner: &TaskManagerInner) -> Option<usize> {
+        (1..MAX_APP_NUM).find(|&id| inner.tasks[id].status == TaskStatus::Unused)
     }
 
-    fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.get_mut();
-            let current = inner.current;
-            inner.tasks[next].status = TaskStatus::Running;
-            inner.current = next;
-            let mut old_cx = &mut inner.tasks[current].context as *mut TaskContext;
-            let new_cx = &inner.tasks[next].context as *const TaskContext;
+    // 在当前任务的地址空间中创建线程，返回线程号
+    fn clone_current(&self, stack: usize) -> Option<usize> {
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        let id = Self::alloc_id(&inner)?;
+        let space = inner.tasks[current].space.clone()?;
+        let files = inner.tasks[current].files.clone();
+        let pid = inner.tasks[current].pid;
+        let parent = *inner.tasks[current].trap_context();
+        if !inner.tasks[id].init_thread(space, files, pid, &parent, stack, id) {
+            return None;
+        }
+        inner.tasks[id].cwd = inner.tasks[current].cwd;
+        inner.scheduler.on_create(id);
+        inner.scheduler.enqueue(id);
+        drop(inner);
+        kick_idle_hart();
+        Some(id)
+    }
+
+    // 复制当前进程，子进程只包含调用 fork 的线程，返回子进程号
+    fn fork_current(&self) -> Option<usize> {
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        let id = Self::alloc_id(&inner)?;
+        let task = &inner.tasks[current];
+        let (space, trapframe) = task.user_space().lock().fork()?;
+        let files = task.files.as_ref().map(|files| files.lock().clone());
+        let pid = task.pid;
+        let parent = *task.trap_context();
+        inner.tasks[id].init_fork(space, trapframe, files, &parent, pid, id);
+        inner.tasks[id].cwd = inner.tasks[current].cwd;
+        inner.scheduler.on_create(id);
+        inner.scheduler.enqueue(id);
+        drop(inner);
+        kick_idle_hart();
+        Some(id)
+    }
+
+    // 用 elf_data 替换当前进程的程序，返回 argc
+    // 参数过长、内存不足或进程中还有其他线程时返回 None
+    fn exec_current(&self, elf_data: &[u8], argv: &[&str], envp: &[&str]) -> Option<usize> {
+        let mut space = UserSpace::empty();
+        let (args, trapframe) = space.init_from_elf(elf_data, argv, envp)?;
+
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        if Arc::strong_count(inner.tasks[current].user_space()) != 1 {
+            return None;
+        }
+        let old = inner.tasks[current].exec(space, trapframe, &args, current);
+        drop(inner);
+        drop(old);
+        Some(args.argc)
+    }
 
+    // 等待子进程 pid（为 None 时任意子进程）结束并回收它，返回其进程号与退出码
+    // 没有这样的子进程或当前任务被 kill 时返回 None
+    // nohang 时若子进程都未结束则返回 Some((0, 0))
+    // 主线程结束后进程中还在运行的线程被 kill，全部结束后才回收，否则进程号可能被重新分配给新任务
+    fn wait(&self, pid: Option<usize>, nohang: bool) -> Option<(usize, i32)> {
+        loop {
+            let mut inner = self.inner.lock();
+            let current = current_id();
+            if inner.tasks[current].killed {
+                return None;
+            }
+            let me = inner.tasks[current].pid;
+            let mut found = false;
+            let mut woken = false;
+            for id in 0..MAX_APP_NUM {
+                let task = &inner.tasks[id];
+                if task.parent != Some(me) || task.pid != id || pid.map_or(false, |pid| pid != id) {
+                    continue;
+                }
+                found = true;
+                if task.status != TaskStatus::Zombie {
+                    continue;
+                }
+                let (alive, kicked) = inner.kill_threads(id);
+                woken |= kicked;
+                if !alive {
+                    let exit_code = inner.tasks[id].exit_code;
+                    for task in inner.tasks.iter_mut().filter(|task| task.pid == id) {
+                        task.clear();
+                    }
+                    return Some((id, exit_code));
+                }
+            }
+            if !found {
+                return None;
+            }
+            if nohang {
+                drop(inner);
+                if woken {
+                    kick_idle_hart();
+                }
+                return Some((0, 0));
+            }
+            inner.tasks[current].waiting_child = true;
+            inner.tasks[current].status = TaskStatus::Sleeping;
+            inner.scheduler.on_block(current);
             drop(inner);
-            println!("[kernel] task{} running", next);
-            unsafe {
-                switch(old_cx, new_cx);
+            if woken {
+                kick_idle_hart();
             }
-        } else {
-            println!("[kernel] All tasks completed!");
-            QEMU_EXIT_HANDLE.exit_success();
+            self.run_next_task();
+        }
+    }
+
+    // 标记进程 pid 的所有线程为被 kill，唤醒其中睡眠的线程，使其尽快结束
+    fn kill(&self, pid: usize) -> bool {
+        let mut inner = self.inner.lock();
+        if pid >= MAX_APP_NUM || inner.tasks[pid].pid != pid || !inner.tasks[pid].is_alive() {
+            return false;
+        }
+        let (_, woken) = inner.kill_threads(pid);
+        drop(inner);
+        if woken {
+            kick_idle_hart();
+        }
+        true
+    }
+
+    fn spawn_kernel_thread(&self, entry: fn()) -> Option<usize> {
+        let mut inner = self.inner.lock();
+        let id = Self::alloc_id(&inner)?;
+        inner.tasks[id].init_kernel_thread(entry, id);
+        inner.scheduler.on_create(id);
+        inner.scheduler.enqueue(id);
+        drop(inner);
+        kick_idle_hart();
+        Some(id)
+    }
+
+    // 等待同一进程中的线程 id 结束并回收它，返回其退出码
+    // 结束的线程已不再持有地址空间，因此按进程号判断是否属于同一进程
+    fn join(&self, id: usize) -> Option<i32> {
+        loop {
+            let mut inner = self.inner.lock();
+            let current = current_id();
+            let target = inner.tasks.get(id)?;
+            let same_process = target.status != TaskStatus::Unused
+                && !target.is_kernel_thread()
+                && target.pid == inner.tasks[current].pid;
+            if id == current || !same_process {
+                return None;
+            }
+            if inner.tasks[id].status == TaskStatus::Zombie {
+                let exit_code = inner.tasks[id].exit_code;
+                inner.tasks[id].clear();
+                return Some(exit_code);
+            }
+            if inner.tasks[id].joiner.is_some() {
+                return None;
+            }
+            inner.tasks[id].joiner = Some(current);
+            inner.tasks[current].status = TaskStatus::Sleeping;
+            inner.scheduler.on_block(current);
+            drop(inner);
+            self.run_next_task();
+        }
+    }
+
+    // 时钟中断到来时由调度器决定是否抢占当前任务
+    fn tick(&self) -> bool {
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        inner.scheduler.on_tick(current)
+    }
+
+    fn set_current_priority(&self, priority: usize) {
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        inner.scheduler.set_priority(current, priority);
+    }
+
+    // 切换回空闲循环，由其选择下一个任务
+    fn run_next_task(&self) {
+        let mut inner = self.inner.lock();
+        let current = current_id();
+        let current_cx = &mut inner.tasks[current].context as *mut TaskContext;
+        let idle_cx = &current_processor().idle_context as *const TaskContext;
+        drop(inner);
+
+        unsafe {
+            switch(current_cx, idle_cx);
         }
     }
 
     fn current_user_satp(&self) -> usize {
-        let inner = self.inner.get_mut();
-        let current = inner.current;
+        let inner = self.inner.lock();
+        let current = current_id();
         inner.tasks[current].user_satp()
     }
 
     fn current_user_epc(&self) -> usize {
-        let inner = self.inner.get_mut();
-        let current = inner.current;
+        let inner = self.inner.lock();
+        let current = current_id();
         inner.tasks[current].user_epc()
     }
 
+    fn current_trap_frame_va(&self) -> usize {
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current].trap_frame_va()
+    }
+
     fn current_pagetable(&self) {
-        let inner = self.inner.get_mut();
-        let current = inner.current;
-        inner.tasks[current].space.print_user_pagetable();
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current]
+            .user_space()
+            .lock()
+            .print_user_pagetable();
     }
 
     fn current_user_trapcontex(&self) -> &'static mut TrapContext {
-        let inner = self.inner.get_mut();
-        let current = inner.current;
+        let inner = self.inner.lock();
+        let current = current_id();
         inner.tasks[current].trap_context()
     }
+
+    fn current_user_translate(&self, addr: usize) -> Option<usize> {
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current]
+            .user_space()
+            .lock()
+            .translate(Addr::new(addr))
+            .map(|pa| pa.bits)
+    }
+
+    fn current_pid(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.tasks[current_id()].pid
+    }
+
+    fn current_killed(&self) -> bool {
+        let inner = self.inner.lock();
+        inner.tasks[current_id()].killed
+    }
+
+    fn current_cwd(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.tasks[current_id()].cwd
+    }
+
+    // 工作目录由进程的所有线程共享
+    fn set_current_cwd(&self, cwd: usize) {
+        let mut inner = self.inner.lock();
+        let pid = inner.tasks[current_id()].pid;
+        for task in inner.tasks.iter_mut() {
+            if task.status != TaskStatus::Unused && task.pid == pid {
+                task.cwd = cwd;
+            }
+        }
+    }
+
+    fn cwd_in_use(&self, ino: usize) -> bool {
+        let inner = self.inner.lock();
+        inner.tasks.iter().any(|task| {
+            task.status != TaskStatus::Unused
+                && task.status != TaskStatus::Zombie
+                && task.cwd == ino
+        })
+    }
+
+    fn current_files(&self) -> Option<Arc<SpinLock<FdTable>>> {
+        let inner = self.inner.lock();
+        inner.tasks[current_id()].files.clone()
+    }
+
+    fn current_copy_in(&self, addr: usize, buf: &mut [u8]) -> bool {
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current]
+            .user_space()
+            .lock()
+            .copy_in(Addr::new(addr), buf)
+    }
+
+    fn current_copy_out(&self, addr: usize, data: &[u8]) -> bool {
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current]
+            .user_space()
+            .lock()
+            .copy_out(Addr::new(addr), data)
+    }
+
+    fn current_copy_in_str(&self, addr: usize, max: usize) -> Option<String> {
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current]
+            .user_space()
+            .lock()
+            .copy_in_str(Addr::new(addr), max)
+    }
+
+    fn current_sbrk(&self, increment: isize) -> Option<usize> {
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current].user_space().lock().sbrk(increment)
+    }
+
+    fn current_stack_fault(&self, addr: usize) -> StackFault {
+        let inner = self.inner.lock();
+        let current = current_id();
+        inner.tasks[current]
+            .user_space()
+            .lock()
+            .handle_stack_fault(Addr::new(addr))
+    }
+}
+
+impl TaskManagerInner {
+    // 唤醒睡眠中的任务，返回是否有任务被加入就绪队列
+    fn wakeup(&mut self, id: usize) -> bool {
+        if self.tasks[id].status == TaskStatus::Sleeping {
+            self.tasks[id].status = TaskStatus::Runable;
+            self.scheduler.enqueue(id);
+            true
+        } else {
+            false
+        }
+    }
+
+    // 标记进程 pid 中尚未结束的线程为被 kill，唤醒其中睡眠的线程，使其尽快结束
+    // 返回是否还有未结束的线程，以及是否有线程被加入就绪队列
+    fn kill_threads(&mut self, pid: usize) -> (bool, bool) {
+        let (mut alive, mut woken) = (false, false);
+        for id in 0..MAX_APP_NUM {
+            if self.tasks[id].pid == pid && self.tasks[id].is_alive() {
+                alive = true;
+                self.tasks[id].killed = true;
+                woken |= self.wakeup(id);
+            }
+        }
+        (alive, woken)
+    }
+
+    // 唤醒进程 pid 中在 wait() 里等待的线程
+    fn wakeup_waiting(&mut self, pid: usize) {
+        for id in 0..MAX_APP_NUM {
+            if self.tasks[id].pid == pid && self.tasks[id].waiting_child {
+                self.tasks[id].waiting_child = false;
+                self.wakeup(id);
+            }
+        }
+    }
 }
 
 pub fn load_tasks() {
     TASK_MANAGER.load_tasks();
 }
 
-pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
+pub fn run_tasks() -> ! {
+    TASK_MANAGER.run_tasks()
 }
 
 pub fn run_next_task_kill() {
-    TASK_MANAGER.mark_current_zombie();
+    TASK_MANAGER.mark_current_zombie(-1);
     TASK_MANAGER.run_next_task();
 }
 
+pub fn run_next_task_exit(exit_code: i32) {
+    TASK_MANAGER.mark_current_zombie(exit_code);
+    TASK_MANAGER.run_next_task();
+}
+
+pub fn clone_current_task(stack: usize) -> Option<usize> {
+    TASK_MANAGER.clone_current(stack)
+}
+
+pub fn join_task(id: usize) -> Option<i32> {
+    TASK_MANAGER.join(id)
+}
+
+// kill 进程 pid，没有这个进程时返回 false
+pub fn kill_task(pid: usize) -> bool {
+    TASK_MANAGER.kill(pid)
+}
+
+pub fn fork_current_task() -> Option<usize> {
+    TASK_MANAGER.fork_current()
+}
+
+pub fn exec_current_task(elf_data: &[u8], argv: &[&str], envp: &[&str]) -> Option<usize> {
+    TASK_MANAGER.exec_current(elf_data, argv, envp)
+}
+
+pub fn wait_child(pid: Option<usize>, nohang: bool) -> Option<(usize, i32)> {
+    TASK_MANAGER.wait(pid, nohang)
+}
+
+// 内嵌的名为 name 的应用的 ELF 镜像
+pub fn find_app(name: &str) -> Option<&'static [u8]> {
+    (0..get_app_num())
+        .find(|&id| get_app_name(id) == name)
+        .map(get_app_data)
+}
+
+// 启动运行 entry 的内核线程，entry 返回时线程结束
+// 内核线程运行时持有大内核锁，且内核态不响应时钟中断，因此不会被抢占
+// 长时间运行的 entry 须调用 run_next_task_suspend() 或睡眠以让出 CPU，否则其他 hart 都会等待大内核锁
+pub fn spawn_kernel_thread(entry: fn()) -> Option<usize> {
+    TASK_MANAGER.spawn_kernel_thread(entry)
+}
+
+// 内核线程第一次运行时由 kernel_thread_start 调用
+#[no_mangle]
+extern "C" fn kernel_thread_main(entry: usize) -> ! {
+    let entry: fn() = unsafe { core::mem::transmute(entry) };
+    entry();
+    run_next_task_exit(0);
+    unreachable!("zombie kernel thread scheduled");
+}
+
 pub fn run_next_task_suspend() {
     TASK_MANAGER.mark_current_runnable();
     TASK_MANAGER.run_next_task();
 }
 
+pub fn run_next_task_block() {
+    TASK_MANAGER.mark_current_sleeping();
+    TASK_MANAGER.run_next_task();
+}
+
+// 释放 guard 并使当前任务睡眠，标记睡眠先于释放锁，以免错过 guard 保护的条件上的唤醒
+pub fn sleep_on<T>(guard: SpinLockGuard<'_, T>) {
+    TASK_MANAGER.mark_current_sleeping();
+    drop(guard);
+    TASK_MANAGER.run_next_task();
+}
+
+pub fn wakeup_task(id: usize) {
+    TASK_MANAGER.wakeup(id);
+}
+
+pub fn scheduler_tick() -> bool {
+    TASK_MANAGER.tick()
+}
+
+pub fn set_current_priority(priority: usize) {
+    TASK_MANAGER.set_current_priority(priority);
+}
+
+// 当前 hart 上正在运行的任务
+fn current_id() -> usize {
+    current_processor()
+        .current
+        .expect("no task running on this hart")
+}
+
+pub fn current_task_id() -> usize {
+    current_id()
+}
+
+// 当前 hart 上正在运行的任务，启动阶段与空闲循环中为 None
+pub fn running_task_id() -> Option<usize> {
+    current_processor().current
+}
+
+pub fn current_trap_frame_va() -> usize {
+    TASK_MANAGER.current_trap_frame_va()
+}
+
 pub fn current_user_satp() -> usize {
     TASK_MANAGER.current_user_satp()
 }
@@ -172,3 +681,56 @@ pub fn current_pagetable() {
 pub fn current_user_trapcontext() -> &'static mut TrapContext {
     TASK_MANAGER.current_user_trapcontex()
 }
+
+pub fn current_user_translate(addr: usize) -> Option<usize> {
+    TASK_MANAGER.current_user_translate(addr)
+}
+
+pub fn current_pid() -> usize {
+    TASK_MANAGER.current_pid()
+}
+
+pub fn current_killed() -> bool {
+    TASK_MANAGER.current_killed()
+}
+
+// 当前进程工作目录的 inode 号
+pub fn current_cwd() -> usize {
+    TASK_MANAGER.current_cwd()
+}
+
+pub fn set_current_cwd(cwd: usize) {
+    TASK_MANAGER.set_current_cwd(cwd)
+}
+
+// 目录 ino 是否是某个未结束任务的工作目录
+pub fn cwd_in_use(ino: usize) -> bool {
+    TASK_MANAGER.cwd_in_use(ino)
+}
+
+pub fn current_files() -> Option<Arc<SpinLock<FdTable>>> {
+    TASK_MANAGER.current_files()
+}
+
+// 从当前用户地址空间复制 buf.len() 个字节，其中有未映射的地址时返回 false
+pub fn current_copy_in(addr: usize, buf: &mut [u8]) -> bool {
+    TASK_MANAGER.current_copy_in(addr, buf)
+}
+
+// 把 data 复制到当前用户地址空间，目标中有未映射的地址时返回 false
+pub fn current_copy_out(addr: usize, data: &[u8]) -> bool {
+    TASK_MANAGER.current_copy_out(addr, data)
+}
+
+pub fn current_copy_in_str(addr: usize, max: usize) -> Option<String> {
+    TASK_MANAGER.current_copy_in_str(addr, max)
+}
+
+// 把当前进程的 program break 移动 increment 字节，返回原来的 break
+pub fn current_sbrk(increment: isize) -> Option<usize> {
+    TASK_MANAGER.current_sbrk(increment)
+}
+
+pub fn current_stack_fault(addr: usize) -> StackFault {
+    TASK_MANAGER.current_stack_fault(addr)
+}
@@ -1,3 +1,8 @@
 pub const MAX_APP_NUM: usize = 16;
 pub const MAX_APP_SIZE: usize = 0x20000;
 pub const APP_BASE_ADDRESS: usize = 0x0;
+pub const DEFAULT_PRIORITY: usize = 16;
+// 不超过步长调度的 BIG_STRIDE，使每次被选中时 stride 至少增加 1
+pub const MAX_PRIORITY: usize = 1 << 10;
+pub const INIT_APP: &str = "init";
+pub const INIT_PID: usize = 1;
@@ -0,0 +1,20 @@
+use crate::cpu::{cpu_id, MAX_HARTS};
+
+use super::TaskContext;
+
+// 每个 hart 上的调度状态，只由其所属的 hart 访问
+pub struct Processor {
+    pub current: Option<usize>,    // 正在运行的任务
+    pub idle_context: TaskContext, // 空闲循环的上下文，运行在该 hart 的启动栈上
+}
+
+const PROCESSOR_INIT: Processor = Processor {
+    current: None,
+    idle_context: TaskContext::new(),
+};
+
+static mut PROCESSORS: [Processor; MAX_HARTS] = [PROCESSOR_INIT; MAX_HARTS];
+
+pub fn current_processor() -> &'static mut Processor {
+    unsafe { &mut PROCESSORS[cpu_id()] }
+}
@@ -0,0 +1,94 @@
+use alloc::collections::VecDeque;
+
+use super::Scheduler;
+use crate::{
+    task::param::MAX_APP_NUM,
+    timer::{get_time, ms_to_cycles},
+};
+
+const LEVELS: usize = 3;
+// 各级的时间片（时钟中断数），越低的级别时间片越长
+const TIMESLICE: [usize; LEVELS] = [1, 2, 4];
+// 每隔 BOOST_INTERVAL_MS 毫秒所有任务回到最高级，避免底层的计算密集型任务饥饿
+// 按时间而不是时钟中断数计算，否则 hart 越多提升越频繁
+const BOOST_INTERVAL_MS: usize = 640;
+
+// 多级反馈队列：新任务从 0 级开始，用完时间片的任务降一级，时间片用完前阻塞的任务升一级
+// 因此交互式任务停留在高级别，优先被选中
+pub struct MlfqScheduler {
+    queues: [VecDeque<usize>; LEVELS],
+    level: [usize; MAX_APP_NUM],
+    used: [usize; MAX_APP_NUM], // 在当前级别已用的时钟中断数
+    last_boost: usize,          // 上次提升的时间
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            level: [0; MAX_APP_NUM],
+            used: [0; MAX_APP_NUM],
+            last_boost: 0,
+        }
+    }
+
+    fn boost(&mut self) {
+        for level in 1..LEVELS {
+            while let Some(id) = self.queues[level].pop_front() {
+                self.queues[0].push_back(id);
+            }
+        }
+        self.level = [0; MAX_APP_NUM];
+        self.used = [0; MAX_APP_NUM];
+        self.last_boost = get_time();
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn enqueue(&mut self, id: usize) {
+        self.queues[self.level[id]].push_back(id);
+    }
+
+    fn dequeue(&mut self, id: usize) {
+        for queue in self.queues.iter_mut() {
+            queue.retain(|&x| x != id);
+        }
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+
+    fn on_tick(&mut self, id: usize) -> bool {
+        if get_time().saturating_sub(self.last_boost) >= ms_to_cycles(BOOST_INTERVAL_MS) {
+            self.boost();
+            return true;
+        }
+        self.used[id] += 1;
+        if self.used[id] < TIMESLICE[self.level[id]] {
+            return false;
+        }
+        if self.level[id] + 1 < LEVELS {
+            self.level[id] += 1;
+        }
+        self.used[id] = 0;
+        true
+    }
+
+    // 新任务从 0 级开始，不继承该槽上一个任务的级别与已用时间片
+    fn on_create(&mut self, id: usize) {
+        self.level[id] = 0;
+        self.used[id] = 0;
+    }
+
+    fn on_block(&mut self, id: usize) {
+        if self.level[id] > 0 {
+            self.level[id] -= 1;
+        }
+        self.used[id] = 0;
+    }
+
+    fn name(&self) -> &'static str {
+        "mlfq"
+    }
+}
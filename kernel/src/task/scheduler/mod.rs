@@ -0,0 +1,65 @@
+// 可替换的调度策略
+// 任务管理器只保存任务控制块，下一个运行哪个就绪任务由 Scheduler 决定
+// 调度器以任务表中的下标标识任务，各自保存所需的每任务状态
+
+use alloc::boxed::Box;
+
+mod mlfq;
+mod priority;
+mod round_robin;
+mod stride;
+
+pub use mlfq::MlfqScheduler;
+pub use priority::PriorityScheduler;
+pub use round_robin::RoundRobinScheduler;
+pub use stride::StrideScheduler;
+
+pub trait Scheduler {
+    // 将可运行的任务加入就绪集合
+    fn enqueue(&mut self, id: usize);
+
+    // 将任务移出就绪集合，例如就绪的任务被 kill 时
+    fn dequeue(&mut self, id: usize);
+
+    // 选出下一个运行的任务并将其移出就绪集合
+    fn pick_next(&mut self) -> Option<usize>;
+
+    // 每个时钟中断时以正在运行的任务调用，返回是否应抢占它
+    fn on_tick(&mut self, id: usize) -> bool;
+
+    // 正在运行的任务因等待而让出 CPU 时调用，被唤醒后才会再次入队
+    fn on_block(&mut self, _id: usize) {}
+
+    // 调整任务的优先级，没有优先级的策略忽略它
+    fn set_priority(&mut self, _id: usize, _priority: usize) {}
+
+    // 任务创建后、第一次入队前调用，任务槽会被重复使用，须重置该槽的每任务状态
+    fn on_create(&mut self, _id: usize) {}
+
+    // 任务结束时调用，此后该槽不会再入队，直到下一次 on_create()
+    fn on_exit(&mut self, _id: usize) {}
+
+    fn name(&self) -> &'static str;
+}
+
+// 编译时通过 sched-* feature 选择调度器
+pub const DEFAULT_SCHEDULER: &str = if cfg!(feature = "sched-mlfq") {
+    "mlfq"
+} else if cfg!(feature = "sched-stride") {
+    "stride"
+} else if cfg!(feature = "sched-priority") {
+    "priority"
+} else {
+    "rr"
+};
+
+// 按名字创建调度器，名字未知时返回 None
+pub fn create_scheduler(name: &str) -> Option<Box<dyn Scheduler + Send>> {
+    match name {
+        "rr" => Some(Box::new(RoundRobinScheduler::new())),
+        "priority" => Some(Box::new(PriorityScheduler::new())),
+        "stride" => Some(Box::new(StrideScheduler::new())),
+        "mlfq" => Some(Box::new(MlfqScheduler::new())),
+        _ => None,
+    }
+}
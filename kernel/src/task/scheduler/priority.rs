@@ -0,0 +1,60 @@
+use alloc::collections::VecDeque;
+
+use super::Scheduler;
+use crate::task::param::{DEFAULT_PRIORITY, MAX_APP_NUM};
+
+// 总是运行优先级最高的就绪任务，优先级相同的任务轮流运行
+pub struct PriorityScheduler {
+    ready: VecDeque<usize>,
+    priority: [usize; MAX_APP_NUM],
+}
+
+impl PriorityScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: VecDeque::new(),
+            priority: [DEFAULT_PRIORITY; MAX_APP_NUM],
+        }
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+    fn enqueue(&mut self, id: usize) {
+        self.ready.push_back(id);
+    }
+
+    fn dequeue(&mut self, id: usize) {
+        self.ready.retain(|&x| x != id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        // max_by_key 返回最后一个最大值，反向扫描以保持先进先出的顺序
+        let (pos, _) = self
+            .ready
+            .iter()
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, &id)| self.priority[id])?;
+        self.ready.remove(pos)
+    }
+
+    fn on_tick(&mut self, _id: usize) -> bool {
+        true
+    }
+
+    fn set_priority(&mut self, id: usize, priority: usize) {
+        self.priority[id] = priority;
+    }
+
+    fn on_create(&mut self, id: usize) {
+        self.priority[id] = DEFAULT_PRIORITY;
+    }
+
+    fn on_exit(&mut self, id: usize) {
+        self.priority[id] = DEFAULT_PRIORITY;
+    }
+
+    fn name(&self) -> &'static str {
+        "priority"
+    }
+}
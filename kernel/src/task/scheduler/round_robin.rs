@@ -0,0 +1,38 @@
+use alloc::collections::VecDeque;
+
+use super::Scheduler;
+
+// 按先进先出的顺序让每个任务运行一个时钟中断
+pub struct RoundRobinScheduler {
+    ready: VecDeque<usize>,
+}
+
+impl RoundRobinScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn enqueue(&mut self, id: usize) {
+        self.ready.push_back(id);
+    }
+
+    fn dequeue(&mut self, id: usize) {
+        self.ready.retain(|&x| x != id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        self.ready.pop_front()
+    }
+
+    fn on_tick(&mut self, _id: usize) -> bool {
+        true
+    }
+
+    fn name(&self) -> &'static str {
+        "rr"
+    }
+}
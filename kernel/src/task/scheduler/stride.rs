@@ -0,0 +1,71 @@
+use alloc::vec::Vec;
+
+use super::Scheduler;
+use crate::task::param::{DEFAULT_PRIORITY, MAX_APP_NUM, MAX_PRIORITY};
+
+// 任务每次被选中时 stride 增加 BIG_STRIDE / priority，因此 CPU 时间按优先级的比例分配
+const BIG_STRIDE: u64 = 1 << 20;
+
+// 步长调度：总是运行 stride 最小的任务
+pub struct StrideScheduler {
+    ready: Vec<usize>,
+    stride: [u64; MAX_APP_NUM],
+    priority: [usize; MAX_APP_NUM],
+    pass: u64, // 最近一次被选中的任务当时的 stride，不大于此后任何就绪任务的 stride
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: Vec::new(),
+            stride: [0; MAX_APP_NUM],
+            priority: [DEFAULT_PRIORITY; MAX_APP_NUM],
+            pass: 0,
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn enqueue(&mut self, id: usize) {
+        self.ready.push(id);
+    }
+
+    fn dequeue(&mut self, id: usize) {
+        self.ready.retain(|&x| x != id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        let (pos, &id) = self
+            .ready
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &id)| self.stride[id])?;
+        self.ready.remove(pos);
+        self.pass = self.stride[id];
+        self.stride[id] += BIG_STRIDE / self.priority[id] as u64;
+        Some(id)
+    }
+
+    fn on_tick(&mut self, _id: usize) -> bool {
+        true
+    }
+
+    fn set_priority(&mut self, id: usize, priority: usize) {
+        self.priority[id] = priority.clamp(1, MAX_PRIORITY);
+    }
+
+    // 新任务从当前最小的 stride 开始，既不会长期独占 CPU，也不会饥饿
+    fn on_create(&mut self, id: usize) {
+        let min = self.ready.iter().map(|&id| self.stride[id]).min();
+        self.stride[id] = min.map_or(self.pass, |min| min.min(self.pass));
+        self.priority[id] = DEFAULT_PRIORITY;
+    }
+
+    fn on_exit(&mut self, id: usize) {
+        self.priority[id] = DEFAULT_PRIORITY;
+    }
+
+    fn name(&self) -> &'static str {
+        "stride"
+    }
+}
@@ -1,10 +1,14 @@
+use alloc::sync::Arc;
+
 use crate::{
+    fs::{ramfs::ROOT_INO, FdTable},
     mem::{
         address::{Addr, Page},
         kernel_sp_i,
         kernel_space::KERNEL_SPACE,
-        user_space::UserSpace,
+        user_space::{trap_frame_va, UserArgs, UserSpace},
     },
+    sync::SpinLock,
     trap::{user_trap_handler, TrapContext},
 };
 
@@ -13,8 +17,18 @@ use super::{param::APP_BASE_ADDRESS, TaskContext};
 pub struct TaskControlBlock {
     pub status: TaskStatus,
     pub context: TaskContext,
-    pub space: UserSpace,
-    pub trapframe: Addr,
+    pub space: Option<Arc<SpinLock<UserSpace>>>, // 同一进程的线程共享，内核线程与僵尸为 None
+    pub trapframe: Addr,                         // trapframe 的物理地址
+    pub trapframe_slot: usize,                   // trapframe 在地址空间中的编号
+    pub files: Option<Arc<SpinLock<FdTable>>>,   // 同一进程的线程共享
+    pub pid: usize,                              // 所属进程的主线程
+    pub parent: Option<usize>,                   // 父进程，只对主线程有意义
+    pub cwd: usize,                              // 工作目录的 inode 号
+    pub killed: bool,                            // 被 kill，返回用户态前结束
+    pub exit_code: i32,
+    pub joiner: Option<usize>, // 等待该线程结束的任务
+    pub waiting_child: bool,   // 正在 wait() 中等待子进程结束
+    pub kernel: bool,          // 内核线程，没有用户地址空间
 }
 
 impl TaskControlBlock {
@@ -22,38 +36,183 @@ impl TaskControlBlock {
         Self {
             status: TaskStatus::Unused,
             context: TaskContext::new(),
-            space: UserSpace::empty(),
+            space: None,
             trapframe: Addr::empty(),
+            trapframe_slot: 0,
+            files: None,
+            pid: 0,
+            parent: None,
+            cwd: ROOT_INO,
+            killed: false,
+            exit_code: 0,
+            joiner: None,
+            waiting_child: false,
+            kernel: false,
         }
     }
 
-    pub fn clear(&mut self) {}
+    // 释放 trapframe 并交出对地址空间的引用，由调用者在释放任务锁之后丢弃
+    pub fn release_space(&mut self) -> Option<Arc<SpinLock<UserSpace>>> {
+        let space = self.space.take()?;
+        space.lock().dealloc_trap_frame(self.trapframe_slot);
+        Some(space)
+    }
+
+    // 回收已结束的线程，其地址空间已在结束时释放
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
 
-    pub fn init_from_elf(&mut self, elf_data: &[u8], id: usize) {
-        let (sp, trapframe) = self.space.init_from_elf(elf_data); // 为用户程序分配内存, 并开启页面映射
+    // 参数过长或内存不足时返回 false，任务保持未使用
+    pub fn init_from_elf(
+        &mut self,
+        elf_data: &[u8],
+        argv: &[&str],
+        envp: &[&str],
+        id: usize,
+    ) -> bool {
+        let mut space = UserSpace::empty();
+        // 为用户程序分配内存, 开启页面映射并在用户栈上放置参数
+        let (args, trapframe) = match space.init_from_elf(elf_data, argv, envp) {
+            Some(result) => result,
+            None => return false,
+        };
+        self.space = Some(Arc::new(SpinLock::new("user_space", space)));
         self.trapframe = trapframe; // 设置 trapframe 指针
+        self.trapframe_slot = 0;
+        // 描述符表为空，由 init 打开控制台
+        self.files = Some(Arc::new(SpinLock::new("fd_table", FdTable::new())));
+        self.pid = id;
+        self.parent = None;
+        self.init_user_context(&args, id);
 
+        println!("[kernel] init task{} success", id);
+        true
+    }
+
+    // 初始化用户程序的 trapcontext 与 taskcontext，使其从程序入口开始执行
+    fn init_user_context(&mut self, args: &UserArgs, id: usize) {
         // 初始化用户程序的 trapcontext, 用以第一次被执行
-        let tf_ptr = trapframe.get_value_mut::<TrapContext>();
+        let tf_ptr = self.trapframe.get_value_mut::<TrapContext>();
         *tf_ptr = TrapContext::app_init_context(
             APP_BASE_ADDRESS,
-            sp.bits,
-            KERNEL_SPACE.get_mut().make_satp(),
+            args.sp,
+            KERNEL_SPACE.lock().make_satp(),
             kernel_sp_i(id),
             user_trap_handler as usize,
         );
+        tf_ptr.set_args(args.argc, args.argv, args.envp);
 
         // 初始化用户程序的 taskcontext, 用以内核线程之间的切换
         self.context.init(kernel_sp_i(id));
 
         // 设置程序状态为 runable
         self.status = TaskStatus::Runable;
+    }
+
+    // fork 出的子进程：地址空间与描述符表均为父进程的副本，从父进程的系统调用返回 0
+    pub fn init_fork(
+        &mut self,
+        space: UserSpace,
+        trapframe: Addr,
+        files: Option<FdTable>,
+        parent: &TrapContext,
+        parent_pid: usize,
+        id: usize,
+    ) {
+        self.space = Some(Arc::new(SpinLock::new("user_space", space)));
+        self.trapframe = trapframe;
+        self.trapframe_slot = 0;
+        self.files = files.map(|files| Arc::new(SpinLock::new("fd_table", files)));
+        self.pid = id;
+        self.parent = Some(parent_pid);
+
+        let cx = trapframe.get_value_mut::<TrapContext>();
+        *cx = *parent;
+        cx.x[10] = 0;
+        cx.set_kernel_sp(kernel_sp_i(id));
+
+        self.context.init(kernel_sp_i(id));
+        self.status = TaskStatus::Runable;
+    }
 
-        println!("[kernel] init task{} success", id);
+    // exec 换用新的地址空间，从新程序的入口开始执行
+    // 返回旧的地址空间，调用者应在释放任务锁后再丢弃它
+    pub fn exec(
+        &mut self,
+        space: UserSpace,
+        trapframe: Addr,
+        args: &UserArgs,
+        id: usize,
+    ) -> Option<Arc<SpinLock<UserSpace>>> {
+        let old = self
+            .space
+            .replace(Arc::new(SpinLock::new("user_space", space)));
+        self.trapframe = trapframe;
+        self.trapframe_slot = 0;
+        self.init_user_context(args, id);
+        // exec 发生在系统调用中，返回用户态前仍处于运行状态
+        self.status = TaskStatus::Running;
+        old
+    }
+
+    // 在 space 中创建线程，trapcontext 复制自 parent，从用户栈 stack 开始执行
+    // 失败时（trapframe 用尽）返回 false
+    pub fn init_thread(
+        &mut self,
+        space: Arc<SpinLock<UserSpace>>,
+        files: Option<Arc<SpinLock<FdTable>>>,
+        pid: usize,
+        parent: &TrapContext,
+        stack: usize,
+        id: usize,
+    ) -> bool {
+        let (slot, trapframe) = match space.lock().alloc_trap_frame() {
+            Some(tf) => tf,
+            None => return false,
+        };
+        self.space = Some(space);
+        self.trapframe = trapframe;
+        self.trapframe_slot = slot;
+        self.files = files;
+        self.pid = pid;
+
+        let cx = trapframe.get_value_mut::<TrapContext>();
+        *cx = *parent;
+        cx.x[10] = 0; // 子线程中 clone 返回 0
+        cx.set_sp(stack);
+        cx.set_kernel_sp(kernel_sp_i(id));
+
+        self.context.init(kernel_sp_i(id));
+        self.status = TaskStatus::Runable;
+        true
+    }
+
+    // 内核线程没有用户地址空间，直接在内核栈上执行 entry
+    pub fn init_kernel_thread(&mut self, entry: fn(), id: usize) {
+        self.space = None;
+        self.kernel = true;
+        self.pid = id;
+        self.context.init_kernel(kernel_sp_i(id), entry as usize);
+        self.status = TaskStatus::Runable;
+    }
+
+    pub fn is_kernel_thread(&self) -> bool {
+        self.kernel
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.status != TaskStatus::Unused && self.status != TaskStatus::Zombie
+    }
+
+    pub fn user_space(&self) -> &Arc<SpinLock<UserSpace>> {
+        self.space
+            .as_ref()
+            .expect("kernel thread has no user space")
     }
 
     pub fn user_satp(&self) -> usize {
-        self.space.make_satp()
+        self.user_space().lock().make_satp()
     }
 
     pub fn user_epc(&self) -> usize {
@@ -61,6 +220,11 @@ impl TaskControlBlock {
         tf_ptr.epc
     }
 
+    // trapframe 在用户地址空间中的虚拟地址，由 trampoline 使用
+    pub fn trap_frame_va(&self) -> usize {
+        trap_frame_va(self.trapframe_slot)
+    }
+
     pub fn trap_context(&self) -> &'static mut TrapContext {
         self.trapframe.get_value_mut()
     }
@@ -0,0 +1,29 @@
+//! In-kernel test harness for `cargo test`
+//!
+//! Functions marked `#[test_case]` are collected by `custom_test_frameworks`
+//! and run by [`runner`] on the boot hart right after memory is set up. A
+//! failing test panics and the panic handler powers off with code 1, so the
+//! exit status of QEMU is the result of the run.
+
+use crate::board::{Board, BOARD};
+
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        print!("test {} ... ", core::any::type_name::<T>());
+        self();
+        println!("ok");
+    }
+}
+
+pub fn runner(tests: &[&dyn Testable]) {
+    println!("[kernel] running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    println!("[kernel] test result: ok. {} passed", tests.len());
+    BOARD.exit(0)
+}
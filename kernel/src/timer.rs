@@ -0,0 +1,169 @@
+// 定时器
+// 所有定时事件（睡眠的任务、调度时间片）按到期时间排在同一个队列中，SBI 定时器设为最早的到期时间
+// 没有到期事件时不产生中断，每个 hart 有自己的定时器，也负责在其上运行的任务的时间片
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use riscv::register::time;
+
+use crate::{
+    cpu::{cpu_id, MAX_HARTS},
+    fdt::{bootarg, Fdt},
+    machine::machine,
+    sbi::set_timer,
+    sync::SpinLock,
+    task::{scheduler_tick, wakeup_task},
+};
+
+// 调度时间片长度，可以用 bootargs 中的 timeslice=<ms> 覆盖
+const DEFAULT_TIMESLICE_MS: usize = 10;
+const MSEC_PER_SEC: usize = 1000;
+
+// timer::init() 之前为 0
+static CLOCK_FREQ: AtomicUsize = AtomicUsize::new(0);
+static TIMESLICE_MS: AtomicUsize = AtomicUsize::new(DEFAULT_TIMESLICE_MS);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimerEvent {
+    // 唤醒睡眠的任务
+    Wakeup(usize),
+}
+
+struct Timer {
+    deadline: usize,
+    event: TimerEvent,
+}
+
+struct TimerQueue {
+    timers: Vec<Timer>,                  // 按到期时间排序
+    quantum: [Option<usize>; MAX_HARTS], // 各 hart 上正在运行的任务的时间片结束时间
+}
+
+impl TimerQueue {
+    fn new() -> Self {
+        Self {
+            timers: Vec::new(),
+            quantum: [None; MAX_HARTS],
+        }
+    }
+
+    fn add(&mut self, deadline: usize, event: TimerEvent) {
+        // 到期时间相同的定时器按加入的顺序触发
+        let pos = self.timers.partition_point(|t| t.deadline <= deadline);
+        self.timers.insert(pos, Timer { deadline, event });
+    }
+
+    fn cancel(&mut self, event: TimerEvent) {
+        self.timers.retain(|t| t.event != event);
+    }
+
+    fn next_deadline(&self) -> Option<usize> {
+        let first = self.timers.first().map(|t| t.deadline);
+        match (first, self.quantum[cpu_id()]) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // 没有待处理事件时把定时器设到最远，同时清除挂起的时钟中断
+    // 只设置当前 hart 的定时器，其他 hart 的到期事件在其下一次中断时处理
+    fn program(&self) {
+        set_timer(self.next_deadline().unwrap_or(usize::MAX));
+    }
+}
+
+lazy_static! {
+    static ref TIMER_QUEUE: SpinLock<TimerQueue> = SpinLock::new("timer_queue", TimerQueue::new());
+}
+
+pub fn init(fdt: Option<&Fdt>) {
+    let freq = machine().timebase();
+    CLOCK_FREQ.store(freq, Ordering::Relaxed);
+
+    if let Some(ms) = bootarg(fdt, "timeslice").and_then(|v| v.parse::<usize>().ok()) {
+        if ms > 0 {
+            TIMESLICE_MS.store(ms, Ordering::Relaxed);
+        }
+    }
+    println!(
+        "[kernel] timer: {} Hz, timeslice {} ms.",
+        freq,
+        TIMESLICE_MS.load(Ordering::Relaxed)
+    );
+}
+
+pub fn clock_freq() -> usize {
+    CLOCK_FREQ.load(Ordering::Relaxed)
+}
+
+pub fn get_time() -> usize {
+    time::read()
+}
+
+// 先除后乘会在频率低于 1 kHz 时除以 0，也会丢失精度，因此分整数秒与余数两部分计算
+// 时钟频率尚未确定时返回 0
+pub fn get_time_ms() -> usize {
+    let freq = clock_freq();
+    if freq == 0 {
+        return 0;
+    }
+    let ticks = get_time();
+    ticks / freq * MSEC_PER_SEC + ticks % freq * MSEC_PER_SEC / freq
+}
+
+// 用户给出的时长可能很大，结果饱和而不溢出
+pub fn ms_to_cycles(ms: usize) -> usize {
+    let freq = clock_freq();
+    (ms / MSEC_PER_SEC)
+        .saturating_mul(freq)
+        .saturating_add(ms % MSEC_PER_SEC * freq / MSEC_PER_SEC)
+}
+
+pub fn add_timer(deadline: usize, event: TimerEvent) {
+    let mut queue = TIMER_QUEUE.lock();
+    queue.add(deadline, event);
+    queue.program();
+}
+
+pub fn cancel_timer(event: TimerEvent) {
+    let mut queue = TIMER_QUEUE.lock();
+    queue.cancel(event);
+    queue.program();
+}
+
+// 为即将运行的任务开始新的时间片
+pub fn start_quantum() {
+    let mut queue = TIMER_QUEUE.lock();
+    queue.quantum[cpu_id()] = Some(get_time() + ms_to_cycles(TIMESLICE_MS.load(Ordering::Relaxed)));
+    queue.program();
+}
+
+// 没有任务在运行，停止计算时间片
+pub fn stop_quantum() {
+    let mut queue = TIMER_QUEUE.lock();
+    queue.quantum[cpu_id()] = None;
+    queue.program();
+}
+
+// 触发所有到期的定时器，返回是否应抢占正在运行的任务
+pub fn handle_timer_interrupt() -> bool {
+    let now = get_time();
+    let mut queue = TIMER_QUEUE.lock();
+    let expired = queue.timers.partition_point(|t| t.deadline <= now);
+    let fired: Vec<Timer> = queue.timers.drain(..expired).collect();
+    let hart = cpu_id();
+    let quantum_expired = queue.quantum[hart].map_or(false, |deadline| deadline <= now);
+    if quantum_expired {
+        queue.quantum[hart] = Some(now + ms_to_cycles(TIMESLICE_MS.load(Ordering::Relaxed)));
+    }
+    queue.program();
+    drop(queue);
+
+    for timer in fired {
+        match timer.event {
+            TimerEvent::Wakeup(id) => wakeup_task(id),
+        }
+    }
+    quantum_expired && scheduler_tick()
+}
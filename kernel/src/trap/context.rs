@@ -4,10 +4,11 @@ use riscv::register::sstatus::Sstatus;
 #[derive(Clone, Copy)]
 pub struct TrapContext {
     pub x: [usize; 32],
-    kernel_satp: usize, // kernel page table
-    kernel_sp: usize,   // top of process's kernel stack
-    kernel_trap: usize, // user_trap_hanbler()
-    pub epc: usize,     // saved user program counter
+    kernel_satp: usize,       // kernel page table
+    kernel_sp: usize,         // top of process's kernel stack
+    kernel_trap: usize,       // user_trap_hanbler()
+    pub epc: usize,           // saved user program counter
+    pub kernel_hartid: usize, // hartid, loaded into tp by user_trap
 }
 
 impl TrapContext {
@@ -18,6 +19,7 @@ impl TrapContext {
             kernel_sp,
             kernel_trap,
             epc: 0,
+            kernel_hartid: 0,
         }
     }
 
@@ -25,6 +27,17 @@ impl TrapContext {
         self.x[2] = sp;
     }
 
+    // 程序入口的参数 main(argc, argv, envp)
+    pub fn set_args(&mut self, argc: usize, argv: usize, envp: usize) {
+        self.x[10] = argc;
+        self.x[11] = argv;
+        self.x[12] = envp;
+    }
+
+    pub fn set_kernel_sp(&mut self, kernel_sp: usize) {
+        self.kernel_sp = kernel_sp;
+    }
+
     pub fn set_epc(&mut self, epc: usize) {
         self.epc = epc;
     }
@@ -44,6 +57,7 @@ impl TrapContext {
             kernel_sp,
             kernel_trap,
             epc: entry,
+            kernel_hartid: 0,
         }
     }
 }
@@ -1,9 +1,4 @@
-use riscv::register::{sie, time};
-
-use crate::sbi::set_timer;
-
-const CLOCK_FREQ: usize = 12500000;
-const TICKS_PER_SEC: usize = 1000;
+use riscv::register::sie;
 
 pub fn enable_clock_interrupt() {
     unsafe {
@@ -11,12 +6,14 @@ pub fn enable_clock_interrupt() {
     }
 }
 
-pub fn unable_clock_interrupt() {
+pub fn enable_soft_interrupt() {
     unsafe {
-        sie::clear_stimer();
+        sie::set_ssoft();
     }
 }
 
-pub fn set_next_clock_interrupt() {
-    set_timer(time::read() + CLOCK_FREQ / TICKS_PER_SEC);
+pub fn unable_clock_interrupt() {
+    unsafe {
+        sie::clear_stimer();
+    }
 }
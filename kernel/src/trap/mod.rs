@@ -1,6 +1,10 @@
 use crate::{
+    mem::{kernel_space::kernel_stack_guard, user_space::StackFault},
     mem_layout::TRAMPOLINE,
-    task::{current_pagetable, current_user_epc, current_user_trapcontext},
+    task::{
+        current_killed, current_pagetable, current_stack_fault, current_user_epc,
+        current_user_trapcontext,
+    },
 };
 use core::arch::{asm, global_asm};
 
@@ -13,25 +17,31 @@ use riscv::register::{
 };
 
 use crate::{
+    cpu::{cpu_id, enter_interrupt, exit_interrupt},
+    smp::handle_ipi,
+    sync::{lock_kernel, unlock_kernel},
     syscall::syscall,
-    task::{current_user_satp, run_next_task_kill, run_next_task_suspend},
-    trap::interrupt::set_next_clock_interrupt,
+    task::{current_trap_frame_va, current_user_satp, run_next_task_kill, run_next_task_suspend},
+    timer::handle_timer_interrupt,
 };
 
 pub mod context;
 mod interrupt;
 global_asm!(include_str!("trampoline.S"));
+global_asm!(include_str!("kernelvec.S"));
 
 extern "C" {
     fn trampoline();
     fn user_trap();
     fn user_return();
+    fn kernel_vec();
 }
 
+// 每个 hart 各自初始化
 pub fn init() {
-    set_user_trap();
+    set_kernel_trap();
     interrupt::enable_clock_interrupt();
-    set_next_clock_interrupt();
+    interrupt::enable_soft_interrupt();
 }
 
 pub fn set_user_trap() {
@@ -45,13 +55,14 @@ pub fn set_user_trap() {
 
 pub fn set_kernel_trap() {
     unsafe {
-        stvec::write(kernel_trap as usize, TrapMode::Direct);
+        stvec::write(kernel_vec as usize, TrapMode::Direct);
     }
 }
 
 #[no_mangle]
 pub fn user_trap_handler() {
     set_kernel_trap();
+    lock_kernel();
 
     if sstatus::read().spp() != SPP::User {
         panic!("user_trap_handler: not from user mode");
@@ -65,9 +76,30 @@ pub fn user_trap_handler() {
         Trap::Exception(Exception::UserEnvCall) => {
             cx.epc += 4;
             let res = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
-            cx.x[10] = res;
+            // exec 成功后 trapframe 已换成新地址空间中的那一个
+            current_user_trapcontext().x[10] = res;
         }
-        Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::StorePageFault) => {
+        Trap::Exception(Exception::StorePageFault) | Trap::Exception(Exception::LoadPageFault) => {
+            match current_stack_fault(stval) {
+                StackFault::Grown => {}
+                StackFault::Overflow => {
+                    println!("[kernel] user stack overflow, kernel killed it.");
+                    run_next_task_kill()
+                }
+                StackFault::NoMemory => {
+                    println!("[kernel] no memory to grow user stack, kernel killed it.");
+                    run_next_task_kill()
+                }
+                StackFault::Outside => {
+                    println!("[kernel] PageFault in application, kernel killed it.");
+                    run_next_task_kill()
+                }
+            }
+        }
+        Trap::Exception(Exception::StoreFault)
+        | Trap::Exception(Exception::LoadFault)
+        | Trap::Exception(Exception::InstructionPageFault)
+        | Trap::Exception(Exception::InstructionFault) => {
             println!("[kernel] PageFault in application, kernel killed it.");
             run_next_task_kill()
         }
@@ -75,9 +107,30 @@ pub fn user_trap_handler() {
             println!("[kernel] IllegalInstruction in application, kernel killed it.");
             run_next_task_kill()
         }
+        // 其余异常同样只由用户程序自身引起
+        Trap::Exception(exception) => {
+            println!(
+                "[kernel] {:?} in application, stval = {:#x}, kernel killed it.",
+                exception, stval
+            );
+            run_next_task_kill()
+        }
+        // 切换任务前离开中断上下文
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_clock_interrupt();
-            run_next_task_suspend();
+            enter_interrupt();
+            let preempt = handle_timer_interrupt();
+            exit_interrupt();
+            if preempt {
+                run_next_task_suspend();
+            }
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            enter_interrupt();
+            let reschedule = handle_ipi();
+            exit_interrupt();
+            if reschedule {
+                run_next_task_suspend();
+            }
         }
         _ => {
             panic!(
@@ -89,6 +142,11 @@ pub fn user_trap_handler() {
         }
     }
 
+    // 被 kill 的任务不再返回用户态
+    if current_killed() {
+        run_next_task_kill();
+    }
+
     user_trap_return()
 }
 
@@ -97,14 +155,18 @@ pub fn user_trap_return() {
         sstatus::set_spp(SPP::User);
     }
     let satp = current_user_satp();
+    let trap_frame = current_trap_frame_va();
     sepc::write(current_user_epc());
+    current_user_trapcontext().kernel_hartid = cpu_id();
     set_user_trap();
+    unlock_kernel();
 
     unsafe {
         asm! {
             "jr {0}",
             in(reg) TRAMPOLINE + (user_return as usize - trampoline as usize),
             in("a0") satp,
+            in("a1") trap_frame,
         }
     }
 }
@@ -113,7 +175,56 @@ pub use context::TrapContext;
 
 use self::interrupt::{enable_clock_interrupt, unable_clock_interrupt};
 
+// 空闲循环中等待中断：先在关中断的状态下执行 wfi，避免错过唤醒
+// 被唤醒后短暂打开中断，由 kernel_interrupt() 处理
+pub fn wait_for_interrupt() {
+    set_kernel_trap();
+    unsafe {
+        asm!("wfi");
+        sstatus::set_sie();
+        sstatus::clear_sie();
+    }
+}
+
+// 只在空闲循环等待中断时进入，此时不持有大内核锁
+#[no_mangle]
+pub fn kernel_interrupt() {
+    enter_interrupt();
+    let scause = scause::read();
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            lock_kernel();
+            handle_timer_interrupt();
+            unlock_kernel();
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // 空闲循环返回后会重新检查就绪队列
+            handle_ipi();
+        }
+        _ => panic!("Unsupported kernel interrupt {:?}!", scause.cause()),
+    }
+    exit_interrupt();
+}
+
+// 由 kernel_vec 在专用栈上调用
 #[no_mangle]
 pub fn kernel_trap() -> ! {
-    panic!("kernel trap!");
+    let scause = scause::read();
+    let stval = stval::read();
+    match scause.cause() {
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            if let Some(id) = kernel_stack_guard(stval) {
+                panic!("kernel stack overflow in task {}", id);
+            }
+        }
+        _ => {}
+    }
+    panic!(
+        "kernel trap {:?}, stval = {:#x}, sepc = {:#x}!",
+        scause.cause(),
+        stval,
+        sepc::read()
+    );
 }
@@ -0,0 +1,20 @@
+#!/bin/sh
+# cargo test 的 runner：在 QEMU 中启动测试内核，测试结束后内核通过
+# sifive_test 关机，QEMU 的退出码即为测试结果
+# 用法: test-runner.sh <kernel elf>，BOARD、SMP、MEM 与 Makefile 中相同
+BOARD=${BOARD:-virt}
+# ELF 文件按其程序头中的地址加载，sifive_u 上 QEMU 自带的 OpenSBI 跳转到 -kernel 给出的内核
+if [ "$BOARD" = sifive_u ]; then
+	BOOTLOADER=default
+	LOAD_KERNEL="-kernel $1"
+else
+	BOOTLOADER=$(dirname "$0")/../bootloader/rustsbi-qemu.bin
+	LOAD_KERNEL="-device loader,file=$1"
+fi
+exec timeout "${TIMEOUT:-120}" qemu-system-riscv64 \
+	-m "${MEM:-128M}" \
+	-machine "$BOARD" \
+	-smp "${SMP:-4}" \
+	-nographic \
+	-bios "$BOOTLOADER" \
+	$LOAD_KERNEL
@@ -0,0 +1,263 @@
+//! Physical and virtual addresses, and the pages they fall in
+
+use core::fmt::{self, Debug, Formatter};
+
+use crate::{
+    pte::PageTableEntry, LEVELS, PAGE_BITS, PAGE_SIZE, PTE_FLAGS_BITS, PTE_NUM_PER_PAGE,
+    PTE_NUM_PER_PAGE_BITS,
+};
+
+// 地址结构
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(C)]
+pub struct Addr {
+    pub bits: usize,
+}
+
+impl Debug for Addr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("Addr: {:#x}", self.bits))
+    }
+}
+
+impl<T> From<*mut T> for Addr {
+    fn from(ptr: *mut T) -> Self {
+        Self { bits: ptr as usize }
+    }
+}
+impl<T> From<Addr> for *mut T {
+    fn from(addr: Addr) -> Self {
+        addr.bits as *mut T
+    }
+}
+
+impl<T> From<*const T> for Addr {
+    fn from(ptr: *const T) -> Self {
+        Self { bits: ptr as usize }
+    }
+}
+impl<T> From<Addr> for *const T {
+    fn from(addr: Addr) -> Self {
+        addr.bits as *const T
+    }
+}
+
+impl Addr {
+    pub fn empty() -> Self {
+        Addr { bits: 0 }
+    }
+
+    pub fn new(addr: usize) -> Self {
+        Addr { bits: addr }
+    }
+
+    // 转换成页表项
+    pub fn to_pte_bits(&self) -> usize {
+        (self.bits >> PAGE_BITS) << PTE_FLAGS_BITS
+    }
+
+    // 页内偏移
+    pub fn page_offset(&self) -> usize {
+        self.bits & (PAGE_SIZE - 1)
+    }
+    // 是否页对齐
+    pub fn aligned(&self) -> bool {
+        self.page_offset() == 0
+    }
+    // 向下页对齐
+    pub fn align_down(&self) -> Self {
+        Self {
+            bits: self.bits & !(PAGE_SIZE - 1),
+        }
+    }
+    // 向上页对齐
+    pub fn align_up(&self) -> Self {
+        Self {
+            bits: (self.bits + PAGE_SIZE - 1) & !(PAGE_SIZE - 1),
+        }
+    }
+    pub fn add(&self, offset: usize) -> Self {
+        Self {
+            bits: self.bits + offset,
+        }
+    }
+
+    // 获取虚拟地址的三级页表项偏移
+    pub fn get_indexes(&self) -> [usize; LEVELS] {
+        let mut res = [0usize; LEVELS];
+        let mut va = self.bits >> PAGE_BITS;
+        for index in res.iter_mut() {
+            *index = va & (PTE_NUM_PER_PAGE - 1);
+            va >>= PTE_NUM_PER_PAGE_BITS;
+        }
+        res
+    }
+
+    pub fn get_value<T>(&self) -> &'static T {
+        unsafe { (self.bits as *const T).as_ref().unwrap() }
+    }
+    pub fn get_value_mut<T>(&self) -> &'static mut T {
+        unsafe { (self.bits as *mut T).as_mut().unwrap() }
+    }
+}
+
+// 页面结构
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Page {
+    pub addr: usize,
+}
+
+impl Debug for Page {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("Page: {:#x}", self.addr))
+    }
+}
+
+impl Page {
+    pub fn empty() -> Self {
+        Self { addr: 0 }
+    }
+    pub fn new(addr: usize) -> Self {
+        Self {
+            addr: addr & !(PAGE_SIZE - 1),
+        }
+    }
+
+    // 清理页面
+    pub fn clean_page(&self) {
+        let dst = self.addr as *mut u8;
+        unsafe {
+            core::ptr::write_bytes(dst, 0, PAGE_SIZE);
+        }
+    }
+
+    // 读取字节序列
+    pub fn get_bytes(&self) -> &'static [u8] {
+        let src = self.addr as *const u8;
+        unsafe { core::slice::from_raw_parts(src, PAGE_SIZE) }
+    }
+    pub fn get_bytes_mut(&self) -> &'static mut [u8] {
+        let src = self.addr as *mut u8;
+        unsafe { core::slice::from_raw_parts_mut(src, PAGE_SIZE) }
+    }
+
+    // 读取页表项序列
+    pub fn get_ptes(&self) -> &'static [PageTableEntry] {
+        let src = self.addr as *const PageTableEntry;
+        unsafe { core::slice::from_raw_parts(src, PTE_NUM_PER_PAGE) }
+    }
+    pub fn get_ptes_mut(&self) -> &'static mut [PageTableEntry] {
+        let src = self.addr as *mut PageTableEntry;
+        unsafe { core::slice::from_raw_parts_mut(src, PTE_NUM_PER_PAGE) }
+    }
+}
+
+impl From<Addr> for Page {
+    fn from(addr: Addr) -> Self {
+        Self {
+            addr: addr.bits & !(PAGE_SIZE - 1),
+        }
+    }
+}
+impl From<Page> for Addr {
+    fn from(page: Page) -> Self {
+        Addr { bits: page.addr }
+    }
+}
+
+impl<T> From<*mut T> for Page {
+    fn from(ptr: *mut T) -> Self {
+        Self { addr: ptr as usize }
+    }
+}
+impl<T> From<Page> for *mut T {
+    fn from(page: Page) -> Self {
+        page.addr as *mut T
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{pte::PTEFlags, MAX_PHYS_ADDR, MAX_VIRT_ADDR};
+
+    #[test]
+    fn page_offset_and_alignment() {
+        let a = Addr::new(0x8020_1234);
+        assert_eq!(a.page_offset(), 0x234);
+        assert_eq!(a.align_down().bits, 0x8020_1000);
+        assert_eq!(a.align_up().bits, 0x8020_2000);
+        assert!(!a.aligned());
+        assert!(Addr::new(0x8020_1000).aligned());
+        assert_eq!(Addr::new(0x8020_1000).align_up().bits, 0x8020_1000);
+        assert_eq!(Addr::new(PAGE_SIZE - 1).page_offset(), PAGE_SIZE - 1);
+    }
+
+    #[test]
+    fn indexes_of_known_addresses() {
+        assert_eq!(Addr::new(0).get_indexes(), [0, 0, 0]);
+        assert_eq!(Addr::new(0x8020_0000).get_indexes(), [0x000, 0x001, 0x002]);
+        assert_eq!(Addr::new(MAX_VIRT_ADDR).get_indexes(), [511, 511, 511]);
+        // TRAMPOLINE
+        assert_eq!(
+            Addr::new((1 << 38) - PAGE_SIZE).get_indexes(),
+            [511, 511, 255]
+        );
+    }
+
+    #[test]
+    fn page_from_addr() {
+        assert_eq!(Page::from(Addr::new(0x8020_1fff)).addr, 0x8020_1000);
+        assert_eq!(Page::new(0x8020_1001).addr, 0x8020_1000);
+        let addr: Addr = Page::new(0x8020_1000).into();
+        assert_eq!(addr.bits, 0x8020_1000);
+    }
+
+    proptest! {
+        // 三级页表索引与页内偏移拼回原地址
+        #[test]
+        fn indexes_recompose(bits in 0..=MAX_VIRT_ADDR) {
+            let va = Addr::new(bits);
+            let indexes = va.get_indexes();
+            let mut recomposed = va.page_offset();
+            for (level, &index) in indexes.iter().enumerate() {
+                prop_assert!(index < PTE_NUM_PER_PAGE);
+                recomposed |= index << (PAGE_BITS + level * PTE_NUM_PER_PAGE_BITS);
+            }
+            prop_assert_eq!(recomposed, bits);
+        }
+
+        #[test]
+        fn offset_splits_address(bits in any::<usize>()) {
+            let a = Addr::new(bits);
+            prop_assert!(a.page_offset() < PAGE_SIZE);
+            prop_assert!(a.align_down().aligned());
+            prop_assert_eq!(a.align_down().bits + a.page_offset(), bits);
+            prop_assert_eq!(a.aligned(), a.page_offset() == 0);
+        }
+
+        #[test]
+        fn align_up_is_next_boundary(bits in 0..=usize::MAX - PAGE_SIZE) {
+            let a = Addr::new(bits);
+            let up = a.align_up();
+            prop_assert!(up.aligned());
+            prop_assert!(up.bits >= bits && up.bits - bits < PAGE_SIZE);
+            prop_assert_eq!(up == a, a.aligned());
+            prop_assert_eq!(up.align_down(), up);
+        }
+
+        // 物理地址存入页表项后原样取回，标志位互不干扰
+        #[test]
+        fn pte_bits_round_trip(bits in 0..=MAX_PHYS_ADDR, flags in any::<u8>()) {
+            let pa = Addr::new(bits).align_down();
+            let flags = PTEFlags::from_bits_truncate(flags);
+            let pte = PageTableEntry::new(pa, flags);
+            prop_assert_eq!(pa.to_pte_bits() & ((1 << PTE_FLAGS_BITS) - 1), 0);
+            prop_assert_eq!(pte.get_addr_bits(), pa.bits);
+            prop_assert!(pte.flags() == flags);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Addresses, page table entries and page table walks of RISC-V Sv39
+//!
+//! Everything here is plain arithmetic on numbers, so the crate builds for
+//! the kernel on riscv64 as well as for the host, where `cargo test` checks
+//! it. Page tables are reached through [`PhysMem`], which the kernel
+//! implements on its direct map of physical memory and the tests on a fake
+//! arena.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod address;
+pub mod pte;
+pub mod walk;
+
+pub use address::{Addr, Page};
+pub use pte::{level_size, PTEFlags, PageTableEntry};
+pub use walk::{translate, walk, walk_alloc, PhysMem, PteSlot};
+
+pub const PAGE_SIZE: usize = 4096; // bytes per page
+pub const PAGE_BITS: usize = 12; // bits of offset within a page
+pub const PTE_FLAGS_BITS: usize = 10;
+pub const PTE_NUM_PER_PAGE: usize = 1 << PTE_NUM_PER_PAGE_BITS;
+pub const PTE_NUM_PER_PAGE_BITS: usize = 9;
+/// Levels of an Sv39 page table, the root is level 2.
+pub const LEVELS: usize = 3;
+
+// physical address & virtual address
+pub const PA_WIDTH_SV39: usize = 56;
+pub const VA_WIDTH_SV39: usize = 39;
+pub const MAX_PHYS_ADDR: usize = (1 << PA_WIDTH_SV39) - 1;
+pub const MAX_VIRT_ADDR: usize = (1 << VA_WIDTH_SV39) - 1;
+pub const MAX_PHYS_SIZE: usize = 1 << (PA_WIDTH_SV39 - 1);
+pub const MAX_VIRT_SIZE: usize = 1 << (VA_WIDTH_SV39 - 1);
+
+// physical page number & virtual page number
+pub const PPN_WIDTH_SV39: usize = PA_WIDTH_SV39 - PAGE_BITS;
+pub const VPN_WIDTH_SV39: usize = VA_WIDTH_SV39 - PAGE_BITS;
+pub const MAX_PPN: usize = (1 << PPN_WIDTH_SV39) - 1;
+pub const MAX_VPN: usize = (1 << VPN_WIDTH_SV39) - 1;
@@ -0,0 +1,145 @@
+//! Sv39 page table entries
+
+use core::fmt::{self, Debug, Formatter};
+
+use bitflags::bitflags;
+
+use crate::{address::Addr, MAX_PPN, PAGE_BITS, PAGE_SIZE, PTE_FLAGS_BITS, PTE_NUM_PER_PAGE_BITS};
+
+bitflags! {
+    #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+    pub struct PTEFlags: u8 {
+        const V = 1 << 0;
+        const R = 1 << 1;
+        const W = 1 << 2;
+        const X = 1 << 3;
+        const U = 1 << 4;
+        const G = 1 << 5;
+        const A = 1 << 6;
+        const D = 1 << 7;
+    }
+}
+
+// 页表项结构
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct PageTableEntry {
+    pub bits: usize,
+}
+
+impl Debug for PageTableEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("pte: {:#x}", self.bits))
+    }
+}
+
+impl PageTableEntry {
+    pub fn empty() -> Self {
+        Self { bits: 0 }
+    }
+    pub fn new(pa: Addr, flags: PTEFlags) -> Self {
+        Self {
+            bits: pa.to_pte_bits() | flags.bits() as usize,
+        }
+    }
+
+    // 获取成页表项指向的物理页面地址，PPN 之上的位保留给扩展，不属于地址
+    pub fn get_addr_bits(&self) -> usize {
+        ((self.bits >> PTE_FLAGS_BITS) & MAX_PPN) << PAGE_BITS
+    }
+    // 获取页表项 flags
+    pub fn flags(&self) -> PTEFlags {
+        PTEFlags::from_bits(self.bits as u8).unwrap()
+    }
+    pub fn valid(&self) -> bool {
+        (self.flags() & PTEFlags::V) != PTEFlags::empty()
+    }
+    pub fn readable(&self) -> bool {
+        (self.flags() & PTEFlags::R) != PTEFlags::empty()
+    }
+    pub fn writable(&self) -> bool {
+        (self.flags() & PTEFlags::W) != PTEFlags::empty()
+    }
+    pub fn executable(&self) -> bool {
+        (self.flags() & PTEFlags::X) != PTEFlags::empty()
+    }
+    pub fn user(&self) -> bool {
+        (self.flags() & PTEFlags::U) != PTEFlags::empty()
+    }
+    // R/W/X 任一置位即为叶子页表项，否则指向下一级页表
+    pub fn is_leaf(&self) -> bool {
+        self.valid() && (self.readable() || self.writable() || self.executable())
+    }
+}
+
+// 第 level 级叶子页表项映射的页面大小：0 级 4K，1 级 2M，2 级 1G
+pub fn level_size(level: usize) -> usize {
+    PAGE_SIZE << (PTE_NUM_PER_PAGE_BITS * level)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::MAX_PHYS_ADDR;
+
+    #[test]
+    fn decode_flags() {
+        // 指向 0x8020_0000 的内核代码页
+        let pte = PageTableEntry {
+            bits: 0x2008_0000 | 0xcb,
+        };
+        assert_eq!(pte.get_addr_bits(), 0x8020_0000);
+        assert!(pte.valid() && pte.readable() && pte.executable());
+        assert!(!pte.writable() && !pte.user());
+        assert!(pte.is_leaf());
+        assert_eq!(
+            pte.flags(),
+            PTEFlags::V | PTEFlags::R | PTEFlags::X | PTEFlags::A | PTEFlags::D
+        );
+
+        // 只有 V 的页表项指向下一级页表
+        let pte = PageTableEntry::new(Addr::new(0x8765_4000), PTEFlags::V);
+        assert!(pte.valid() && !pte.is_leaf());
+        assert!(!PageTableEntry::empty().valid());
+        assert!(!PageTableEntry::new(Addr::new(0x1000), PTEFlags::R).is_leaf());
+    }
+
+    #[test]
+    fn reserved_bits_are_not_address() {
+        // 位 54..63 保留给 Svpbmt、Svnapot 等扩展
+        let pte = PageTableEntry {
+            bits: (0x3ff << 54) | (0x80200 << PTE_FLAGS_BITS) | 0x1,
+        };
+        assert_eq!(pte.get_addr_bits(), 0x8020_0000);
+        assert!(pte.get_addr_bits() <= MAX_PHYS_ADDR);
+    }
+
+    #[test]
+    fn sizes_of_levels() {
+        assert_eq!(level_size(0), 4096);
+        assert_eq!(level_size(1), 2 * 1024 * 1024);
+        assert_eq!(level_size(2), 1024 * 1024 * 1024);
+    }
+
+    proptest! {
+        #[test]
+        fn predicates_match_flags(bits in any::<usize>()) {
+            let pte = PageTableEntry { bits };
+            let flags = PTEFlags::from_bits_truncate(bits as u8);
+            prop_assert_eq!(pte.flags(), flags);
+            prop_assert_eq!(pte.valid(), flags.contains(PTEFlags::V));
+            prop_assert_eq!(pte.readable(), flags.contains(PTEFlags::R));
+            prop_assert_eq!(pte.writable(), flags.contains(PTEFlags::W));
+            prop_assert_eq!(pte.executable(), flags.contains(PTEFlags::X));
+            prop_assert_eq!(pte.user(), flags.contains(PTEFlags::U));
+            prop_assert_eq!(
+                pte.is_leaf(),
+                pte.valid() && flags.intersects(PTEFlags::R | PTEFlags::W | PTEFlags::X)
+            );
+            prop_assert!(pte.get_addr_bits() <= MAX_PHYS_ADDR);
+            prop_assert_eq!(pte.get_addr_bits() % PAGE_SIZE, 0);
+        }
+    }
+}
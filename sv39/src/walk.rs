@@ -0,0 +1,102 @@
+//! Walking page tables that live in physical memory
+//!
+//! The walks only compute where an entry is, reading and writing entries
+//! through [`PhysMem`]. Callers turn the returned [`PteSlot`] into whatever
+//! reference to the entry their memory allows.
+
+use crate::{
+    address::{Addr, Page},
+    pte::{level_size, PTEFlags, PageTableEntry},
+    LEVELS, MAX_VIRT_ADDR,
+};
+
+/// Physical memory holding page tables.
+pub trait PhysMem {
+    /// Entry `index` of the page table in `table`.
+    fn read_pte(&self, table: Page, index: usize) -> PageTableEntry;
+    fn write_pte(&mut self, table: Page, index: usize, pte: PageTableEntry);
+}
+
+/// Entry `index` of the page table in `table`, which is at level `level`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PteSlot {
+    pub table: Page,
+    pub index: usize,
+    pub level: usize,
+}
+
+/// Find the entry of level `target` for `va` in the page table at `root`,
+/// stopping early at a superpage leaf. None when a table on the way is
+/// missing.
+pub fn walk(mem: &impl PhysMem, root: Page, va: Addr, target: usize) -> Option<PteSlot> {
+    assert!(va.bits <= MAX_VIRT_ADDR);
+    assert!(target < LEVELS);
+
+    let indexes = va.get_indexes();
+    let mut table = root;
+    let mut level = LEVELS - 1;
+    loop {
+        let index = indexes[level];
+        let pte = mem.read_pte(table, index);
+        if level == target || pte.is_leaf() {
+            return Some(PteSlot {
+                table,
+                index,
+                level,
+            });
+        }
+        if !pte.valid() {
+            return None;
+        }
+        table = Page::new(pte.get_addr_bits());
+        level -= 1;
+    }
+}
+
+/// Like [`walk`], but missing tables are taken from `alloc`, which must
+/// return zeroed pages. None when `alloc` runs out.
+pub fn walk_alloc(
+    mem: &mut impl PhysMem,
+    root: Page,
+    va: Addr,
+    target: usize,
+    mut alloc: impl FnMut() -> Option<Page>,
+) -> Option<PteSlot> {
+    assert!(va.bits <= MAX_VIRT_ADDR);
+    assert!(target < LEVELS);
+
+    let indexes = va.get_indexes();
+    let mut table = root;
+    let mut level = LEVELS - 1;
+    loop {
+        let index = indexes[level];
+        let pte = mem.read_pte(table, index);
+        if level == target || pte.is_leaf() {
+            return Some(PteSlot {
+                table,
+                index,
+                level,
+            });
+        }
+        table = if pte.valid() {
+            Page::new(pte.get_addr_bits())
+        } else {
+            let page = alloc()?;
+            mem.write_pte(table, index, PageTableEntry::new(page.into(), PTEFlags::V));
+            page
+        };
+        level -= 1;
+    }
+}
+
+/// The physical address `va` maps to and the flags of its leaf entry.
+pub fn translate(mem: &impl PhysMem, root: Page, va: Addr) -> Option<(Addr, PTEFlags)> {
+    let slot = walk(mem, root, va, 0)?;
+    let pte = mem.read_pte(slot.table, slot.index);
+    // 0 级页表项必须是叶子
+    if !pte.is_leaf() {
+        return None;
+    }
+    let offset = va.bits & (level_size(slot.level) - 1);
+    Some((Addr::new(pte.get_addr_bits() | offset), pte.flags()))
+}
@@ -0,0 +1,236 @@
+//! Page table walks over a fake physical memory
+
+use std::collections::HashMap;
+
+use proptest::prelude::*;
+use sv39::{
+    level_size, translate, walk, walk_alloc, Addr, PTEFlags, Page, PageTableEntry, PhysMem,
+    MAX_PPN, MAX_VPN, PAGE_BITS, PAGE_SIZE, PTE_NUM_PER_PAGE,
+};
+
+// 假的物理内存从 BASE 开始，每页都是一个页表
+const BASE: usize = 0x8000_0000;
+
+struct Arena {
+    tables: Vec<[PageTableEntry; PTE_NUM_PER_PAGE]>,
+}
+
+impl Arena {
+    fn table(&self, table: Page) -> usize {
+        assert!(table.addr >= BASE, "{:?} is outside the arena", table);
+        let i = (table.addr - BASE) / PAGE_SIZE;
+        assert!(i < self.tables.len(), "{:?} is outside the arena", table);
+        i
+    }
+}
+
+impl PhysMem for Arena {
+    fn read_pte(&self, table: Page, index: usize) -> PageTableEntry {
+        self.tables[self.table(table)][index]
+    }
+
+    fn write_pte(&mut self, table: Page, index: usize, pte: PageTableEntry) {
+        let i = self.table(table);
+        self.tables[i][index] = pte;
+    }
+}
+
+// 依次分配 arena 中的页面，第 0 页是根页表
+struct Frames {
+    next: usize,
+    end: usize,
+}
+
+impl Frames {
+    fn alloc(&mut self) -> Option<Page> {
+        if self.next == self.end {
+            return None;
+        }
+        self.next += 1;
+        Some(Page::new(BASE + (self.next - 1) * PAGE_SIZE))
+    }
+}
+
+struct Machine {
+    mem: Arena,
+    frames: Frames,
+    root: Page,
+}
+
+impl Machine {
+    fn new(pages: usize) -> Self {
+        let mut frames = Frames {
+            next: 0,
+            end: pages,
+        };
+        let root = frames.alloc().unwrap();
+        Self {
+            mem: Arena {
+                tables: vec![[PageTableEntry::empty(); PTE_NUM_PER_PAGE]; pages],
+            },
+            frames,
+            root,
+        }
+    }
+
+    // 在第 level 级建立叶子页表项，与内核的 PageTable::map_level() 相同
+    fn map(&mut self, va: Addr, pa: Addr, flags: PTEFlags, level: usize) -> bool {
+        let frames = &mut self.frames;
+        let slot = match walk_alloc(&mut self.mem, self.root, va, level, || frames.alloc()) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        assert_eq!(slot.level, level, "{:?} is inside a superpage", va);
+        assert!(!self.mem.read_pte(slot.table, slot.index).valid());
+        self.mem.write_pte(
+            slot.table,
+            slot.index,
+            PageTableEntry::new(pa, flags | PTEFlags::V),
+        );
+        true
+    }
+
+    fn translate(&self, va: usize) -> Option<(usize, PTEFlags)> {
+        translate(&self.mem, self.root, Addr::new(va)).map(|(pa, flags)| (pa.bits, flags))
+    }
+
+    fn tables(&self) -> usize {
+        self.frames.next
+    }
+}
+
+const RW: PTEFlags = PTEFlags::R.union(PTEFlags::W);
+
+#[test]
+fn empty_table_maps_nothing() {
+    let m = Machine::new(1);
+    for va in [0, 0x1000, 0x8020_0000, (1 << 38) - PAGE_SIZE] {
+        assert_eq!(m.translate(va), None);
+        assert_eq!(walk(&m.mem, m.root, Addr::new(va), 0), None);
+        let slot = walk(&m.mem, m.root, Addr::new(va), 2).unwrap();
+        assert_eq!(slot.table, m.root);
+        assert_eq!(slot.index, Addr::new(va).get_indexes()[2]);
+    }
+}
+
+#[test]
+fn map_pages() {
+    let mut m = Machine::new(8);
+    let va = 0x1000_3000;
+    let pa = 0x8765_4000;
+    assert!(m.map(Addr::new(va), Addr::new(pa), RW | PTEFlags::U, 0));
+    // 根页表之外还需要 1 级与 0 级页表
+    assert_eq!(m.tables(), 3);
+    assert_eq!(
+        m.translate(va + 0x123),
+        Some((pa + 0x123, PTEFlags::V | RW | PTEFlags::U))
+    );
+    assert_eq!(m.translate(va - PAGE_SIZE), None);
+    assert_eq!(m.translate(va + PAGE_SIZE), None);
+
+    // 同一个 2M 区域共用 0 级页表，同一个 1G 区域共用 1 级页表
+    assert!(m.map(Addr::new(va + PAGE_SIZE), Addr::new(pa), PTEFlags::R, 0));
+    assert_eq!(m.tables(), 3);
+    assert!(m.map(Addr::new(va + level_size(1)), Addr::new(pa), PTEFlags::R, 0));
+    assert_eq!(m.tables(), 4);
+    assert!(m.map(Addr::new(va + level_size(2)), Addr::new(pa), PTEFlags::R, 0));
+    assert_eq!(m.tables(), 6);
+
+    let slot = walk(&m.mem, m.root, Addr::new(va), 0).unwrap();
+    assert_eq!(slot.level, 0);
+    assert_eq!(slot.index, 3);
+}
+
+#[test]
+fn map_superpages() {
+    let mut m = Machine::new(4);
+    let va_2m = 0x4000_0000 + 5 * level_size(1);
+    let pa_2m = 0x8040_0000;
+    assert!(m.map(Addr::new(va_2m), Addr::new(pa_2m), RW, 1));
+    let va_1g = 3 * level_size(2);
+    let pa_1g = 0x8000_0000;
+    assert!(m.map(
+        Addr::new(va_1g),
+        Addr::new(pa_1g),
+        PTEFlags::R | PTEFlags::X,
+        2
+    ));
+    assert_eq!(m.tables(), 2);
+
+    for offset in [0, 0x1234, level_size(1) - 1] {
+        assert_eq!(
+            m.translate(va_2m + offset),
+            Some((pa_2m + offset, PTEFlags::V | RW))
+        );
+    }
+    assert_eq!(m.translate(va_2m + level_size(1)), None);
+    for offset in [0, 0x20_1234, level_size(2) - 1] {
+        assert_eq!(m.translate(va_1g + offset).unwrap().0, pa_1g + offset);
+    }
+
+    // 查找 0 级页表项时停在大页叶子上
+    assert_eq!(
+        walk(&m.mem, m.root, Addr::new(va_2m + 0x5000), 0)
+            .unwrap()
+            .level,
+        1
+    );
+    assert_eq!(
+        walk(&m.mem, m.root, Addr::new(va_1g + 0x5000), 0)
+            .unwrap()
+            .level,
+        2
+    );
+}
+
+#[test]
+fn out_of_tables() {
+    let mut m = Machine::new(2);
+    assert!(!m.map(Addr::new(0x1000), Addr::new(0x8000_0000), RW, 0));
+    assert_eq!(m.translate(0x1000), None);
+    // 大页只需要已分配的 1 级页表
+    assert!(m.map(Addr::new(0x20_0000), Addr::new(0x8020_0000), RW, 1));
+    assert_eq!(
+        m.translate(0x20_0000),
+        Some((0x8020_0000, PTEFlags::V | RW))
+    );
+}
+
+#[test]
+fn table_pointer_is_not_a_mapping() {
+    let mut m = Machine::new(4);
+    assert!(m.map(Addr::new(0x1000), Addr::new(0x8000_0000), RW, 0));
+    // 把 0 级页表项改成只有 V 的非叶子项
+    let slot = walk(&m.mem, m.root, Addr::new(0x1000), 0).unwrap();
+    m.mem.write_pte(
+        slot.table,
+        slot.index,
+        PageTableEntry::new(Addr::new(0x8000_0000), PTEFlags::V),
+    );
+    assert_eq!(m.translate(0x1000), None);
+}
+
+proptest! {
+    // 随机映射一组页面，每个页面都按模型翻译，其余地址都没有映射
+    #[test]
+    fn walk_matches_model(
+        pages in prop::collection::hash_map(0..=MAX_VPN, 0..=MAX_PPN, 1..64),
+        probes in prop::collection::vec(0..=MAX_VPN, 64),
+        offset in 0..PAGE_SIZE,
+    ) {
+        let mut m = Machine::new(1 + 2 * pages.len() + 1);
+        let model: HashMap<usize, usize> = pages;
+        for (&vpn, &ppn) in &model {
+            let va = Addr::new(vpn << PAGE_BITS);
+            let pa = Addr::new(ppn << PAGE_BITS);
+            prop_assert!(m.map(va, pa, RW | PTEFlags::U, 0));
+        }
+        for vpn in model.keys().copied().chain(probes) {
+            let va = (vpn << PAGE_BITS) | offset;
+            let expected = model
+                .get(&vpn)
+                .map(|&ppn| ((ppn << PAGE_BITS) | offset, PTEFlags::V | RW | PTEFlags::U));
+            prop_assert_eq!(m.translate(va), expected);
+        }
+    }
+}
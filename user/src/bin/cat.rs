@@ -0,0 +1,50 @@
+//! Copy files, or standard input when there are none, to standard output.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{close, env, open, read, write, O_RDONLY};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+fn cat(fd: usize) -> bool {
+    let mut buf = [0u8; 512];
+    loop {
+        let n = read(fd, &mut buf);
+        if n == 0 {
+            return true;
+        }
+        if n < 0 {
+            eprintln!("cat: read error");
+            return false;
+        }
+        if write(STDOUT, &buf[..n as usize]) != n {
+            eprintln!("cat: write error");
+            return false;
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    if env::args().len() < 2 {
+        return if cat(STDIN) { 0 } else { 1 };
+    }
+    for path in env::args().skip(1) {
+        let fd = open(path, O_RDONLY);
+        if fd < 0 {
+            eprintln!("cat: cannot open {}", path);
+            return 1;
+        }
+        let ok = cat(fd as usize);
+        close(fd as usize);
+        if !ok {
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,21 @@
+//! Print the arguments separated by spaces.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::env;
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    for (i, arg) in env::args().skip(1).enumerate() {
+        if i > 0 {
+            print!(" ");
+        }
+        print!("{}", arg);
+    }
+    println!("");
+    0
+}
@@ -0,0 +1,132 @@
+//! Print the lines that match a pattern.
+//!
+//! Patterns are the simple regular expressions of xv6's `grep`, from
+//! Kernighan and Pike's *The Practice of Programming*: `c` matches the
+//! character c, `.` any character, `^` the start and `$` the end of the
+//! line, and `*` zero or more of the preceding character. Exits with 0
+//! when some line matched and 1 otherwise.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{close, env, open, read, write, O_RDONLY};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+const BUF_SIZE: usize = 1024;
+
+fn matches(re: &[u8], text: &[u8]) -> bool {
+    if let Some((b'^', re)) = re.split_first() {
+        return match_here(re, text);
+    }
+    // 空行也要尝试一次
+    (0..=text.len()).any(|i| match_here(re, &text[i..]))
+}
+
+// re 是否匹配 text 的开头
+fn match_here(re: &[u8], text: &[u8]) -> bool {
+    match re {
+        [] => true,
+        [c, b'*', rest @ ..] => match_star(*c, rest, text),
+        [b'$'] => text.is_empty(),
+        [c, rest @ ..] => match text.split_first() {
+            Some((t, text)) if *c == b'.' || c == t => match_here(rest, text),
+            _ => false,
+        },
+    }
+}
+
+// c* 后接 re 是否匹配 text 的开头
+fn match_star(c: u8, re: &[u8], text: &[u8]) -> bool {
+    let mut i = 0;
+    loop {
+        if match_here(re, &text[i..]) {
+            return true;
+        }
+        if i < text.len() && (text[i] == c || c == b'.') {
+            i += 1;
+        } else {
+            return false;
+        }
+    }
+}
+
+// 逐行匹配，行可能跨越多次 read，不完整的行留在 buf 开头
+fn grep(pattern: &[u8], fd: usize) -> Option<bool> {
+    let mut buf = [0u8; BUF_SIZE];
+    let mut len = 0;
+    let mut found = false;
+    loop {
+        let n = read(fd, &mut buf[len..]);
+        if n < 0 {
+            eprintln!("grep: read error");
+            return None;
+        }
+        let eof = n == 0;
+        len += n as usize;
+        let mut start = 0;
+        while let Some(end) = buf[start..len].iter().position(|&c| c == b'\n') {
+            let line = &buf[start..start + end + 1];
+            if matches(pattern, &line[..end]) {
+                write(STDOUT, line);
+                found = true;
+            }
+            start += end + 1;
+        }
+        // 文件末尾没有换行的最后一行，以及放不下缓冲区的超长行
+        if (eof || (start == 0 && len == BUF_SIZE)) && start < len {
+            let line = &buf[start..len];
+            if matches(pattern, line) {
+                write(STDOUT, line);
+                write(STDOUT, b"\n");
+                found = true;
+            }
+            start = len;
+        }
+        buf.copy_within(start..len, 0);
+        len -= start;
+        if eof {
+            return Some(found);
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    let mut args = env::args().skip(1);
+    let pattern = match args.next() {
+        Some(pattern) => pattern.as_bytes(),
+        None => {
+            eprintln!("usage: grep pattern [file ...]");
+            return 2;
+        }
+    };
+    let mut found = false;
+    if args.len() == 0 {
+        match grep(pattern, STDIN) {
+            Some(matched) => found = matched,
+            None => return 2,
+        }
+    }
+    for path in args {
+        let fd = open(path, O_RDONLY);
+        if fd < 0 {
+            eprintln!("grep: cannot open {}", path);
+            return 2;
+        }
+        let result = grep(pattern, fd as usize);
+        close(fd as usize);
+        match result {
+            Some(matched) => found |= matched,
+            None => return 2,
+        }
+    }
+    if found {
+        0
+    } else {
+        1
+    }
+}
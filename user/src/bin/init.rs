@@ -0,0 +1,60 @@
+//! The first process
+//!
+//! Opens the console as standard input, output and error, which every
+//! other process inherits, then keeps a shell running: it starts `sh` and
+//! starts it again whenever it exits. Processes whose parent exits become
+//! children of init, which reaps them while waiting for the shell. init
+//! never returns, the kernel panics if it exits: anything that fails is
+//! retried after [`RETRY_MS`].
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{dup, exec, exit, fork, open, sleep, wait, O_RDWR};
+
+const RETRY_MS: usize = 1000;
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    // 描述符表为空，控制台依次成为 0、1、2 号描述符，打开失败时没有地方报告错误
+    while open("/dev/console", O_RDWR) != 0 {
+        sleep(RETRY_MS);
+    }
+    dup(0);
+    dup(0);
+
+    loop {
+        println!("init: starting sh");
+        let pid = fork();
+        if pid < 0 {
+            eprintln!("init: fork failed");
+            sleep(RETRY_MS);
+            continue;
+        }
+        if pid == 0 {
+            exec("sh", &["sh"]);
+            eprintln!("init: exec sh failed");
+            exit(1);
+        }
+        loop {
+            let mut status = 0;
+            let wpid = wait(&mut status);
+            if wpid == pid {
+                // shell 退出，重新启动，异常退出（例如 exec 失败）时稍后再启动
+                if status != 0 {
+                    sleep(RETRY_MS);
+                }
+                break;
+            }
+            if wpid < 0 {
+                // 不应发生，shell 仍是 init 的子进程
+                eprintln!("init: wait returned an error");
+                sleep(RETRY_MS);
+            }
+            // 被回收的是孤儿进程
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Kill processes by pid.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{env, kill};
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    if env::args().len() < 2 {
+        eprintln!("Usage: kill pid...");
+        return 1;
+    }
+    let mut status = 0;
+    for arg in env::args().skip(1) {
+        match arg.parse::<usize>() {
+            Ok(pid) if kill(pid) == 0 => {}
+            Ok(_) => {
+                eprintln!("kill: ({}) - No such process", arg);
+                status = 1;
+            }
+            Err(_) => {
+                eprintln!("kill: {}: not a pid", arg);
+                status = 1;
+            }
+        }
+    }
+    status
+}
@@ -0,0 +1,26 @@
+//! Give a file another name.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{env, link};
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    let mut args = env::args().skip(1);
+    let (old, new) = match (args.next(), args.next(), args.next()) {
+        (Some(old), Some(new), None) => (old, new),
+        _ => {
+            eprintln!("Usage: ln old new");
+            return 1;
+        }
+    };
+    if link(old, new) < 0 {
+        eprintln!("link {} {}: failed", old, new);
+        return 1;
+    }
+    0
+}
@@ -0,0 +1,94 @@
+//! List directories, or describe files.
+//!
+//! Each line gives the name, type, inode number and size, as xv6's `ls`
+//! does. Without arguments the working directory is listed.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{close, env, fstat, open, stat, Dirent, Stat, DIRSIZ, MAX_PATH, O_RDONLY, T_DIR};
+
+fn print_entry(name: &str, st: &Stat) {
+    println!(
+        "{:<width$} {} {} {}",
+        name,
+        st.kind,
+        st.ino,
+        st.size,
+        width = DIRSIZ
+    );
+}
+
+// 路径的最后一项
+fn basename(path: &str) -> &str {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(i) => &path[i + 1..],
+        None => path,
+    }
+}
+
+fn list_dir(fd: usize, path: &str) -> bool {
+    if path.len() + 1 + DIRSIZ > MAX_PATH {
+        eprintln!("ls: path too long");
+        return false;
+    }
+    // 在 buf 中拼出 "path/name" 以读取每一项的状态
+    let mut buf = [0u8; MAX_PATH];
+    let prefix = path.len() + 1;
+    buf[..path.len()].copy_from_slice(path.as_bytes());
+    buf[path.len()] = b'/';
+    while let Some(entry) = Dirent::read(fd) {
+        if entry.ino == 0 {
+            continue;
+        }
+        let name = entry.name();
+        buf[prefix..prefix + name.len()].copy_from_slice(name.as_bytes());
+        let full = core::str::from_utf8(&buf[..prefix + name.len()]).unwrap_or("");
+        let mut st = Stat::default();
+        if stat(full, &mut st) < 0 {
+            eprintln!("ls: cannot stat {}", full);
+            continue;
+        }
+        print_entry(name, &st);
+    }
+    true
+}
+
+fn ls(path: &str) -> bool {
+    let fd = open(path, O_RDONLY);
+    if fd < 0 {
+        eprintln!("ls: cannot open {}", path);
+        return false;
+    }
+    let fd = fd as usize;
+    let mut st = Stat::default();
+    let ok = if fstat(fd, &mut st) < 0 {
+        eprintln!("ls: cannot stat {}", path);
+        false
+    } else if st.kind == T_DIR {
+        list_dir(fd, path)
+    } else {
+        print_entry(basename(path), &st);
+        true
+    };
+    close(fd);
+    ok
+}
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    if env::args().len() < 2 {
+        return if ls(".") { 0 } else { 1 };
+    }
+    let mut status = 0;
+    for path in env::args().skip(1) {
+        if !ls(path) {
+            status = 1;
+        }
+    }
+    status
+}
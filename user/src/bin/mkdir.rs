@@ -0,0 +1,24 @@
+//! Create directories.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{env, mkdir};
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    if env::args().len() < 2 {
+        eprintln!("Usage: mkdir files...");
+        return 1;
+    }
+    for path in env::args().skip(1) {
+        if mkdir(path) < 0 {
+            eprintln!("mkdir: {} failed to create", path);
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,24 @@
+//! Remove files and empty directories.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{env, unlink};
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    if env::args().len() < 2 {
+        eprintln!("Usage: rm files...");
+        return 1;
+    }
+    for path in env::args().skip(1) {
+        if unlink(path) < 0 {
+            eprintln!("rm: {} failed to delete", path);
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,541 @@
+//! Command shell
+//!
+//! Reads command lines from standard input and runs them, with the same
+//! grammar as the xv6 shell:
+//!
+//! ```text
+//! line     := pipeline ('&')* ((';' | after '&') line)?
+//! pipeline := command ('|' pipeline)?
+//! command  := '(' line ')' redirect* | (word | redirect)+
+//! redirect := '<' word | '>' word | '>>' word
+//! ```
+//!
+//! A word starting with `#` starts a comment. `cd` and `exit` are built
+//! in. A nonzero exit status of a foreground command is reported, and
+//! background jobs are reported when they finish, before the next prompt.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{
+    chdir, close, dup, exec, exit, fork, open, pipe, read, waitpid, MAX_ARGS, O_APPEND, O_CREATE,
+    O_RDONLY, O_TRUNC, O_WRONLY, WNOHANG,
+};
+
+const LINE_MAX: usize = 512;
+const MAX_NODES: usize = 64;
+const MAX_WORDS: usize = 128;
+const MAX_REDIRS: usize = 8;
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Word(&'a str),
+    Pipe,
+    Less,
+    Great,
+    DoubleGreat,
+    Amp,
+    Semi,
+    LParen,
+    RParen,
+    End,
+}
+
+#[derive(Clone, Copy)]
+struct Redir<'a> {
+    file: &'a str,
+    flags: u32,
+    fd: usize,
+}
+
+// 子命令以其在 nodes 中的下标引用
+#[derive(Clone, Copy)]
+enum Cmd<'a> {
+    Exec { start: usize, len: usize }, // 参数为 words[start..start + len]
+    Redir { cmd: usize, redir: Redir<'a> },
+    Pipe { left: usize, right: usize },
+    List { left: usize, right: usize },
+    Back { cmd: usize },
+}
+
+struct Parser<'a> {
+    line: &'a str,
+    pos: usize,
+    peeked: Option<Token<'a>>,
+    nodes: [Option<Cmd<'a>>; MAX_NODES],
+    node_count: usize,
+    words: [&'a str; MAX_WORDS],
+    word_count: usize,
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\r' | b'\n')
+}
+
+fn is_symbol(c: u8) -> bool {
+    matches!(c, b'|' | b'<' | b'>' | b'&' | b';' | b'(' | b')')
+}
+
+impl<'a> Parser<'a> {
+    fn new(line: &'a str) -> Self {
+        Self {
+            line,
+            pos: 0,
+            peeked: None,
+            nodes: [None; MAX_NODES],
+            node_count: 0,
+            words: [""; MAX_WORDS],
+            word_count: 0,
+        }
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        if let Some(token) = self.peeked.take() {
+            return token;
+        }
+        let bytes = self.line.as_bytes();
+        while self.pos < bytes.len() && is_space(bytes[self.pos]) {
+            self.pos += 1;
+        }
+        if self.pos >= bytes.len() || bytes[self.pos] == b'#' {
+            self.pos = bytes.len();
+            return Token::End;
+        }
+        let start = self.pos;
+        self.pos += 1;
+        match bytes[start] {
+            b'|' => Token::Pipe,
+            b'<' => Token::Less,
+            b'>' if self.pos < bytes.len() && bytes[self.pos] == b'>' => {
+                self.pos += 1;
+                Token::DoubleGreat
+            }
+            b'>' => Token::Great,
+            b'&' => Token::Amp,
+            b';' => Token::Semi,
+            b'(' => Token::LParen,
+            b')' => Token::RParen,
+            _ => {
+                while self.pos < bytes.len()
+                    && !is_space(bytes[self.pos])
+                    && !is_symbol(bytes[self.pos])
+                {
+                    self.pos += 1;
+                }
+                Token::Word(&self.line[start..self.pos])
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Token<'a> {
+        let token = self.next();
+        self.peeked = Some(token);
+        token
+    }
+
+    fn add(&mut self, cmd: Cmd<'a>) -> Option<usize> {
+        if self.node_count == MAX_NODES {
+            eprintln!("sh: command too long");
+            return None;
+        }
+        self.nodes[self.node_count] = Some(cmd);
+        self.node_count += 1;
+        Some(self.node_count - 1)
+    }
+
+    fn node(&self, id: usize) -> Cmd<'a> {
+        self.nodes[id].unwrap()
+    }
+
+    fn argv(&self, start: usize, len: usize) -> &[&'a str] {
+        &self.words[start..start + len]
+    }
+
+    // 整行解析，空行返回 Some(None)，语法错误返回 None
+    fn parse(&mut self) -> Option<Option<usize>> {
+        if self.peek() == Token::End {
+            return Some(None);
+        }
+        let cmd = self.parse_line()?;
+        if self.next() != Token::End {
+            eprintln!("sh: syntax error");
+            return None;
+        }
+        Some(Some(cmd))
+    }
+
+    fn parse_line(&mut self) -> Option<usize> {
+        let mut cmd = self.parse_pipeline()?;
+        let mut background = false;
+        while self.peek() == Token::Amp {
+            self.next();
+            cmd = self.add(Cmd::Back { cmd })?;
+            background = true;
+        }
+        let more = match self.peek() {
+            Token::Semi => {
+                self.next();
+                !matches!(self.peek(), Token::End | Token::RParen)
+            }
+            // "a & b" 与 "a & ; b" 相同
+            Token::Word(_) | Token::LParen => background,
+            _ => false,
+        };
+        if more {
+            let right = self.parse_line()?;
+            cmd = self.add(Cmd::List { left: cmd, right })?;
+        }
+        Some(cmd)
+    }
+
+    fn parse_pipeline(&mut self) -> Option<usize> {
+        let left = self.parse_command()?;
+        if self.peek() != Token::Pipe {
+            return Some(left);
+        }
+        self.next();
+        let right = self.parse_pipeline()?;
+        self.add(Cmd::Pipe { left, right })
+    }
+
+    fn parse_command(&mut self) -> Option<usize> {
+        let mut redirs = [None; MAX_REDIRS];
+        let mut redir_count = 0;
+        let cmd = if self.peek() == Token::LParen {
+            self.next();
+            let inner = self.parse_line()?;
+            if self.next() != Token::RParen {
+                eprintln!("sh: missing )");
+                return None;
+            }
+            while let Some(redir) = self.parse_redir()? {
+                if redir_count == MAX_REDIRS {
+                    eprintln!("sh: too many redirections");
+                    return None;
+                }
+                redirs[redir_count] = Some(redir);
+                redir_count += 1;
+            }
+            inner
+        } else {
+            let start = self.word_count;
+            loop {
+                if let Token::Word(word) = self.peek() {
+                    self.next();
+                    if self.word_count - start == MAX_ARGS || self.word_count == MAX_WORDS {
+                        eprintln!("sh: too many arguments");
+                        return None;
+                    }
+                    self.words[self.word_count] = word;
+                    self.word_count += 1;
+                } else if let Some(redir) = self.parse_redir()? {
+                    if redir_count == MAX_REDIRS {
+                        eprintln!("sh: too many redirections");
+                        return None;
+                    }
+                    redirs[redir_count] = Some(redir);
+                    redir_count += 1;
+                } else {
+                    break;
+                }
+            }
+            let len = self.word_count - start;
+            if len == 0 && redir_count == 0 {
+                eprintln!("sh: syntax error");
+                return None;
+            }
+            self.add(Cmd::Exec { start, len })?
+        };
+        // 后出现的重定向在内层，最后执行，因此同一描述符以最后一个为准
+        let mut cmd = cmd;
+        for redir in redirs[..redir_count].iter().rev().flatten() {
+            cmd = self.add(Cmd::Redir { cmd, redir: *redir })?;
+        }
+        Some(cmd)
+    }
+
+    // 下一个记号不是重定向时返回 Some(None)
+    fn parse_redir(&mut self) -> Option<Option<Redir<'a>>> {
+        let (flags, fd) = match self.peek() {
+            Token::Less => (O_RDONLY, STDIN),
+            Token::Great => (O_WRONLY | O_CREATE | O_TRUNC, STDOUT),
+            Token::DoubleGreat => (O_WRONLY | O_CREATE | O_APPEND, STDOUT),
+            _ => return Some(None),
+        };
+        self.next();
+        match self.next() {
+            Token::Word(file) => Some(Some(Redir { file, flags, fd })),
+            _ => {
+                eprintln!("sh: missing file for redirection");
+                None
+            }
+        }
+    }
+}
+
+fn fork1() -> isize {
+    let pid = fork();
+    if pid < 0 {
+        eprintln!("sh: fork failed");
+    }
+    pid
+}
+
+fn wait_for(pid: isize) -> i32 {
+    let mut status = 0;
+    if waitpid(pid, &mut status, 0) < 0 {
+        return -1;
+    }
+    status
+}
+
+fn quit(status: i32) -> ! {
+    exit(status);
+    unreachable!("sh: exit returned");
+}
+
+fn cd(dir: &str) -> i32 {
+    if chdir(dir) < 0 {
+        eprintln!("sh: cannot cd {}", dir);
+        return 1;
+    }
+    0
+}
+
+// cd 与 exit 需要在 shell 进程自身中执行，不是内建命令时返回 None
+fn builtin(argv: &[&str], last_status: i32) -> Option<i32> {
+    let status = match argv {
+        ["cd"] => cd("/"),
+        ["cd", dir] => cd(dir),
+        ["cd", ..] => {
+            eprintln!("usage: cd [dir]");
+            1
+        }
+        ["exit"] => quit(last_status),
+        ["exit", code] => match code.parse::<i32>() {
+            Ok(code) => quit(code),
+            Err(_) => {
+                eprintln!("sh: exit: {}: numeric argument required", code);
+                2
+            }
+        },
+        ["exit", ..] => {
+            eprintln!("usage: exit [status]");
+            1
+        }
+        _ => return None,
+    };
+    Some(status)
+}
+
+// 在子进程中执行 cmd，不会返回
+fn run(parser: &Parser, cmd: usize) -> ! {
+    match parser.node(cmd) {
+        Cmd::Exec { start, len } => {
+            let argv = parser.argv(start, len);
+            if len == 0 {
+                quit(0);
+            }
+            if let Some(status) = builtin(argv, 0) {
+                quit(status);
+            }
+            exec(argv[0], argv);
+            eprintln!("sh: {}: command not found", argv[0]);
+            quit(127);
+        }
+        Cmd::Redir { cmd, redir } => {
+            close(redir.fd);
+            if open(redir.file, redir.flags) < 0 {
+                eprintln!("sh: cannot open {}", redir.file);
+                quit(1);
+            }
+            run(parser, cmd);
+        }
+        Cmd::List { left, right } => {
+            let pid = fork1();
+            if pid == 0 {
+                run(parser, left);
+            }
+            if pid > 0 {
+                wait_for(pid);
+            }
+            run(parser, right);
+        }
+        Cmd::Pipe { left, right } => {
+            let mut fds = [0u32; 2];
+            if pipe(&mut fds) < 0 {
+                eprintln!("sh: pipe failed");
+                quit(1);
+            }
+            let (read_end, write_end) = (fds[0] as usize, fds[1] as usize);
+            let left_pid = fork1();
+            if left_pid == 0 {
+                close(STDOUT);
+                dup(write_end);
+                close(read_end);
+                close(write_end);
+                run(parser, left);
+            }
+            let right_pid = fork1();
+            if right_pid == 0 {
+                close(STDIN);
+                dup(read_end);
+                close(read_end);
+                close(write_end);
+                run(parser, right);
+            }
+            close(read_end);
+            close(write_end);
+            if left_pid > 0 {
+                wait_for(left_pid);
+            }
+            // 管道的退出状态取最后一个命令的
+            let status = if right_pid > 0 {
+                wait_for(right_pid)
+            } else {
+                1
+            };
+            quit(status);
+        }
+        Cmd::Back { cmd } => {
+            if fork1() == 0 {
+                run(parser, cmd);
+            }
+            quit(0);
+        }
+    }
+}
+
+// 从标准输入读取行，一次读到的多余内容留给下一行
+struct Input {
+    buf: [u8; LINE_MAX],
+    start: usize,
+    end: usize,
+}
+
+impl Input {
+    fn new() -> Self {
+        Self {
+            buf: [0; LINE_MAX],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    // 返回行的长度，不含换行符，过长的行被截断，输入结束时返回 None
+    fn read_line(&mut self, line: &mut [u8]) -> Option<usize> {
+        let mut len = 0;
+        loop {
+            if self.start == self.end {
+                let n = read(STDIN, &mut self.buf);
+                if n <= 0 {
+                    return if len > 0 { Some(len) } else { None };
+                }
+                self.start = 0;
+                self.end = n as usize;
+            }
+            let c = self.buf[self.start];
+            self.start += 1;
+            if c == b'\n' {
+                return Some(len);
+            }
+            if len < line.len() {
+                line[len] = c;
+                len += 1;
+            }
+        }
+    }
+}
+
+struct Shell {
+    last_status: i32,
+}
+
+impl Shell {
+    // 在 shell 进程中执行一行命令：列表逐项执行，后台任务不等待，内建命令不创建进程
+    fn run_line(&mut self, parser: &Parser, cmd: usize) {
+        match parser.node(cmd) {
+            Cmd::List { left, right } => {
+                self.run_line(parser, left);
+                self.run_line(parser, right);
+            }
+            Cmd::Back { cmd } => {
+                let pid = fork1();
+                if pid == 0 {
+                    run(parser, cmd);
+                }
+                if pid > 0 {
+                    println!("[{}]", pid);
+                }
+            }
+            Cmd::Exec { start, len } => match builtin(parser.argv(start, len), self.last_status) {
+                Some(status) => self.last_status = status,
+                None => self.run_foreground(parser, cmd),
+            },
+            _ => self.run_foreground(parser, cmd),
+        }
+    }
+
+    fn run_foreground(&mut self, parser: &Parser, cmd: usize) {
+        let pid = fork1();
+        if pid == 0 {
+            run(parser, cmd);
+        }
+        if pid < 0 {
+            self.last_status = 1;
+            return;
+        }
+        let status = wait_for(pid);
+        if status != 0 {
+            eprintln!("sh: exit status {}", status);
+        }
+        self.last_status = status;
+    }
+
+    // 回收已结束的后台任务
+    fn reap_jobs(&self) {
+        let mut status = 0;
+        loop {
+            let pid = waitpid(-1, &mut status, WNOHANG);
+            if pid <= 0 {
+                break;
+            }
+            println!("[{}] done, exit status {}", pid, status);
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    let mut input = Input::new();
+    let mut line = [0u8; LINE_MAX];
+    let mut shell = Shell { last_status: 0 };
+    loop {
+        shell.reap_jobs();
+        print!("$ ");
+        let len = match input.read_line(&mut line) {
+            Some(len) => len,
+            None => break,
+        };
+        let text = match core::str::from_utf8(&line[..len]) {
+            Ok(text) => text,
+            Err(_) => {
+                eprintln!("sh: invalid input");
+                continue;
+            }
+        };
+        let mut parser = Parser::new(text);
+        match parser.parse() {
+            Some(Some(cmd)) => shell.run_line(&parser, cmd),
+            Some(None) => {}
+            None => shell.last_status = 2,
+        }
+    }
+    shell.last_status
+}
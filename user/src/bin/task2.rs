@@ -5,7 +5,7 @@
 extern crate user;
 
 #[no_mangle]
-pub fn main() -> usize {
+pub fn main(_argc: usize, _argv: *const *const u8) -> usize {
     for i in 0..5 {
         for _ in 0..10 {
             print!("B");
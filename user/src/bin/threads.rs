@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::sync::Mutex;
+use user::thread;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 1000;
+
+static COUNTER: Mutex<usize> = Mutex::new(0);
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    let mut handles = [(); THREADS].map(|_| None);
+    for (i, handle) in handles.iter_mut().enumerate() {
+        *handle = Some(thread::spawn(move || {
+            for _ in 0..ROUNDS {
+                *COUNTER.lock() += 1;
+            }
+            i as i32
+        }));
+    }
+    for (i, handle) in handles.into_iter().enumerate() {
+        let exit_code = handle.unwrap().join();
+        assert_eq!(exit_code, i as i32);
+    }
+    assert_eq!(*COUNTER.lock(), THREADS * ROUNDS);
+    println!("threads OK!");
+    0
+}
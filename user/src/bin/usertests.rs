@@ -0,0 +1,1082 @@
+//! Regression tests of the system call interface, after xv6's usertests
+//!
+//! Every test runs in a child process, so a test that crashes or is killed
+//! only fails itself. A test passes when its process exits with 0. When all
+//! tests have run the machine is powered off, with a nonzero exit code if
+//! any of them failed, so the host sees the result. `usertests name` runs
+//! only the test called `name`.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use core::fmt::{self, Write};
+
+use user::syscall::{
+    syscall, SYS_CHDIR, SYS_EXEC, SYS_FSTAT, SYS_FUTEX_WAIT, SYS_LINK, SYS_MKDIR, SYS_OPEN,
+    SYS_PIPE, SYS_READ, SYS_UNLINK, SYS_WAIT, SYS_WRITE,
+};
+use user::{
+    chdir, close, dup, env, exec, exit, fork, fstat, kill, link, mkdir, open, pipe, read, sbrk,
+    shutdown, sleep, stat, unlink, uptime, wait, waitpid, write, Dirent, Stat, O_APPEND, O_CREATE,
+    O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, T_DEVICE, T_DIR, T_FILE,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+// 用户不可访问的地址：内核、trampoline 与地址空间的最高一页
+const BAD_ADDRS: [usize; 3] = [
+    0x8020_0000,
+    (1 << 38) - PAGE_SIZE,
+    usize::MAX - PAGE_SIZE + 1,
+];
+
+struct Test {
+    name: &'static str,
+    func: fn(),
+}
+
+const TESTS: &[Test] = &[
+    Test {
+        name: "exitwait",
+        func: exitwait,
+    },
+    Test {
+        name: "forktest",
+        func: forktest,
+    },
+    Test {
+        name: "reparent",
+        func: reparent,
+    },
+    Test {
+        name: "killtest",
+        func: killtest,
+    },
+    Test {
+        name: "sbrkbasic",
+        func: sbrkbasic,
+    },
+    Test {
+        name: "sbrkfork",
+        func: sbrkfork,
+    },
+    Test {
+        name: "sbrkunmap",
+        func: sbrkunmap,
+    },
+    Test {
+        name: "outofmemory",
+        func: outofmemory,
+    },
+    Test {
+        name: "pipe1",
+        func: pipe1,
+    },
+    Test {
+        name: "pipeclose",
+        func: pipeclose,
+    },
+    Test {
+        name: "filetest",
+        func: filetest,
+    },
+    Test {
+        name: "linktest",
+        func: linktest,
+    },
+    Test {
+        name: "dirtest",
+        func: dirtest,
+    },
+    Test {
+        name: "bigfile",
+        func: bigfile,
+    },
+    Test {
+        name: "sharedfd",
+        func: sharedfd,
+    },
+    Test {
+        name: "createdelete",
+        func: createdelete,
+    },
+    Test {
+        name: "concurrent",
+        func: concurrent,
+    },
+    Test {
+        name: "exectest",
+        func: exectest,
+    },
+    Test {
+        name: "badptr",
+        func: badptr,
+    },
+    Test {
+        name: "badaccess",
+        func: badaccess,
+    },
+    Test {
+        name: "badjump",
+        func: badjump,
+    },
+    Test {
+        name: "sleeptest",
+        func: sleeptest,
+    },
+];
+
+// 测试失败时结束测试进程
+fn fail() -> ! {
+    exit(1);
+    unreachable!();
+}
+
+macro_rules! check {
+    ($cond: expr, $($msg: tt)+) => {
+        if !$cond {
+            eprintln!($($msg)+);
+            fail();
+        }
+    };
+}
+
+// 在栈上格式化文件名
+struct Name {
+    buf: [u8; 32],
+    len: usize,
+}
+
+impl Name {
+    fn new(args: fmt::Arguments) -> Self {
+        let mut name = Self {
+            buf: [0; 32],
+            len: 0,
+        };
+        name.write_fmt(args).unwrap();
+        name
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap()
+    }
+}
+
+impl Write for Name {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.buf.len() {
+            return Err(fmt::Error);
+        }
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+macro_rules! name {
+    ($($arg: tt)+) => {
+        Name::new(format_args!($($arg)+))
+    };
+}
+
+// 等待子进程 pid 并返回其退出码
+fn wait_for(pid: isize) -> i32 {
+    let mut status = 0;
+    check!(
+        waitpid(pid, &mut status, 0) == pid,
+        "waitpid {} failed",
+        pid
+    );
+    status
+}
+
+fn fork_checked() -> isize {
+    let pid = fork();
+    check!(pid >= 0, "fork failed");
+    pid
+}
+
+fn open_checked(path: &str, flags: u32) -> usize {
+    let fd = open(path, flags);
+    check!(fd >= 0, "cannot open {}", path);
+    fd as usize
+}
+
+fn pipe_checked() -> (usize, usize) {
+    let mut fds = [0u32; 2];
+    check!(pipe(&mut fds) == 0, "pipe failed");
+    (fds[0] as usize, fds[1] as usize)
+}
+
+// 读到文件末尾或 buf 填满，返回读到的字节数
+fn read_all(fd: usize, buf: &mut [u8]) -> usize {
+    let mut total = 0;
+    while total < buf.len() {
+        let n = read(fd, &mut buf[total..]);
+        check!(n >= 0, "read failed");
+        if n == 0 {
+            break;
+        }
+        total += n as usize;
+    }
+    total
+}
+
+fn stat_checked(path: &str) -> Stat {
+    let mut st = Stat::default();
+    check!(stat(path, &mut st) == 0, "cannot stat {}", path);
+    st
+}
+
+// 子进程的退出码依次交给 wait
+fn exitwait() {
+    for i in 0..50 {
+        let pid = fork_checked();
+        if pid == 0 {
+            exit(i);
+            fail();
+        }
+        let mut status = 0;
+        check!(wait(&mut status) == pid, "wait returned the wrong pid");
+        check!(status == i, "exit status {} instead of {}", status, i);
+    }
+
+    const CHILDREN: usize = 8;
+    let mut pids = [0isize; CHILDREN];
+    for (i, pid) in pids.iter_mut().enumerate() {
+        *pid = fork_checked();
+        if *pid == 0 {
+            exit(i as i32 + 100);
+            fail();
+        }
+    }
+    for _ in 0..CHILDREN {
+        let mut status = 0;
+        let pid = wait(&mut status);
+        let i = pids.iter().position(|&p| p == pid);
+        check!(i.is_some(), "wait returned unknown pid {}", pid);
+        check!(
+            status == i.unwrap() as i32 + 100,
+            "child {} exited with {}",
+            pid,
+            status
+        );
+    }
+    let mut status = 0;
+    check!(wait(&mut status) == -1, "wait succeeded without children");
+    check!(
+        waitpid(-1, &mut status, user::WNOHANG) == -1,
+        "WNOHANG succeeded without children"
+    );
+}
+
+// fork 到进程数的上限时失败，且不泄漏任何东西
+fn forktest() {
+    const N: usize = 100;
+    let mut n = 0;
+    while n < N {
+        let pid = fork();
+        if pid < 0 {
+            break;
+        }
+        if pid == 0 {
+            exit(0);
+            fail();
+        }
+        n += 1;
+    }
+    check!(n < N, "fork never ran out of processes");
+    check!(n > 0, "no fork succeeded");
+    let mut status = 0;
+    for _ in 0..n {
+        check!(wait(&mut status) >= 0, "wait stopped early");
+    }
+    check!(wait(&mut status) == -1, "wait got too many children");
+    // 进程槽已全部归还
+    for _ in 0..2 {
+        let mut pids = [0isize; 8];
+        for pid in pids.iter_mut().take(n.min(8)) {
+            *pid = fork_checked();
+            if *pid == 0 {
+                exit(0);
+                fail();
+            }
+        }
+        for &pid in pids.iter().take(n.min(8)) {
+            wait_for(pid);
+        }
+    }
+}
+
+// 父进程先结束的孙进程由 init 回收，否则进程槽很快耗尽
+fn reparent() {
+    for _ in 0..100 {
+        let pid = fork_checked();
+        if pid == 0 {
+            let grandchild = fork();
+            if grandchild < 0 {
+                exit(1);
+            }
+            exit(0);
+            fail();
+        }
+        check!(wait_for(pid) == 0, "no process left for a grandchild");
+    }
+}
+
+fn killtest() {
+    let pid = fork_checked();
+    if pid == 0 {
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+    sleep(20);
+    check!(kill(pid as usize) == 0, "kill failed");
+    check!(wait_for(pid) == -1, "killed process did not exit with -1");
+    check!(kill(pid as usize) == -1, "killed a reaped process");
+
+    // 睡眠在空管道上的进程也会被唤醒并结束
+    let (rfd, wfd) = pipe_checked();
+    let pid = fork_checked();
+    if pid == 0 {
+        let mut buf = [0u8; 1];
+        read(rfd, &mut buf);
+        exit(0);
+        fail();
+    }
+    sleep(20);
+    check!(kill(pid as usize) == 0, "kill failed");
+    check!(
+        wait_for(pid) == -1,
+        "process blocked on a pipe survived kill"
+    );
+    close(rfd);
+    close(wfd);
+}
+
+fn heap(start: isize, len: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(start as *mut u8, len) }
+}
+
+fn sbrkbasic() {
+    let start = sbrk(0);
+    check!(start > 0, "sbrk(0) failed");
+    check!(
+        sbrk((4 * PAGE_SIZE) as isize) == start,
+        "sbrk did not return the old break"
+    );
+    check!(
+        sbrk(0) == start + (4 * PAGE_SIZE) as isize,
+        "break did not move"
+    );
+    let mem = heap(start, 4 * PAGE_SIZE);
+    check!(mem.iter().all(|&b| b == 0), "new heap memory is not zeroed");
+    mem.fill(0xab);
+
+    // 收缩后再增长得到的是新的清零页面
+    check!(
+        sbrk(-((4 * PAGE_SIZE) as isize)) == start + (4 * PAGE_SIZE) as isize,
+        "shrinking failed"
+    );
+    check!(sbrk(PAGE_SIZE as isize) == start, "growing again failed");
+    check!(
+        heap(start, PAGE_SIZE).iter().all(|&b| b == 0),
+        "reused heap memory is not zeroed"
+    );
+
+    // 不按页对齐的增长
+    for _ in 0..PAGE_SIZE / 512 * 3 {
+        let old = sbrk(512);
+        check!(old > 0, "small sbrk failed");
+        heap(old, 512).fill(1);
+    }
+    let end = sbrk(0);
+    check!(sbrk(isize::MAX) == -1, "sbrk of isize::MAX succeeded");
+    check!(
+        sbrk(start - end - 1) == -1,
+        "shrank below the start of the heap"
+    );
+    check!(sbrk(0) == end, "failed sbrk moved the break");
+    check!(sbrk(start - end) == end, "shrinking to the start failed");
+    check!(sbrk(0) == start, "heap not empty");
+}
+
+// fork 复制堆，父子进程互不影响
+fn sbrkfork() {
+    let start = sbrk((2 * PAGE_SIZE) as isize);
+    check!(start > 0, "sbrk failed");
+    heap(start, 2 * PAGE_SIZE).fill(1);
+    let pid = fork_checked();
+    if pid == 0 {
+        let mem = heap(start, 2 * PAGE_SIZE);
+        check!(mem.iter().all(|&b| b == 1), "child sees a different heap");
+        mem.fill(2);
+        check!(sbrk(PAGE_SIZE as isize) > 0, "child cannot grow its heap");
+        exit(0);
+        fail();
+    }
+    check!(wait_for(pid) == 0, "child failed");
+    check!(
+        heap(start, 2 * PAGE_SIZE).iter().all(|&b| b == 1),
+        "child changed the heap of its parent"
+    );
+    check!(
+        sbrk(0) == start + (2 * PAGE_SIZE) as isize,
+        "child moved the break of its parent"
+    );
+}
+
+// 释放的堆内存不能再访问
+fn sbrkunmap() {
+    let start = sbrk(PAGE_SIZE as isize);
+    check!(start > 0, "sbrk failed");
+    check!(
+        sbrk(-(PAGE_SIZE as isize)) == start + PAGE_SIZE as isize,
+        "shrinking failed"
+    );
+    let pid = fork_checked();
+    if pid == 0 {
+        let value = unsafe { (start as *const u8).read_volatile() };
+        eprintln!("read {} from freed memory", value);
+        exit(0);
+        fail();
+    }
+    check!(wait_for(pid) == -1, "freed memory is still mapped");
+}
+
+// 内存耗尽时 sbrk 失败而不是让内核崩溃，进程结束后内存全部归还
+fn outofmemory() {
+    const CHUNK: usize = 64 * 1024;
+    for _ in 0..2 {
+        let pid = fork_checked();
+        if pid == 0 {
+            let mut total = 0usize;
+            loop {
+                let old = sbrk(CHUNK as isize);
+                if old == -1 {
+                    break;
+                }
+                unsafe { ((old as usize + CHUNK - 1) as *mut u8).write_volatile(1) };
+                total += CHUNK;
+            }
+            check!(total > 0, "no memory at all");
+            let end = sbrk(0);
+            let last = unsafe { ((end - 1) as *const u8).read_volatile() };
+            check!(last == 1, "heap damaged after failing sbrk");
+            exit(0);
+            fail();
+        }
+        check!(wait_for(pid) == 0, "child failed");
+    }
+    let old = sbrk(PAGE_SIZE as isize);
+    check!(old > 0, "memory not returned");
+    sbrk(-(PAGE_SIZE as isize));
+}
+
+fn pipe1() {
+    const N: usize = 5;
+    const SIZE: usize = 1033;
+    let (rfd, wfd) = pipe_checked();
+    let pid = fork_checked();
+    if pid == 0 {
+        close(rfd);
+        let mut seq = 0u8;
+        let mut buf = [0u8; SIZE];
+        for _ in 0..N {
+            for b in buf.iter_mut() {
+                *b = seq;
+                seq = seq.wrapping_add(1);
+            }
+            check!(write(wfd, &buf) == SIZE as isize, "pipe write failed");
+        }
+        exit(0);
+        fail();
+    }
+    close(wfd);
+    let mut buf = [0u8; SIZE];
+    let mut seq = 0u8;
+    let mut total = 0;
+    let mut want = 1;
+    loop {
+        let n = read(rfd, &mut buf[..want]);
+        check!(n >= 0, "pipe read failed");
+        if n == 0 {
+            break;
+        }
+        for &b in &buf[..n as usize] {
+            check!(b == seq, "pipe delivered the wrong byte");
+            seq = seq.wrapping_add(1);
+        }
+        total += n as usize;
+        want = (want * 2).min(SIZE);
+    }
+    check!(total == N * SIZE, "pipe delivered {} bytes", total);
+    close(rfd);
+    check!(wait_for(pid) == 0, "writer failed");
+}
+
+fn pipeclose() {
+    // 写端关闭后读完剩余的数据即为文件末尾
+    let (rfd, wfd) = pipe_checked();
+    check!(write(wfd, b"abc") == 3, "pipe write failed");
+    close(wfd);
+    let mut buf = [0u8; 8];
+    check!(read(rfd, &mut buf) == 3, "data lost on close");
+    check!(read(rfd, &mut buf) == 0, "no end of file");
+    close(rfd);
+
+    // 读端关闭后写入失败
+    let (rfd, wfd) = pipe_checked();
+    close(rfd);
+    check!(write(wfd, b"abc") == -1, "wrote to a pipe without readers");
+    close(wfd);
+
+    // 另一个进程持有写端时读者等待它关闭
+    let (rfd, wfd) = pipe_checked();
+    let pid = fork_checked();
+    if pid == 0 {
+        close(rfd);
+        sleep(10);
+        write(wfd, b"x");
+        exit(0);
+        fail();
+    }
+    close(wfd);
+    check!(read_all(rfd, &mut buf) == 1, "reader did not wait");
+    close(rfd);
+    check!(wait_for(pid) == 0, "writer failed");
+
+    // 描述符用尽时 pipe 失败
+    let mut fds = [0usize; 16];
+    let mut count = 0;
+    loop {
+        let mut pair = [0u32; 2];
+        if pipe(&mut pair) < 0 {
+            break;
+        }
+        check!(count + 2 <= fds.len(), "descriptors never ran out");
+        fds[count] = pair[0] as usize;
+        fds[count + 1] = pair[1] as usize;
+        count += 2;
+    }
+    for &fd in &fds[..count] {
+        close(fd);
+    }
+    check!(close(fds[0]) == -1, "closed a descriptor twice");
+}
+
+fn filetest() {
+    let fd = open_checked("ut_file", O_CREATE | O_RDWR);
+    check!(write(fd, b"hello, world") == 12, "write failed");
+    close(fd);
+
+    let fd = open_checked("ut_file", O_RDONLY);
+    let mut buf = [0u8; 64];
+    check!(read_all(fd, &mut buf) == 12, "read back the wrong size");
+    check!(&buf[..12] == b"hello, world", "read back the wrong data");
+    check!(
+        write(fd, b"x") == -1,
+        "wrote through a read-only descriptor"
+    );
+    let mut st = Stat::default();
+    check!(fstat(fd, &mut st) == 0, "fstat failed");
+    check!(
+        st.kind == T_FILE && st.size == 12 && st.nlink == 1,
+        "wrong status"
+    );
+    close(fd);
+
+    let fd = open_checked("ut_file", O_WRONLY);
+    check!(
+        read(fd, &mut buf) == -1,
+        "read through a write-only descriptor"
+    );
+    close(fd);
+
+    let fd = open_checked("ut_file", O_WRONLY | O_TRUNC);
+    close(fd);
+    check!(stat_checked("ut_file").size == 0, "O_TRUNC kept data");
+
+    for _ in 0..2 {
+        let fd = open_checked("ut_file", O_WRONLY | O_APPEND);
+        check!(write(fd, b"ab") == 2, "append failed");
+        close(fd);
+    }
+    check!(stat_checked("ut_file").size == 4, "O_APPEND overwrote data");
+
+    check!(unlink("ut_file") == 0, "unlink failed");
+    check!(open("ut_file", O_RDONLY) < 0, "opened an unlinked file");
+    check!(unlink("ut_file") < 0, "unlinked a file twice");
+
+    // 已打开的文件删除后仍可读写，关闭后才释放
+    let wfd = open_checked("ut_file", O_CREATE | O_WRONLY);
+    let rfd = open_checked("ut_file", O_RDONLY);
+    check!(unlink("ut_file") == 0, "unlink failed");
+    check!(write(wfd, b"abc") == 3, "write to an unlinked file failed");
+    check!(
+        read_all(rfd, &mut buf) == 3,
+        "read of an unlinked file failed"
+    );
+    close(wfd);
+    close(rfd);
+}
+
+fn linktest() {
+    let fd = open_checked("ut_l1", O_CREATE | O_WRONLY);
+    check!(write(fd, b"hello") == 5, "write failed");
+    close(fd);
+
+    check!(link("ut_l1", "ut_l2") == 0, "link failed");
+    check!(stat_checked("ut_l2").nlink == 2, "nlink is not 2");
+    check!(unlink("ut_l1") == 0, "unlink failed");
+    check!(open("ut_l1", O_RDONLY) < 0, "opened an unlinked name");
+
+    let fd = open_checked("ut_l2", O_RDONLY);
+    let mut buf = [0u8; 16];
+    check!(read_all(fd, &mut buf) == 5, "data lost after unlink");
+    check!(&buf[..5] == b"hello", "wrong data after unlink");
+    close(fd);
+    check!(stat_checked("ut_l2").nlink == 1, "nlink is not 1");
+
+    check!(link("ut_l2", "ut_l2") < 0, "linked onto an existing name");
+    check!(link("ut_l1", "ut_l3") < 0, "linked a missing file");
+    check!(link(".", "ut_l3") < 0, "linked a directory");
+    check!(
+        link("ut_l2", "ut_nodir/x") < 0,
+        "linked into a missing directory"
+    );
+    check!(unlink("ut_l2") == 0, "unlink failed");
+}
+
+fn dirtest() {
+    check!(mkdir("ut_dir") == 0, "mkdir failed");
+    check!(mkdir("ut_dir") < 0, "mkdir of an existing name");
+    check!(stat_checked("ut_dir").kind == T_DIR, "not a directory");
+    check!(chdir("ut_dir") == 0, "chdir failed");
+    check!(unlink("../ut_dir") < 0, "removed the working directory");
+    let fd = open_checked("file", O_CREATE | O_WRONLY);
+    close(fd);
+    check!(chdir("..") == 0, "chdir .. failed");
+    let fd = open_checked("ut_dir/file", O_RDONLY);
+    close(fd);
+    check!(open("ut_dir", O_RDWR) < 0, "opened a directory for writing");
+    check!(unlink("ut_dir/.") < 0, "unlinked .");
+    check!(unlink("ut_dir/..") < 0, "unlinked ..");
+    check!(
+        unlink("ut_dir") < 0,
+        "removed a directory that is not empty"
+    );
+    check!(mkdir("ut_dir/file/x") < 0, "mkdir under a file");
+
+    // 目录读出的是 Dirent
+    let fd = open_checked("ut_dir", O_RDONLY);
+    let (mut dot, mut dotdot, mut file) = (false, false, false);
+    while let Some(entry) = Dirent::read(fd) {
+        match entry.name() {
+            "." => dot = true,
+            ".." => dotdot = true,
+            "file" => file = true,
+            name => check!(false, "unexpected entry {}", name),
+        }
+    }
+    close(fd);
+    check!(dot && dotdot && file, "entries missing");
+
+    check!(unlink("ut_dir/file") == 0, "unlink failed");
+    check!(unlink("ut_dir") == 0, "removing an empty directory failed");
+    check!(chdir("ut_dir") < 0, "chdir into a removed directory");
+
+    check!(
+        mkdir("a_name_much_too_long") < 0,
+        "created a name longer than DIRSIZ"
+    );
+    check!(
+        stat_checked("/dev/console").kind == T_DEVICE,
+        "console is not a device"
+    );
+    check!(stat_checked("/").kind == T_DIR, "/ is not a directory");
+}
+
+// 写到文件大小的上限时失败，已写入的数据完好
+fn bigfile() {
+    const LIMIT: usize = 16 * 1024 * 1024;
+    let fd = open_checked("ut_big", O_CREATE | O_TRUNC | O_WRONLY);
+    let mut buf = [0u8; 512];
+    let mut total = 0;
+    loop {
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = ((total + i) % 251) as u8;
+        }
+        let n = write(fd, &buf);
+        if n < 0 {
+            break;
+        }
+        check!(n > 0, "write returned 0");
+        total += n as usize;
+        check!(total <= LIMIT, "file never filled up");
+    }
+    close(fd);
+    check!(total > 0, "nothing written");
+    check!(
+        stat_checked("ut_big").size as usize == total,
+        "size does not match"
+    );
+
+    let fd = open_checked("ut_big", O_RDONLY);
+    let mut offset = 0;
+    loop {
+        let n = read(fd, &mut buf);
+        check!(n >= 0, "read failed");
+        if n == 0 {
+            break;
+        }
+        for &b in &buf[..n as usize] {
+            check!(b == (offset % 251) as u8, "wrong data at {}", offset);
+            offset += 1;
+        }
+    }
+    close(fd);
+    check!(offset == total, "read {} of {} bytes", offset, total);
+    check!(unlink("ut_big") == 0, "unlink failed");
+}
+
+// fork 后父子进程共享文件偏移，写入不会互相覆盖
+fn sharedfd() {
+    const N: usize = 100;
+    const SIZE: usize = 10;
+    unlink("ut_shared");
+    let fd = open_checked("ut_shared", O_CREATE | O_RDWR);
+    let pid = fork_checked();
+    let buf = [if pid == 0 { b'c' } else { b'p' }; SIZE];
+    for _ in 0..N {
+        check!(write(fd, &buf) == SIZE as isize, "write failed");
+    }
+    if pid == 0 {
+        exit(0);
+        fail();
+    }
+    check!(wait_for(pid) == 0, "child failed");
+    close(fd);
+
+    let fd = open_checked("ut_shared", O_RDONLY);
+    let (mut children, mut parents) = (0, 0);
+    let mut buf = [0u8; 64];
+    loop {
+        let n = read(fd, &mut buf);
+        check!(n >= 0, "read failed");
+        if n == 0 {
+            break;
+        }
+        for &b in &buf[..n as usize] {
+            match b {
+                b'c' => children += 1,
+                b'p' => parents += 1,
+                _ => check!(false, "unexpected byte {}", b),
+            }
+        }
+    }
+    close(fd);
+    check!(
+        children == N * SIZE && parents == N * SIZE,
+        "{} from the child and {} from the parent",
+        children,
+        parents
+    );
+    check!(unlink("ut_shared") == 0, "unlink failed");
+}
+
+// 多个进程同时在同一目录中创建与删除文件
+fn createdelete() {
+    const PROCS: usize = 4;
+    const N: usize = 20;
+    let mut pids = [0isize; PROCS];
+    for (p, pid) in pids.iter_mut().enumerate() {
+        *pid = fork_checked();
+        if *pid != 0 {
+            continue;
+        }
+        for i in 0..N {
+            let name = name!("ut_cd{}_{}", p, i);
+            let fd = open_checked(name.as_str(), O_CREATE | O_RDWR);
+            close(fd);
+            if i % 2 == 0 {
+                check!(
+                    unlink(name.as_str()) == 0,
+                    "unlink {} failed",
+                    name.as_str()
+                );
+            }
+        }
+        exit(0);
+        fail();
+    }
+    for pid in pids {
+        check!(wait_for(pid) == 0, "child failed");
+    }
+    for p in 0..PROCS {
+        for i in 0..N {
+            let name = name!("ut_cd{}_{}", p, i);
+            let fd = open(name.as_str(), O_RDONLY);
+            if i % 2 == 0 {
+                check!(fd < 0, "{} was not deleted", name.as_str());
+            } else {
+                check!(fd >= 0, "{} is missing", name.as_str());
+                close(fd as usize);
+                check!(unlink(name.as_str()) == 0, "unlink failed");
+            }
+        }
+    }
+}
+
+// 多个进程同时写各自的文件和同一个管道
+fn concurrent() {
+    const PROCS: usize = 4;
+    const N: usize = 12;
+    const SIZE: usize = 500;
+    let (rfd, wfd) = pipe_checked();
+    let mut pids = [0isize; PROCS];
+    for (p, pid) in pids.iter_mut().enumerate() {
+        *pid = fork_checked();
+        if *pid != 0 {
+            continue;
+        }
+        close(rfd);
+        let name = name!("ut_cc{}", p);
+        let fd = open_checked(name.as_str(), O_CREATE | O_TRUNC | O_WRONLY);
+        let buf = [b'0' + p as u8; SIZE];
+        for _ in 0..N {
+            check!(write(fd, &buf) == SIZE as isize, "file write failed");
+            check!(write(wfd, &buf[..100]) == 100, "pipe write failed");
+        }
+        close(fd);
+        exit(0);
+        fail();
+    }
+    close(wfd);
+    let mut counts = [0usize; PROCS];
+    let mut buf = [0u8; 256];
+    loop {
+        let n = read(rfd, &mut buf);
+        check!(n >= 0, "pipe read failed");
+        if n == 0 {
+            break;
+        }
+        for &b in &buf[..n as usize] {
+            let p = b.wrapping_sub(b'0') as usize;
+            check!(p < PROCS, "unexpected byte {} in the pipe", b);
+            counts[p] += 1;
+        }
+    }
+    close(rfd);
+    for pid in pids {
+        check!(wait_for(pid) == 0, "child failed");
+    }
+    for (p, &count) in counts.iter().enumerate() {
+        check!(count == N * 100, "{} bytes from child {}", count, p);
+        let name = name!("ut_cc{}", p);
+        let fd = open_checked(name.as_str(), O_RDONLY);
+        let mut total = 0;
+        loop {
+            let n = read(fd, &mut buf);
+            check!(n >= 0, "file read failed");
+            if n == 0 {
+                break;
+            }
+            check!(
+                buf[..n as usize].iter().all(|&b| b == b'0' + p as u8),
+                "{} holds data of another process",
+                name.as_str()
+            );
+            total += n as usize;
+        }
+        close(fd);
+        check!(total == N * SIZE, "{} has {} bytes", name.as_str(), total);
+        check!(unlink(name.as_str()) == 0, "unlink failed");
+    }
+}
+
+// exec 传递参数，且保留描述符
+fn exectest() {
+    check!(
+        exec("ut_no_such_program", &["ut_no_such_program"]) == -1,
+        "exec of a missing program"
+    );
+    let (rfd, wfd) = pipe_checked();
+    let pid = fork_checked();
+    if pid == 0 {
+        close(1);
+        check!(dup(wfd) == 1, "dup did not use the lowest descriptor");
+        close(rfd);
+        close(wfd);
+        exec("echo", &["echo", "hello", "usertests"]);
+        eprintln!("exec echo failed");
+        fail();
+    }
+    close(wfd);
+    let mut buf = [0u8; 64];
+    let n = read_all(rfd, &mut buf);
+    close(rfd);
+    check!(
+        &buf[..n] == b"hello usertests\n",
+        "echo printed the wrong output"
+    );
+    check!(wait_for(pid) == 0, "echo failed");
+}
+
+// 传给系统调用的非法指针只会让调用失败
+fn badptr() {
+    let (rfd, wfd) = pipe_checked();
+    check!(write(wfd, &[0u8; 64]) == 64, "pipe write failed");
+    let dir = open_checked(".", O_RDONLY);
+    for addr in BAD_ADDRS {
+        let calls = [
+            ("write", syscall(SYS_WRITE, [wfd, addr, 10])),
+            ("read", syscall(SYS_READ, [rfd, addr, 1])),
+            ("open", syscall(SYS_OPEN, [addr, O_RDONLY as usize, 0])),
+            ("pipe", syscall(SYS_PIPE, [addr, 0, 0])),
+            ("fstat", syscall(SYS_FSTAT, [dir, addr, 0])),
+            ("exec", syscall(SYS_EXEC, [addr, 0, 0])),
+            ("mkdir", syscall(SYS_MKDIR, [addr, 0, 0])),
+            ("unlink", syscall(SYS_UNLINK, [addr, 0, 0])),
+            ("chdir", syscall(SYS_CHDIR, [addr, 0, 0])),
+            ("link", syscall(SYS_LINK, [addr, addr, 0])),
+            ("futex_wait", syscall(SYS_FUTEX_WAIT, [addr, 0, 0])),
+        ];
+        for (name, ret) in calls {
+            check!(
+                ret == -1,
+                "{} with pointer {:#x} returned {}",
+                name,
+                addr,
+                ret
+            );
+        }
+        // 参数数组本身合法，其中的指针非法
+        let argv = [addr, 0];
+        let path = b"echo\0";
+        check!(
+            syscall(
+                SYS_EXEC,
+                [path.as_ptr() as usize, argv.as_ptr() as usize, 0]
+            ) == -1,
+            "exec with a bad argument"
+        );
+        check!(
+            syscall(SYS_EXEC, [path.as_ptr() as usize, addr, 0]) == -1,
+            "exec with a bad argv"
+        );
+
+        let pid = fork_checked();
+        if pid == 0 {
+            exit(0);
+            fail();
+        }
+        check!(
+            syscall(SYS_WAIT, [pid as usize, addr, 0]) == -1,
+            "wait with a bad status pointer"
+        );
+    }
+    close(dir);
+    close(rfd);
+    close(wfd);
+}
+
+// 访问非法地址的进程被内核结束，退出码为 -1
+fn badaccess() {
+    for addr in BAD_ADDRS {
+        let pid = fork_checked();
+        if pid == 0 {
+            let value = unsafe { (addr as *const u8).read_volatile() };
+            eprintln!("read {} from {:#x}", value, addr);
+            exit(0);
+            fail();
+        }
+        check!(wait_for(pid) == -1, "read of {:#x} not caught", addr);
+    }
+    // 代码段只读
+    let pid = fork_checked();
+    if pid == 0 {
+        unsafe { (badaccess as usize as *mut u8).write_volatile(0) };
+        eprintln!("wrote to the code segment");
+        exit(0);
+        fail();
+    }
+    check!(wait_for(pid) == -1, "write to code not caught");
+}
+
+// 跳转到不可执行的地址只会杀死该进程
+fn badjump() {
+    let data = [0u8; 16];
+    let addrs = BAD_ADDRS.into_iter().chain([data.as_ptr() as usize]);
+    for addr in addrs {
+        let pid = fork_checked();
+        if pid == 0 {
+            let f: fn() = unsafe { core::mem::transmute(addr) };
+            f();
+            eprintln!("returned from {:#x}", addr);
+            exit(0);
+            fail();
+        }
+        check!(wait_for(pid) == -1, "jump to {:#x} not caught", addr);
+    }
+}
+
+fn sleeptest() {
+    let start = uptime();
+    check!(sleep(50) == 0, "sleep failed");
+    let elapsed = uptime() - start;
+    check!(elapsed >= 50, "slept only {} ms", elapsed);
+}
+
+// 在子进程中运行一个测试
+fn run(test: &Test) -> bool {
+    let pid = fork();
+    if pid < 0 {
+        println!("test {}: FAIL (fork failed)", test.name);
+        return false;
+    }
+    if pid == 0 {
+        (test.func)();
+        exit(0);
+        fail();
+    }
+    let mut status = 0;
+    let ok = waitpid(pid, &mut status, 0) == pid && status == 0;
+    if ok {
+        println!("test {}: PASS", test.name);
+    } else {
+        println!("test {}: FAIL (exit status {})", test.name, status);
+    }
+    ok
+}
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    let only = env::args().nth(1);
+    println!("usertests starting");
+    let (mut ran, mut failed) = (0, 0);
+    for test in TESTS {
+        if only.map_or(true, |name| name == test.name) {
+            ran += 1;
+            if !run(test) {
+                failed += 1;
+            }
+        }
+    }
+    if ran == 0 {
+        eprintln!("usertests: no test called {}", only.unwrap_or(""));
+        return 1;
+    }
+    if failed == 0 {
+        println!("ALL TESTS PASSED");
+        shutdown(0)
+    } else {
+        println!("{} of {} TESTS FAILED", failed, ran);
+        shutdown(1)
+    }
+}
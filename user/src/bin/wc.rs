@@ -0,0 +1,66 @@
+//! Count lines, words and bytes of files, or of standard input when there
+//! are none.
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user;
+
+use user::{close, env, open, read, O_RDONLY};
+
+const STDIN: usize = 0;
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\r' | b'\n' | b'\x0b')
+}
+
+fn wc(fd: usize, name: &str) -> bool {
+    let mut buf = [0u8; 512];
+    let (mut lines, mut words, mut bytes) = (0, 0, 0);
+    let mut in_word = false;
+    loop {
+        let n = read(fd, &mut buf);
+        if n == 0 {
+            break;
+        }
+        if n < 0 {
+            eprintln!("wc: read error");
+            return false;
+        }
+        for &c in &buf[..n as usize] {
+            bytes += 1;
+            if c == b'\n' {
+                lines += 1;
+            }
+            if is_space(c) {
+                in_word = false;
+            } else if !in_word {
+                words += 1;
+                in_word = true;
+            }
+        }
+    }
+    println!("{} {} {} {}", lines, words, bytes, name);
+    true
+}
+
+#[no_mangle]
+pub fn main(_argc: usize, _argv: *const *const u8) -> i32 {
+    if env::args().len() < 2 {
+        return if wc(STDIN, "") { 0 } else { 1 };
+    }
+    for path in env::args().skip(1) {
+        let fd = open(path, O_RDONLY);
+        if fd < 0 {
+            eprintln!("wc: cannot open {}", path);
+            return 1;
+        }
+        let ok = wc(fd as usize, path);
+        close(fd as usize);
+        if !ok {
+            return 1;
+        }
+    }
+    0
+}
@@ -0,0 +1,119 @@
+// 程序的参数与环境变量
+// 程序开始运行时内核把 argc、argv、envp 放在 a0-a2 中，它们指向的字符串与辅助向量位于初始用户栈上，
+// 在程序运行期间一直有效，_start 在调用 main 之前把这些指针记录在这里
+
+use core::ptr::null;
+
+pub const AT_NULL: usize = 0;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+
+// 只在 _start 中写入一次，此后只读
+static mut ARGC: usize = 0;
+static mut ARGV: *const *const u8 = null();
+static mut ENVP: *const *const u8 = null();
+
+pub(crate) unsafe fn init(argc: usize, argv: *const *const u8, envp: *const *const u8) {
+    ARGC = argc;
+    ARGV = argv;
+    ENVP = envp;
+}
+
+// exec 时原样传给新程序
+pub(crate) fn envp() -> *const *const u8 {
+    unsafe { ENVP }
+}
+
+// 以 '\0' 结尾的字符串，不是合法 UTF-8 时返回空串
+unsafe fn c_str(ptr: *const u8) -> &'static str {
+    let mut len = 0;
+    while ptr.add(len).read() != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(ptr, len)).unwrap_or("")
+}
+
+// 程序参数的迭代器，第一个参数是程序名
+pub struct Args {
+    index: usize,
+}
+
+impl Iterator for Args {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<&'static str> {
+        if self.index >= unsafe { ARGC } {
+            return None;
+        }
+        let arg = unsafe { c_str(ARGV.add(self.index).read()) };
+        self.index += 1;
+        Some(arg)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = unsafe { ARGC } - self.index;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+pub fn args() -> Args {
+    Args { index: 0 }
+}
+
+// 环境变量 (key, value) 的迭代器
+pub struct Vars {
+    next: *const *const u8,
+}
+
+impl Iterator for Vars {
+    type Item = (&'static str, &'static str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next.is_null() {
+            return None;
+        }
+        let entry = unsafe { self.next.read() };
+        if entry.is_null() {
+            return None;
+        }
+        self.next = unsafe { self.next.add(1) };
+        let entry = unsafe { c_str(entry) };
+        Some(entry.split_once('=').unwrap_or((entry, "")))
+    }
+}
+
+pub fn vars() -> Vars {
+    Vars {
+        next: unsafe { ENVP },
+    }
+}
+
+pub fn var(key: &str) -> Option<&'static str> {
+    vars().find(|&(k, _)| k == key).map(|(_, v)| v)
+}
+
+// 查找辅助向量中的一项，辅助向量在初始栈上紧跟在环境变量之后
+pub fn auxv(kind: usize) -> Option<usize> {
+    let mut p = unsafe { ENVP };
+    if p.is_null() {
+        return None;
+    }
+    unsafe {
+        while !p.read().is_null() {
+            p = p.add(1);
+        }
+        let mut aux = p.add(1) as *const usize;
+        loop {
+            let (key, value) = (aux.read(), aux.add(1).read());
+            if key == AT_NULL {
+                return None;
+            }
+            if key == kind {
+                return Some(value);
+            }
+            aux = aux.add(2);
+        }
+    }
+}
@@ -4,20 +4,26 @@
 #![allow(unused)]
 
 pub mod console;
+pub mod env;
 mod lang_items;
-mod syscall;
+pub mod sync;
+pub mod syscall;
+pub mod thread;
 
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start() -> ! {
-    let ret = main();
+pub extern "C" fn _start(argc: usize, argv: *const *const u8, envp: *const *const u8) -> ! {
+    unsafe { env::init(argc, argv, envp) };
+    let ret = main(argc, argv);
     exit(ret);
     panic!("unreachable after sys_exit!");
 }
 
+// 所有程序的 main 都声明为 main(argc, argv)，argv 中的字符串以 '\0' 结尾
+// 也可以通过更安全的 env::args()、env::vars() 访问参数与环境变量
 #[linkage = "weak"]
 #[no_mangle]
-fn main() -> i32 {
+fn main(argc: usize, argv: *const *const u8) -> i32 {
     panic!("cannot find main!");
 }
 
@@ -30,12 +36,245 @@ fn main() -> i32 {
 //         .for_each(|x| unsafe { (x as *mut u8).write_volatile(0) });
 // }
 
+use core::sync::atomic::AtomicU32;
 use syscall::*;
 
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
 
+pub fn read(fd: usize, buf: &mut [u8]) -> isize {
+    sys_read(fd, buf)
+}
+
+pub fn close(fd: usize) -> isize {
+    sys_close(fd)
+}
+
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+
+// fds[0] 为读端，fds[1] 为写端
+pub fn pipe(fds: &mut [u32; 2]) -> isize {
+    sys_pipe(fds)
+}
+
+pub const O_RDONLY: u32 = 0x000;
+pub const O_WRONLY: u32 = 0x001;
+pub const O_RDWR: u32 = 0x002;
+pub const O_CREATE: u32 = 0x200;
+pub const O_TRUNC: u32 = 0x400;
+pub const O_APPEND: u32 = 0x800;
+
+/// Longest path accepted by the wrappers below.
+pub const MAX_PATH: usize = 128;
+/// Most arguments `exec` passes, the same limit as the kernel's.
+pub const MAX_ARGS: usize = 32;
+const EXEC_STRINGS: usize = 1024;
+
+// 在 buf 中构造以 '\0' 结尾的字符串，过长时返回 None
+fn c_str(s: &str, buf: &mut [u8]) -> Option<*const u8> {
+    if s.len() >= buf.len() {
+        return None;
+    }
+    buf[..s.len()].copy_from_slice(s.as_bytes());
+    buf[s.len()] = 0;
+    Some(buf.as_ptr())
+}
+
+pub fn open(path: &str, flags: u32) -> isize {
+    let mut buf = [0u8; MAX_PATH + 1];
+    match c_str(path, &mut buf) {
+        Some(path) => sys_open(path, flags),
+        None => -1,
+    }
+}
+
+pub fn chdir(path: &str) -> isize {
+    let mut buf = [0u8; MAX_PATH + 1];
+    match c_str(path, &mut buf) {
+        Some(path) => sys_chdir(path),
+        None => -1,
+    }
+}
+
+pub fn mkdir(path: &str) -> isize {
+    let mut buf = [0u8; MAX_PATH + 1];
+    match c_str(path, &mut buf) {
+        Some(path) => sys_mkdir(path),
+        None => -1,
+    }
+}
+
+pub fn unlink(path: &str) -> isize {
+    let mut buf = [0u8; MAX_PATH + 1];
+    match c_str(path, &mut buf) {
+        Some(path) => sys_unlink(path),
+        None => -1,
+    }
+}
+
+// 为文件 old 创建新的名字 new
+pub fn link(old: &str, new: &str) -> isize {
+    let mut old_buf = [0u8; MAX_PATH + 1];
+    let mut new_buf = [0u8; MAX_PATH + 1];
+    match (c_str(old, &mut old_buf), c_str(new, &mut new_buf)) {
+        (Some(old), Some(new)) => sys_link(old, new),
+        _ => -1,
+    }
+}
+
+pub const T_DIR: u16 = 1;
+pub const T_FILE: u16 = 2;
+pub const T_DEVICE: u16 = 3;
+
+/// File status filled in by `fstat`, the same layout as xv6's.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Stat {
+    pub dev: u32,
+    pub ino: u32,
+    pub kind: u16,
+    pub nlink: u16,
+    pub size: u64,
+}
+
+pub fn fstat(fd: usize, stat: &mut Stat) -> isize {
+    sys_fstat(fd, stat as *mut Stat as *mut u8)
+}
+
+// 打开 path 读取其状态
+pub fn stat(path: &str, stat: &mut Stat) -> isize {
+    let fd = open(path, O_RDONLY);
+    if fd < 0 {
+        return -1;
+    }
+    let ret = fstat(fd as usize, stat);
+    close(fd as usize);
+    ret
+}
+
+/// Longest name in a directory.
+pub const DIRSIZ: usize = 14;
+
+/// A directory reads as an array of these, entries with `ino` 0 are unused.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Dirent {
+    pub ino: u16,
+    pub name: [u8; DIRSIZ],
+}
+
+impl Dirent {
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(DIRSIZ);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+
+    // 从目录 fd 读取下一项，到达末尾时返回 None
+    pub fn read(fd: usize) -> Option<Dirent> {
+        let mut bytes = [0u8; core::mem::size_of::<Dirent>()];
+        if read(fd, &mut bytes) != bytes.len() as isize {
+            return None;
+        }
+        let mut name = [0u8; DIRSIZ];
+        name.copy_from_slice(&bytes[2..]);
+        Some(Dirent {
+            ino: u16::from_ne_bytes([bytes[0], bytes[1]]),
+            name,
+        })
+    }
+}
+
+pub fn fork() -> isize {
+    sys_fork()
+}
+
+/// Run the program `path` in place of the current one with arguments
+/// `args`, conventionally starting with its name. The environment is
+/// inherited. Only returns, with -1, on failure.
+pub fn exec(path: &str, args: &[&str]) -> isize {
+    let mut path_buf = [0u8; MAX_PATH + 1];
+    let mut strings = [0u8; EXEC_STRINGS];
+    let mut argv = [core::ptr::null::<u8>(); MAX_ARGS + 1];
+    let path = match c_str(path, &mut path_buf) {
+        Some(path) => path,
+        None => return -1,
+    };
+    if args.len() > MAX_ARGS {
+        return -1;
+    }
+    let mut used = 0;
+    for (i, arg) in args.iter().enumerate() {
+        argv[i] = match c_str(arg, &mut strings[used..]) {
+            Some(arg) => arg,
+            None => return -1,
+        };
+        used += arg.len() + 1;
+    }
+    sys_exec(path, argv.as_ptr(), env::envp())
+}
+
+/// `waitpid` option: return 0 instead of blocking when no child has exited.
+pub const WNOHANG: usize = 1;
+
+// 等待任意子进程结束，返回其进程号，退出码存入 status
+pub fn wait(status: &mut i32) -> isize {
+    sys_waitpid(-1, status, 0)
+}
+
+// pid 为 -1 时等待任意子进程
+pub fn waitpid(pid: isize, status: &mut i32, options: usize) -> isize {
+    sys_waitpid(pid, status, options)
+}
+
+pub fn getpid() -> isize {
+    sys_getpid()
+}
+
+pub fn kill(pid: usize) -> isize {
+    sys_kill(pid)
+}
+
+// 堆增长 increment 字节，为负时收缩，返回原来的堆顶，失败时返回 -1
+pub fn sbrk(increment: isize) -> isize {
+    sys_sbrk(increment)
+}
+
 pub fn exit(exit_code: i32) -> isize {
     sys_exit(exit_code)
 }
+
+pub fn sleep(ms: usize) -> isize {
+    sys_sleep(ms)
+}
+
+pub fn uptime() -> isize {
+    sys_uptime()
+}
+
+pub fn shutdown(code: u32) -> ! {
+    sys_shutdown(code);
+    panic!("unreachable after sys_shutdown!");
+}
+
+pub fn set_priority(priority: isize) -> isize {
+    sys_set_priority(priority)
+}
+
+pub const FUTEX_EAGAIN: isize = -2;
+pub const FUTEX_ETIMEDOUT: isize = -3;
+
+// timeout 以毫秒为单位，None 表示一直等待
+pub fn futex_wait(word: &AtomicU32, expected: u32, timeout: Option<usize>) -> isize {
+    sys_futex_wait(
+        word as *const AtomicU32 as *const u32,
+        expected,
+        timeout.unwrap_or(0),
+    )
+}
+
+pub fn futex_wake(word: &AtomicU32, n: usize) -> isize {
+    sys_futex_wake(word as *const AtomicU32 as *const u32, n)
+}
@@ -0,0 +1,166 @@
+//! Blocking synchronization primitives built on futexes
+//!
+//! The fast paths are plain atomic operations; the kernel is only entered
+//! when a thread actually has to wait or somebody is waiting.
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{futex_wait, futex_wake, FUTEX_ETIMEDOUT};
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+// 已上锁且可能有线程在等待，解锁时需要唤醒
+const CONTENDED: u32 = 2;
+
+pub struct Mutex<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for Mutex<T> {}
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // 拿到锁时无法确定是否还有其他等待者，保守地标记为 CONTENDED
+            while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+                futex_wait(&self.state, CONTENDED, None);
+            }
+        }
+        MutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { mutex: self })
+    }
+
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+// 条件变量：每次通知递增序号，等待者在序号改变前睡眠
+pub struct Condvar {
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.wait_timeout(guard, None).0
+    }
+
+    // 返回值中的 bool 表示是否超时
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Option<usize>,
+    ) -> (MutexGuard<'a, T>, bool) {
+        let mutex = guard.mutex;
+        // 在释放锁之前读取序号，之后的通知都会使 futex_wait 立即返回
+        let seq = self.seq.load(Ordering::Relaxed);
+        drop(guard);
+        let timed_out = futex_wait(&self.seq, seq, timeout) == FUTEX_ETIMEDOUT;
+        (mutex.lock(), timed_out)
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex_wake(&self.seq, 1);
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        futex_wake(&self.seq, usize::MAX);
+    }
+}
+
+// 计数信号量
+pub struct Semaphore {
+    count: AtomicU32,
+}
+
+impl Semaphore {
+    pub const fn new(count: u32) -> Self {
+        Self {
+            count: AtomicU32::new(count),
+        }
+    }
+
+    pub fn acquire(&self) {
+        loop {
+            let count = self.count.load(Ordering::Relaxed);
+            if count == 0 {
+                futex_wait(&self.count, 0, None);
+            } else if self
+                .count
+                .compare_exchange(count, count - 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                return;
+            }
+        }
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        let count = self.count.load(Ordering::Relaxed);
+        count > 0
+            && self
+                .count
+                .compare_exchange(count, count - 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    pub fn release(&self) {
+        self.count.fetch_add(1, Ordering::Release);
+        futex_wake(&self.count, 1);
+    }
+}
@@ -21,8 +21,18 @@ pub const SYS_UNLINK: usize = 18;
 pub const SYS_LINK: usize = 19;
 pub const SYS_MKDIR: usize = 20;
 pub const SYS_CLOSE: usize = 21;
+pub const SYS_SETPRIORITY: usize = 22;
+pub const SYS_SHUTDOWN: usize = 23;
+pub const SYS_FUTEX_WAIT: usize = 24;
+pub const SYS_FUTEX_WAKE: usize = 25;
+pub const SYS_CLONE: usize = 26;
+pub const SYS_JOIN: usize = 27;
 
-fn syscall(id: usize, args: [usize; 3]) -> isize {
+pub const CLONE_VM: usize = 0x100;
+
+/// Raw system call, for programs such as `usertests` that need to pass
+/// arguments the wrappers would not.
+pub fn syscall(id: usize, args: [usize; 3]) -> isize {
     let mut ret: isize;
 
     unsafe {
@@ -37,6 +47,70 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+pub fn sys_fork() -> isize {
+    syscall(SYS_FORK, [0, 0, 0])
+}
+
+pub fn sys_exec(path: *const u8, argv: *const *const u8, envp: *const *const u8) -> isize {
+    syscall(SYS_EXEC, [path as usize, argv as usize, envp as usize])
+}
+
+pub fn sys_waitpid(pid: isize, status: *mut i32, options: usize) -> isize {
+    syscall(SYS_WAIT, [pid as usize, status as usize, options])
+}
+
+pub fn sys_getpid() -> isize {
+    syscall(SYS_GETPID, [0, 0, 0])
+}
+
+pub fn sys_pipe(fds: &mut [u32; 2]) -> isize {
+    syscall(SYS_PIPE, [fds.as_mut_ptr() as usize, 0, 0])
+}
+
+pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(SYS_READ, [fd, buffer.as_mut_ptr() as usize, buffer.len()])
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    syscall(SYS_CLOSE, [fd, 0, 0])
+}
+
+pub fn sys_dup(fd: usize) -> isize {
+    syscall(SYS_DUP, [fd, 0, 0])
+}
+
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    syscall(SYS_OPEN, [path as usize, flags as usize, 0])
+}
+
+pub fn sys_chdir(path: *const u8) -> isize {
+    syscall(SYS_CHDIR, [path as usize, 0, 0])
+}
+
+pub fn sys_fstat(fd: usize, stat: *mut u8) -> isize {
+    syscall(SYS_FSTAT, [fd, stat as usize, 0])
+}
+
+pub fn sys_mkdir(path: *const u8) -> isize {
+    syscall(SYS_MKDIR, [path as usize, 0, 0])
+}
+
+pub fn sys_unlink(path: *const u8) -> isize {
+    syscall(SYS_UNLINK, [path as usize, 0, 0])
+}
+
+pub fn sys_link(old: *const u8, new: *const u8) -> isize {
+    syscall(SYS_LINK, [old as usize, new as usize, 0])
+}
+
+pub fn sys_sbrk(increment: isize) -> isize {
+    syscall(SYS_SBRK, [increment as usize, 0, 0])
+}
+
+pub fn sys_kill(pid: usize) -> isize {
+    syscall(SYS_KILL, [pid, 0, 0])
+}
+
 pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYS_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
@@ -44,3 +118,52 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
 pub fn sys_exit(exit_code: i32) -> isize {
     syscall(SYS_EXIT, [exit_code as usize, 0, 0])
 }
+
+pub fn sys_sleep(ms: usize) -> isize {
+    syscall(SYS_SLEEP, [ms, 0, 0])
+}
+
+pub fn sys_uptime() -> isize {
+    syscall(SYS_UPTIME, [0, 0, 0])
+}
+
+pub fn sys_shutdown(code: u32) -> isize {
+    syscall(SYS_SHUTDOWN, [code as usize, 0, 0])
+}
+
+pub fn sys_set_priority(priority: isize) -> isize {
+    syscall(SYS_SETPRIORITY, [priority as usize, 0, 0])
+}
+
+pub fn sys_futex_wait(addr: *const u32, expected: u32, timeout: usize) -> isize {
+    syscall(SYS_FUTEX_WAIT, [addr as usize, expected as usize, timeout])
+}
+
+// 子线程从 ecall 返回 0 后不能回到调用者的栈帧，
+// 而是带着 a4 中的参数直接跳转到 a3 中的入口，入口函数不能返回
+pub fn sys_clone(stack: usize, flags: usize, entry: usize, arg: usize) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            "bnez a0, 1f",
+            "mv a0, a4",
+            "jr a3",
+            "1:",
+            inlateout("x10") stack => ret,
+            in("x11") flags,
+            in("x13") entry,
+            in("x14") arg,
+            in("x17") SYS_CLONE
+        );
+    }
+    ret
+}
+
+pub fn sys_join(tid: usize) -> isize {
+    syscall(SYS_JOIN, [tid, 0, 0])
+}
+
+pub fn sys_futex_wake(addr: *const u32, n: usize) -> isize {
+    syscall(SYS_FUTEX_WAKE, [addr as usize, n, 0])
+}
@@ -0,0 +1,85 @@
+//! Threads sharing the address space of the program
+//!
+//! There is no heap in user space, so thread stacks come from a fixed pool
+//! and the closure is moved to the top of the new thread's stack, from where
+//! the thread picks it up.
+
+use core::mem::{align_of, size_of};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::exit;
+use crate::syscall::{sys_clone, sys_join, CLONE_VM};
+
+const MAX_THREADS: usize = 8;
+const THREAD_STACK_SIZE: usize = 0x4000;
+
+#[repr(C, align(16))]
+struct Stack([u8; THREAD_STACK_SIZE]);
+
+const STACK_INIT: Stack = Stack([0; THREAD_STACK_SIZE]);
+static mut STACKS: [Stack; MAX_THREADS] = [STACK_INIT; MAX_THREADS];
+
+const FREE: AtomicBool = AtomicBool::new(false);
+static STACK_USED: [AtomicBool; MAX_THREADS] = [FREE; MAX_THREADS];
+
+pub struct JoinHandle {
+    tid: usize,
+    stack: usize, // 栈在池中的编号
+}
+
+impl JoinHandle {
+    pub fn tid(&self) -> usize {
+        self.tid
+    }
+
+    // 等待线程结束，返回其退出码
+    pub fn join(self) -> i32 {
+        let exit_code = sys_join(self.tid);
+        STACK_USED[self.stack].store(false, Ordering::Release);
+        exit_code as i32
+    }
+}
+
+fn alloc_stack() -> Option<usize> {
+    (0..MAX_THREADS).find(|&i| {
+        STACK_USED[i]
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    })
+}
+
+extern "C" fn thread_start<F: FnOnce() -> i32>(f: *mut F) -> ! {
+    let f = unsafe { ptr::read(f) };
+    exit(f());
+    unreachable!("thread exited");
+}
+
+// 创建线程执行 f，f 的返回值作为线程的退出码
+pub fn spawn<F>(f: F) -> JoinHandle
+where
+    F: FnOnce() -> i32 + Send + 'static,
+{
+    let stack = alloc_stack().expect("too many threads");
+    let top = unsafe { STACKS[stack].0.as_ptr() as usize + THREAD_STACK_SIZE };
+
+    // 闭包放在栈顶，线程从其下方开始使用栈
+    let f_addr = (top - size_of::<F>()) & !(align_of::<F>() - 1);
+    unsafe {
+        ptr::write(f_addr as *mut F, f);
+    }
+    let sp = f_addr & !15;
+
+    let tid = sys_clone(sp, CLONE_VM, thread_start::<F> as usize, f_addr);
+    if tid < 0 {
+        unsafe {
+            drop(ptr::read(f_addr as *mut F));
+        }
+        STACK_USED[stack].store(false, Ordering::Release);
+        panic!("spawn thread failed");
+    }
+    JoinHandle {
+        tid: tid as usize,
+        stack,
+    }
+}
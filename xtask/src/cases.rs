@@ -0,0 +1,74 @@
+//! Test programs run by `cargo xtask test`
+//!
+//! The kernel boots into `init`, which starts `sh` on the serial console.
+//! A case types its commands into the shell, waiting for the prompt before
+//! each one, then looks for its patterns in the serial output. The console
+//! echoes what is typed, so a pattern must not be part of a command.
+
+/// What ends a passing case.
+pub enum Finish {
+    /// The guest powers off through `shutdown` with this code.
+    Exit(u32),
+    /// The guest keeps running, the case passes once all patterns are seen.
+    Running,
+}
+
+pub struct Case {
+    pub name: &'static str,
+    /// Lines typed into the shell.
+    pub input: &'static [&'static str],
+    /// Must appear in the serial output in this order.
+    pub expect: &'static [&'static str],
+    pub finish: Finish,
+    /// Seconds allowed from boot to the end of the case.
+    pub timeout: u64,
+}
+
+/// Printed by `sh` before reading each line.
+pub const PROMPT: &str = "$ ";
+
+/// Any of these in the output fails the case at once.
+pub const FATAL: &[&str] = &["Panicked at", "Panicked:"];
+
+pub const CASES: &[Case] = &[
+    Case {
+        name: "boot",
+        input: &[],
+        expect: &["[kernel] board:", "init: starting sh", PROMPT],
+        finish: Finish::Running,
+        timeout: 30,
+    },
+    Case {
+        name: "pipeline",
+        input: &["echo one two three | wc", "echo abc | grep ^a.c | wc"],
+        expect: &["1 3 14", "1 1 4"],
+        finish: Finish::Running,
+        timeout: 30,
+    },
+    Case {
+        name: "files",
+        input: &[
+            "mkdir tmp",
+            "echo first > tmp/f",
+            "echo second >> tmp/f",
+            "ln tmp/f tmp/g",
+            "rm tmp/f",
+            "wc < tmp/g",
+            "cat tmp/g",
+        ],
+        expect: &["2 2 13", "first\nsecond"],
+        finish: Finish::Running,
+        timeout: 30,
+    },
+    Case {
+        name: "usertests",
+        input: &["usertests"],
+        expect: &["usertests starting", "ALL TESTS PASSED"],
+        finish: Finish::Exit(0),
+        timeout: 300,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Case> {
+    CASES.iter().find(|case| case.name == name)
+}
@@ -0,0 +1,367 @@
+//! Build and test tasks run on the host, `cargo xtask help` lists them
+//!
+//! `cargo xtask test` builds the kernel and the user programs, boots each
+//! test case in a fresh QEMU and checks the serial output and the exit code
+//! the guest reports through the `sifive_test` device. It exits with a
+//! nonzero status when any case fails, so it can drive unattended runs.
+
+mod cases;
+mod qemu;
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+    time::{Duration, Instant},
+};
+
+use cases::{Case, Finish, CASES, FATAL, PROMPT};
+use qemu::{Machine, Outcome, Qemu};
+
+const USAGE: &str = "\
+usage: cargo xtask <task>
+
+tasks:
+    test [options] [case...]   build the kernel and run the test cases, all by default
+    list                       list the test cases
+    help                       show this message
+
+options of test:
+    --board <virt|sifive_u>    machine to boot, virt by default
+    --smp <n>                  number of harts, 4 by default
+    --mem <size>               memory of the machine, 128M by default
+    --timeout <seconds>        override the timeout of every case
+    --no-build                 use the kernel image that was built last
+    --verbose                  print the serial output of passing cases too
+
+The guest reports its exit code through the sifive_test device on both boards.";
+
+struct Options {
+    machine: Machine,
+    timeout: Option<u64>,
+    build: bool,
+    verbose: bool,
+    cases: Vec<&'static Case>,
+}
+
+fn parse_test_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        machine: Machine {
+            board: "virt".to_string(),
+            smp: 4,
+            mem: "128M".to_string(),
+        },
+        timeout: None,
+        build: true,
+        verbose: false,
+        cases: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--board" => {
+                let board = value("--board")?;
+                if board != "virt" && board != "sifive_u" {
+                    return Err(format!("unknown board {}", board));
+                }
+                options.machine.board = board;
+            }
+            "--smp" => {
+                let smp = value("--smp")?;
+                options.machine.smp = smp
+                    .parse()
+                    .map_err(|_| format!("bad number of harts {}", smp))?;
+            }
+            "--mem" => options.machine.mem = value("--mem")?,
+            "--timeout" => {
+                let timeout = value("--timeout")?;
+                let timeout = timeout
+                    .parse()
+                    .map_err(|_| format!("bad timeout {}", timeout))?;
+                options.timeout = Some(timeout);
+            }
+            "--no-build" => options.build = false,
+            "--verbose" => options.verbose = true,
+            name if name.starts_with("--") => return Err(format!("unknown option {}", name)),
+            name => {
+                let case = cases::find(name).ok_or(format!("no test case called {}", name))?;
+                options.cases.push(case);
+            }
+        }
+    }
+    if options.cases.is_empty() {
+        options.cases = CASES.iter().collect();
+    }
+    Ok(options)
+}
+
+// 仓库根目录，即 xtask 的上一级
+fn root_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+// 与 `make kernel-bin` 相同，用户程序会先被编译并嵌入内核
+fn build(root: &Path, board: &str) -> Result<PathBuf, String> {
+    let status = Command::new("make")
+        .arg("-C")
+        .arg(root.join("kernel"))
+        .arg("kernel-bin")
+        .arg(format!("BOARD={}", board))
+        .status()
+        .map_err(|err| format!("cannot run make: {}", err))?;
+    if !status.success() {
+        return Err(format!("building the kernel failed: {}", status));
+    }
+    Ok(kernel_bin(root))
+}
+
+fn kernel_bin(root: &Path) -> PathBuf {
+    root.join("kernel/target/riscv64gc-unknown-none-elf/release/kernel.bin")
+}
+
+fn find_from(log: &[u8], from: usize, pattern: &str) -> Option<usize> {
+    let pattern = pattern.as_bytes();
+    // windows(0) 会 panic，空模式出现在任何位置
+    if pattern.is_empty() {
+        return (from <= log.len()).then_some(from);
+    }
+    log.get(from..)?
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|i| from + i)
+}
+
+fn find_fatal(log: &[u8]) -> Option<&'static str> {
+    FATAL
+        .iter()
+        .copied()
+        .find(|pattern| find_from(log, 0, pattern).is_some())
+}
+
+// 按顺序匹配，返回第一个找不到的模式
+fn first_missing(log: &[u8], expect: &[&'static str]) -> Option<&'static str> {
+    let mut pos = 0;
+    for &pattern in expect {
+        match find_from(log, pos, pattern) {
+            Some(i) => pos = i + pattern.len(),
+            None => return Some(pattern),
+        }
+    }
+    None
+}
+
+fn describe(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Exited(code) => format!("guest exited with code {}", code),
+        Outcome::Crashed(status) => format!("QEMU was killed: {}", status),
+        Outcome::TimedOut => "timed out".to_string(),
+    }
+}
+
+/// Boot a fresh machine and run `case`, returns why it failed.
+fn run_case(case: &Case, qemu: &mut Qemu, timeout: u64) -> Result<(), String> {
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    let mut pos = 0;
+    for line in case.input {
+        // 等 sh 打印提示符后再输入下一行
+        let mut prompt = None;
+        let outcome = qemu.wait_until(deadline, |log| {
+            prompt = find_from(log, pos, PROMPT);
+            prompt.is_some() || find_fatal(log).is_some()
+        });
+        if let Some(pattern) = find_fatal(&qemu.log) {
+            return Err(format!("found \"{}\"", pattern));
+        }
+        if let Some(outcome) = outcome {
+            return Err(format!("{} before typing \"{}\"", describe(outcome), line));
+        }
+        pos = prompt.unwrap() + PROMPT.len();
+        qemu.send_line(line);
+    }
+
+    let outcome = match case.finish {
+        Finish::Exit(_) => qemu.wait_until(deadline, |log| find_fatal(log).is_some()),
+        Finish::Running => qemu.wait_until(deadline, |log| {
+            find_fatal(log).is_some() || first_missing(log, case.expect).is_none()
+        }),
+    };
+    if let Some(pattern) = find_fatal(&qemu.log) {
+        return Err(format!("found \"{}\"", pattern));
+    }
+    if let Some(pattern) = first_missing(&qemu.log, case.expect) {
+        let reason = outcome.map_or("output ended".to_string(), describe);
+        return Err(format!("{} without printing \"{}\"", reason, pattern));
+    }
+    match (&case.finish, outcome) {
+        (Finish::Running, None) => Ok(()),
+        (Finish::Exit(expected), Some(Outcome::Exited(code))) if code == *expected => Ok(()),
+        (Finish::Exit(expected), Some(outcome)) => Err(format!(
+            "{}, expected exit code {}",
+            describe(outcome),
+            expected
+        )),
+        (_, outcome) => Err(outcome.map_or("guest did not exit".to_string(), describe)),
+    }
+}
+
+fn test(options: Options) -> Result<bool, String> {
+    let root = root_dir();
+    let kernel_bin = if options.build {
+        build(&root, &options.machine.board)?
+    } else {
+        kernel_bin(&root)
+    };
+    let log_dir = root.join("target/xtask");
+    fs::create_dir_all(&log_dir).map_err(|err| format!("cannot create {:?}: {}", log_dir, err))?;
+
+    let mut failed = Vec::new();
+    for case in &options.cases {
+        let timeout = options.timeout.unwrap_or(case.timeout);
+        let started = Instant::now();
+        let mut qemu = Qemu::boot(&root, &kernel_bin, &options.machine)
+            .map_err(|err| format!("cannot start QEMU: {}", err))?;
+        let result = run_case(case, &mut qemu, timeout);
+        qemu.kill();
+
+        let log_file = log_dir.join(format!("{}.log", case.name));
+        fs::write(&log_file, &qemu.log)
+            .map_err(|err| format!("cannot write {:?}: {}", log_file, err))?;
+        let elapsed = started.elapsed().as_secs_f32();
+        match result {
+            Ok(()) => {
+                println!("test {} ... ok ({:.1}s)", case.name, elapsed);
+                if options.verbose {
+                    println!("{}", String::from_utf8_lossy(&qemu.log));
+                }
+            }
+            Err(reason) => {
+                println!(
+                    "test {} ... FAILED ({:.1}s): {}",
+                    case.name, elapsed, reason
+                );
+                println!("---- serial output of {} ----", case.name);
+                println!("{}", String::from_utf8_lossy(&qemu.log));
+                println!("---- saved to {} ----", log_file.display());
+                failed.push(case.name);
+            }
+        }
+    }
+
+    println!();
+    if failed.is_empty() {
+        println!("test result: ok, {} passed", options.cases.len());
+    } else {
+        println!(
+            "test result: FAILED, {} passed, {} failed: {}",
+            options.cases.len() - failed.len(),
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(failed.is_empty())
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("test") => parse_test_args(args).and_then(test),
+        Some("list") => {
+            for case in CASES {
+                println!("{}", case.name);
+            }
+            Ok(true)
+        }
+        Some("help") | None => {
+            println!("{}", USAGE);
+            Ok(true)
+        }
+        Some(task) => Err(format!("unknown task {}\n\n{}", task, USAGE)),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("xtask: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn find_from_starts_at_offset() {
+        let log = b"abc abc";
+        assert_eq!(find_from(log, 0, "abc"), Some(0));
+        assert_eq!(find_from(log, 1, "abc"), Some(4));
+        assert_eq!(find_from(log, 5, "abc"), None);
+        assert_eq!(find_from(log, 100, "abc"), None);
+        assert_eq!(find_from(log, 7, ""), Some(7));
+        assert_eq!(find_from(log, 8, ""), None);
+    }
+
+    #[test]
+    fn first_missing_matches_in_order() {
+        let log = b"init: starting sh\n$ ";
+        assert_eq!(first_missing(log, &["init:", "$ "]), None);
+        assert_eq!(first_missing(log, &["$ ", "init:"]), Some("init:"));
+        assert_eq!(first_missing(log, &["sh", "sh"]), Some("sh"));
+        assert_eq!(first_missing(log, &[]), None);
+    }
+
+    #[test]
+    fn parse_test_args_defaults_to_every_case() {
+        let options = parse_test_args(args(&[])).unwrap();
+        assert_eq!(options.machine.board, "virt");
+        assert_eq!(options.machine.smp, 4);
+        assert_eq!(options.timeout, None);
+        assert!(options.build && !options.verbose);
+        assert_eq!(options.cases.len(), CASES.len());
+    }
+
+    #[test]
+    fn parse_test_args_reads_options_and_cases() {
+        let options = parse_test_args(args(&[
+            "--board",
+            "sifive_u",
+            "--smp",
+            "2",
+            "--mem",
+            "256M",
+            "--timeout",
+            "30",
+            "--no-build",
+            "--verbose",
+            "usertests",
+        ]))
+        .unwrap();
+        assert_eq!(options.machine.board, "sifive_u");
+        assert_eq!(options.machine.smp, 2);
+        assert_eq!(options.machine.mem, "256M");
+        assert_eq!(options.timeout, Some(30));
+        assert!(!options.build && options.verbose);
+        let names: Vec<_> = options.cases.iter().map(|case| case.name).collect();
+        assert_eq!(names, ["usertests"]);
+    }
+
+    #[test]
+    fn parse_test_args_rejects_bad_input() {
+        assert!(parse_test_args(args(&["--board", "spike"])).is_err());
+        assert!(parse_test_args(args(&["--smp", "many"])).is_err());
+        assert!(parse_test_args(args(&["--timeout"])).is_err());
+        assert!(parse_test_args(args(&["--fast"])).is_err());
+        assert!(parse_test_args(args(&["no-such-case"])).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Booting the kernel in QEMU with the serial console on a pipe
+
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Instant,
+};
+
+/// Where the kernel image is loaded, as in kernel/Makefile.
+const KERNEL_ENTRY_PA: &str = "0x80200000";
+
+pub struct Machine {
+    pub board: String,
+    pub smp: usize,
+    pub mem: String,
+}
+
+impl Machine {
+    fn bios(&self, root: &Path) -> String {
+        // rustsbi-qemu 只支持 virt，sifive_u 使用 QEMU 自带的 OpenSBI
+        if self.board == "virt" {
+            root.join("bootloader/rustsbi-qemu.bin")
+                .display()
+                .to_string()
+        } else {
+            "default".to_string()
+        }
+    }
+
+    // QEMU 自带的 OpenSBI 跳转到 -kernel 加载的内核，rustsbi-qemu 固定跳转到 KERNEL_ENTRY_PA
+    fn load_kernel(&self, kernel_bin: &Path) -> [String; 2] {
+        if self.board == "virt" {
+            [
+                "-device".to_string(),
+                format!(
+                    "loader,file={},addr={}",
+                    kernel_bin.display(),
+                    KERNEL_ENTRY_PA
+                ),
+            ]
+        } else {
+            ["-kernel".to_string(), kernel_bin.display().to_string()]
+        }
+    }
+}
+
+/// How a run of QEMU ended.
+pub enum Outcome {
+    /// The guest powered off, with the code it passed to `shutdown`.
+    Exited(u32),
+    /// QEMU died for another reason, e.g. it could not start.
+    Crashed(ExitStatus),
+    TimedOut,
+}
+
+/// A running QEMU and everything it has written to the serial console.
+pub struct Qemu {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    output: Receiver<Vec<u8>>,
+    pub log: Vec<u8>,
+}
+
+impl Qemu {
+    pub fn boot(root: &Path, kernel_bin: &Path, machine: &Machine) -> io::Result<Self> {
+        let mut child = Command::new("qemu-system-riscv64")
+            .arg("-m")
+            .arg(&machine.mem)
+            .arg("-machine")
+            .arg(&machine.board)
+            .arg("-smp")
+            .arg(machine.smp.to_string())
+            .arg("-nographic")
+            .arg("-bios")
+            .arg(machine.bios(root))
+            .args(machine.load_kernel(kernel_bin))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let mut stdout = child.stdout.take().unwrap();
+        // 单独的线程读取串口，主线程才能按超时等待
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match stdout.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if sender.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            child,
+            stdin,
+            output,
+            log: Vec::new(),
+        })
+    }
+
+    /// Type `line` into the serial console.
+    pub fn send_line(&mut self, line: &str) {
+        // QEMU 可能已经退出，结果由 wait_until() 报告
+        if let Some(stdin) = self.stdin.as_mut() {
+            let _ = stdin.write_all(line.as_bytes());
+            let _ = stdin.write_all(b"\r");
+            let _ = stdin.flush();
+        }
+    }
+
+    /// Collect output until `done` holds for the log, QEMU exits or
+    /// `deadline` passes. Returns None only in the first case.
+    pub fn wait_until(
+        &mut self,
+        deadline: Instant,
+        mut done: impl FnMut(&[u8]) -> bool,
+    ) -> Option<Outcome> {
+        loop {
+            if done(&self.log) {
+                return None;
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.output.recv_timeout(timeout) {
+                Ok(bytes) => self.log.extend_from_slice(&bytes),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.kill();
+                    return Some(Outcome::TimedOut);
+                }
+                // 串口关闭说明 QEMU 已退出
+                Err(RecvTimeoutError::Disconnected) => return Some(self.exit_outcome()),
+            }
+        }
+    }
+
+    fn exit_outcome(&mut self) -> Outcome {
+        match self.child.wait() {
+            Ok(status) => decode_exit(status),
+            Err(_) => Outcome::TimedOut,
+        }
+    }
+
+    pub fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for Qemu {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            self.kill();
+        }
+    }
+}
+
+// sifive_test 写入 FINISHER_PASS 时 QEMU 以 0 退出，写入 (code << 16) |
+// FINISHER_FAIL 时以 code 退出，见 kernel/src/board/qemu_exit.rs。被信号
+// 结束的 QEMU 没有退出码。
+fn decode_exit(status: ExitStatus) -> Outcome {
+    match status.code() {
+        Some(code) if code >= 0 => Outcome::Exited(code as u32),
+        _ => Outcome::Crashed(status),
+    }
+}